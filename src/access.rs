@@ -0,0 +1,185 @@
+#![allow(dead_code)]
+
+use std::fmt;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+
+/// Times the salted code is hashed over, to slow down guessing.
+const HASH_ROUNDS: u32 = 10_000;
+const SALT_LENGTH: usize = 16;
+
+/// What a member of a farm is allowed to do on it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    Owner,
+    Agronomist,
+    Viewer,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Role::Owner => write!(f, "owner"),
+            Role::Agronomist => write!(f, "agronomist"),
+            Role::Viewer => write!(f, "viewer"),
+        }
+    }
+}
+
+/// Operations on a farm that are checked against the role of the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    ViewReport,
+    RunSimulation,
+    EditPlan,
+    /// Changing the farm details, its members or deactivating it
+    ManageFarm,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operation::ViewReport => write!(f, "view reports"),
+            Operation::RunSimulation => write!(f, "run simulations"),
+            Operation::EditPlan => write!(f, "edit plans"),
+            Operation::ManageFarm => write!(f, "manage the farm"),
+        }
+    }
+}
+
+impl Role {
+    pub fn allows(&self, operation: Operation) -> bool {
+        match self {
+            Role::Owner => true,
+            Role::Agronomist => operation != Operation::ManageFarm,
+            Role::Viewer => operation == Operation::ViewReport,
+        }
+    }
+}
+
+/// A user asking for access to a farm: their email and the farm's security code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub email: String,
+    pub security_code: String,
+}
+
+impl Credentials {
+    pub fn new(email: &str, security_code: &str) -> Self {
+        Self {
+            email: email.to_string(),
+            security_code: security_code.to_string(),
+        }
+    }
+}
+
+/// Security code of a farm, kept as a salted SHA-256 hash. Salt and hash are hex encoded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SecurityCode {
+    salt: String,
+    hash: String,
+}
+
+impl SecurityCode {
+    /// Hashes `code` with a new random salt.
+    pub fn new(code: &str) -> Self {
+        let salt: [u8; SALT_LENGTH] = thread_rng().gen();
+        let salt = to_hex(&salt);
+        let hash = Self::hash(&salt, code);
+        Self { salt, hash }
+    }
+
+    pub fn verify(&self, code: &str) -> bool {
+        let hash = Self::hash(&self.salt, code);
+        // Compare every byte so the time taken does not tell how much of the code was right.
+        hash.len() == self.hash.len()
+            && hash.bytes().zip(self.hash.bytes()).fold(0, |diff, (first, second)| diff | (first ^ second)) == 0
+    }
+
+    fn hash(salt: &str, code: &str) -> String {
+        let mut digest = Sha256::new().chain_update(salt).chain_update(code).finalize();
+        for _ in 1..HASH_ROUNDS {
+            digest = Sha256::new().chain_update(salt).chain_update(digest).finalize();
+        }
+        to_hex(&digest)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::AgroException;
+    use crate::setup::demo_record;
+
+    const OPERATIONS: [Operation; 4] = [Operation::ViewReport, Operation::RunSimulation, Operation::EditPlan, Operation::ManageFarm];
+
+    #[test]
+    fn roles_allow_their_operations_only() {
+        let allowed = |role: Role| OPERATIONS.map(|operation| role.allows(operation));
+
+        assert_eq!(allowed(Role::Owner), [true, true, true, true]);
+        assert_eq!(allowed(Role::Agronomist), [true, true, true, false]);
+        assert_eq!(allowed(Role::Viewer), [true, false, false, false]);
+    }
+
+    #[test]
+    fn only_the_right_code_verifies() {
+        let code = SecurityCode::new("Zsx12-00-RSA");
+
+        assert!(code.verify("Zsx12-00-RSA"));
+        for wrong in ["", "Zsx12-00-RS", "Zsx12-00-RSA ", "zsx12-00-rsa", "Zsx12-00-RSB"] {
+            assert!(!code.verify(wrong), "{:?} verified", wrong);
+        }
+    }
+
+    #[test]
+    fn the_same_code_hashes_differently_under_different_salts() {
+        let first = SecurityCode::new("Zsx12-00-RSA");
+        let second = SecurityCode::new("Zsx12-00-RSA");
+
+        assert_ne!(first.salt, second.salt);
+        assert_ne!(first.hash, second.hash);
+        assert!(first.verify("Zsx12-00-RSA") && second.verify("Zsx12-00-RSA"));
+        // Hex encoded salt and SHA-256 digest.
+        assert_eq!((first.salt.len(), first.hash.len()), (2 * SALT_LENGTH, 64));
+        // The hash is iterated, so it is not the digest of the salted code.
+        let single = to_hex(&Sha256::new().chain_update(&first.salt).chain_update("Zsx12-00-RSA").finalize());
+        assert_ne!(first.hash, single);
+        assert_eq!(SecurityCode::hash(&first.salt, "Zsx12-00-RSA"), first.hash);
+    }
+
+    #[test]
+    fn farm_members_are_checked_against_their_role() {
+        let mut record = demo_record();
+        record.members.insert("agronomist@example.com".to_string(), Role::Agronomist);
+        record.members.insert("viewer@example.com".to_string(), Role::Viewer);
+        let code = "Zsx12-00-RSA";
+
+        let members = [
+            (record.owner.email.to_string(), Role::Owner),
+            // Emails are matched whatever their case.
+            ("Agronomist@Example.com".to_string(), Role::Agronomist),
+            ("viewer@example.com".to_string(), Role::Viewer),
+        ];
+        for (email, role) in members {
+            for operation in OPERATIONS {
+                let result = record.authorize(&Credentials::new(&email, code), operation);
+                match role.allows(operation) {
+                    true => assert_eq!(result.unwrap(), role),
+                    false => assert!(matches!(result, Err(AgroException::PermissionDenied)), "{} may {}", email, operation),
+                }
+            }
+        }
+
+        let wrong_code = record.authorize(&Credentials::new("viewer@example.com", "wrong"), Operation::ViewReport);
+        assert!(matches!(wrong_code, Err(AgroException::Unauthorized(_))));
+        let stranger = record.authorize(&Credentials::new("stranger@example.com", code), Operation::ViewReport);
+        assert!(matches!(stranger, Err(AgroException::Unauthorized(_))));
+    }
+}
@@ -0,0 +1,99 @@
+#![allow(dead_code)]
+
+use std::f32::consts::PI;
+use chrono::{Datelike, NaiveDate};
+
+use crate::dto::Location;
+use crate::errors::AgroException;
+
+
+/// Solar constant in MJ m-2 min-1
+const SOLAR_CONSTANT: f32 = 0.0820;
+
+/// Sun related quantities of one day at one location, following FAO-56.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolarDay {
+    /// Hours between sunrise and sunset
+    pub day_length: f32,
+    /// Radiation at the top of the atmosphere (MJ m-2 day-1)
+    pub extraterrestrial_radiation: f32,
+    /// Radiation reaching the ground on a cloudless day (MJ m-2 day-1)
+    pub clear_sky_radiation: f32,
+}
+
+impl SolarDay {
+    pub fn new(latitude: f32, date: NaiveDate, elevation: f32) -> Self {
+        let day_of_year = date.ordinal();
+        let extraterrestrial_radiation = extraterrestrial_radiation(latitude, day_of_year);
+        Self {
+            day_length: day_length(latitude, day_of_year),
+            extraterrestrial_radiation,
+            clear_sky_radiation: clear_sky_radiation(extraterrestrial_radiation, elevation),
+        }
+    }
+
+    /// Solar day at sea level for a physical location.
+    pub fn at(location: &Location, date: NaiveDate) -> Result<Self, AgroException> {
+        let (latitude, _) = location.coordinates()?;
+        Ok(Self::new(latitude, date, 0.0))
+    }
+}
+
+/// Whether a crop flowers when days get shorter or longer than a critical day length (hours).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhotoperiodResponse {
+    ShortDay(f32),
+    LongDay(f32),
+}
+
+impl PhotoperiodResponse {
+    /// True when the day length triggers flowering.
+    pub fn is_inductive(&self, day_length: f32) -> bool {
+        match self {
+            PhotoperiodResponse::ShortDay(critical) => day_length <= *critical,
+            PhotoperiodResponse::LongDay(critical) => day_length >= *critical,
+        }
+    }
+}
+
+fn inverse_relative_distance(day_of_year: u32) -> f32 {
+    1.0 + 0.033 * (2.0 * PI * day_of_year as f32 / 365.0).cos()
+}
+
+fn solar_declination(day_of_year: u32) -> f32 {
+    0.409 * (2.0 * PI * day_of_year as f32 / 365.0 - 1.39).sin()
+}
+
+/// Sunset hour angle in radians. Clamped so that polar day and polar night do not produce NaN.
+fn sunset_hour_angle(latitude: f32, day_of_year: u32) -> f32 {
+    let phi = latitude.to_radians();
+    let delta = solar_declination(day_of_year);
+    (-phi.tan() * delta.tan()).clamp(-1.0, 1.0).acos()
+}
+
+/// Maximum possible duration of sunshine in hours.
+pub fn day_length(latitude: f32, day_of_year: u32) -> f32 {
+    24.0 / PI * sunset_hour_angle(latitude, day_of_year)
+}
+
+/// Extraterrestrial radiation in MJ m-2 day-1.
+pub fn extraterrestrial_radiation(latitude: f32, day_of_year: u32) -> f32 {
+    let phi = latitude.to_radians();
+    let delta = solar_declination(day_of_year);
+    let omega = sunset_hour_angle(latitude, day_of_year);
+    let radiation = 24.0 * 60.0 / PI * SOLAR_CONSTANT * inverse_relative_distance(day_of_year)
+        * (omega * phi.sin() * delta.sin() + phi.cos() * delta.cos() * omega.sin());
+    radiation.max(0.0)
+}
+
+/// Clear-sky solar radiation in MJ m-2 day-1 for a station `elevation` metres above sea level.
+pub fn clear_sky_radiation(extraterrestrial_radiation: f32, elevation: f32) -> f32 {
+    (0.75 + 2e-5 * elevation) * extraterrestrial_radiation
+}
+
+/// Hargreaves reference evapotranspiration (mm/day). Only needs temperatures and extraterrestrial radiation.
+pub fn hargreaves_et0(t_min: f32, t_max: f32, extraterrestrial_radiation: f32) -> f32 {
+    let t_mean = (t_min + t_max) / 2.0;
+    let et0 = 0.0023 * (t_mean + 17.8) * (t_max - t_min).max(0.0).sqrt() * 0.408 * extraterrestrial_radiation;
+    et0.max(0.0)
+}
@@ -0,0 +1,102 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use chrono::{Datelike, Days, NaiveDate};
+
+use crate::dto::{Crop, Location};
+use crate::errors::AgroException;
+use crate::seeds::SeedType;
+use crate::weather_service::Climate;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hemisphere {
+    Northern,
+    Southern,
+}
+
+impl Hemisphere {
+    /// Locations without coordinates are assumed to be in the northern hemisphere.
+    pub fn from_location(location: &Location) -> Self {
+        match location.latitude {
+            Some(latitude) if latitude < 0.0 => Hemisphere::Southern,
+            _ => Hemisphere::Northern,
+        }
+    }
+}
+
+/// Months (1 - 12, inclusive) in which a crop can be sown outdoors.
+/// A window may wrap around the end of the year, e.g. October -> February.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SowingWindow {
+    pub first_month: u32,
+    pub last_month: u32,
+}
+
+impl SowingWindow {
+    pub fn new(first_month: u32, last_month: u32) -> Self {
+        Self { first_month, last_month }
+    }
+
+    /// Windows are defined for the northern hemisphere and shifted by six months for the southern one.
+    pub fn for_hemisphere(&self, hemisphere: Hemisphere) -> Self {
+        match hemisphere {
+            Hemisphere::Northern => *self,
+            Hemisphere::Southern => Self {
+                first_month: (self.first_month + 5) % 12 + 1,
+                last_month: (self.last_month + 5) % 12 + 1,
+            },
+        }
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        let month = date.month();
+        if self.first_month <= self.last_month {
+            (self.first_month..=self.last_month).contains(&month)
+        } else {
+            month >= self.first_month || month <= self.last_month
+        }
+    }
+}
+
+/// Maps simulation days onto real dates. Day 1 of a run is the start date.
+#[derive(Debug, Clone)]
+pub struct SimulationCalendar {
+    pub start_date: NaiveDate,
+    pub hemisphere: Hemisphere,
+}
+
+impl SimulationCalendar {
+    pub fn new(start_date: NaiveDate, hemisphere: Hemisphere) -> Self {
+        Self { start_date, hemisphere }
+    }
+
+    pub fn date_for_day(&self, day: u32) -> NaiveDate {
+        self.start_date + Days::new(day.saturating_sub(1) as u64)
+    }
+
+    pub fn climate_on(&self, date: NaiveDate) -> Climate {
+        Climate::from_month(date.month(), self.hemisphere)
+    }
+
+    /// Returns `InvalidSeasonCropError` when the crop is sown outside of its sowing window.
+    pub fn check_sowing(&self, crop: &Crop, date: NaiveDate) -> Result<(), AgroException> {
+        let seed_type = SeedType::from_str(&crop.verbose_name)
+            .map_err(|err| AgroException::InvalidCropError(format!("{}: {}", crop.verbose_name, err)))?;
+        let window = seed_type.sowing_window().for_hemisphere(self.hemisphere);
+        if window.contains(date) {
+            return Ok(());
+        }
+
+        let mut params = HashMap::new();
+        params.insert("crop".to_string(), crop.verbose_name.to_string());
+        params.insert("sowing_date".to_string(), date.to_string());
+        params.insert("season".to_string(), self.climate_on(date).name().to_string());
+        params.insert("hemisphere".to_string(), format!("{:?}", self.hemisphere));
+        params.insert("window".to_string(), format!("month {} - month {}", window.first_month, window.last_month));
+        Err(AgroException::InvalidSeasonCropError(
+            format!("{} cannot be sown on {}", crop.verbose_name, date),
+            Some(params),
+        ))
+    }
+}
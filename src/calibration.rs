@@ -0,0 +1,387 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use chrono::NaiveDate;
+use csv::ReaderBuilder;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::dto::{Farm, GrowthStage};
+use crate::economics::ManagementAction;
+use crate::errors::AgroException;
+use crate::optimize::nelder_mead;
+use crate::parameters::ModelParameters;
+use crate::plant_service::ManagementPolicy;
+use crate::report::{EventKind, RunReport};
+use crate::rotation::RotationRunner;
+use crate::seeds::SeedType;
+use crate::sensitivity::ParameterRange;
+use crate::statistics::mean;
+
+
+/// Size of the first simplex, as a share of every parameter range.
+const INITIAL_STEP: f64 = 0.25;
+/// The search stops once the objective differs by less than this across the simplex.
+const TOLERANCE: f64 = 1e-4;
+
+
+/// A stage date, and optionally the yield, observed for a crop in a trial.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub crop: String,
+    pub stage: GrowthStage,
+    pub date: NaiveDate,
+    /// Mean yield of a harvested plant (kg)
+    pub yield_kg: Option<f32>,
+}
+
+impl Observation {
+    /// Reads the observations from a CSV file with the columns `crop,stage,date,yield`.
+    /// The yield may be left empty.
+    pub fn import_csv(path: &str) -> Result<Vec<Observation>, AgroException> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .trim(csv::Trim::All)
+            .from_path(path)
+            .map_err(|err| AgroException::InvalidObservations(err.to_string()))?;
+        let headers = reader.headers()
+            .map_err(|err| AgroException::InvalidObservations(err.to_string()))?
+            .clone();
+
+        let mut observations = Vec::new();
+        for (line, result) in reader.records().enumerate() {
+            let record = result.map_err(|err| AgroException::InvalidObservations(err.to_string()))?;
+            let map: HashMap<String, String> = headers.iter()
+                .zip(record.iter())
+                .map(|(header, value)| (header.to_string(), value.to_string()))
+                .collect();
+            let observation = Self::from_record(&map)
+                .map_err(|err| AgroException::InvalidObservations(format!("line {}: {}", line + 2, err)))?;
+            observations.push(observation);
+        }
+        Ok(observations)
+    }
+
+    fn from_record(record: &HashMap<String, String>) -> Result<Observation, String> {
+        let field = |name: &str| record.get(name).ok_or(format!("Missing column {}", name));
+        let crop = field("crop")?.to_string();
+        SeedType::from_str(&crop)?;
+        let stage = GrowthStage::from_str(field("stage")?)?;
+        let yield_kg = match field("yield")?.as_str() {
+            "" => None,
+            value => Some(value.parse::<f32>().map_err(|err| err.to_string())?),
+        };
+        Ok(Self {
+            crop,
+            stage,
+            date: NaiveDate::parse_from_str(field("date")?, "%Y-%m-%d").map_err(|err| err.to_string())?,
+            yield_kg,
+        })
+    }
+}
+
+/// An observation next to what the simulation gave for it.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Residual {
+    pub crop: String,
+    pub stage: GrowthStage,
+    /// Days from sowing to the stage
+    pub observed_day: f64,
+    /// Mean over the replicates in which the crops reached the stage, if any did
+    pub simulated_day: Option<f64>,
+    /// Share of the replicates in which the crops never reached the stage
+    pub missed_share: f64,
+    pub observed_yield_kg: Option<f64>,
+    pub simulated_yield_kg: Option<f64>,
+}
+
+/// How far the simulation is from the observations.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct GoodnessOfFit {
+    pub stage_rmse_days: f64,
+    /// Mean of simulated minus observed stage days: positive when the simulated crops are late
+    pub stage_bias_days: f64,
+    pub yield_rmse_kg: f64,
+    pub yield_bias_kg: f64,
+    /// Mean share of the replicates in which the crops never reached an observed stage. These are
+    /// left out of the stage RMSE and bias
+    pub missed_stages: f64,
+    /// What the search minimises: the RMSEs over the mean of the observations, plus the share of
+    /// missed stages
+    pub objective: f64,
+}
+
+impl GoodnessOfFit {
+    fn from_residuals(residuals: &[Residual]) -> Self {
+        let stage_errors: Vec<f64> = residuals.iter()
+            .filter_map(|residual| Some(residual.simulated_day? - residual.observed_day))
+            .collect();
+        let yields: Vec<(f64, f64)> = residuals.iter()
+            .filter_map(|residual| Some((residual.observed_yield_kg?, residual.simulated_yield_kg.unwrap_or(0.0))))
+            .collect();
+        let yield_errors: Vec<f64> = yields.iter().map(|(observed, simulated)| simulated - observed).collect();
+        let rmse = |errors: &[f64]| mean(&errors.iter().map(|error| error * error).collect::<Vec<_>>()).sqrt();
+
+        let stage_rmse_days = rmse(&stage_errors);
+        let yield_rmse_kg = rmse(&yield_errors);
+        let mean_day = mean(&residuals.iter().map(|residual| residual.observed_day).collect::<Vec<_>>());
+        let mean_yield = mean(&yields.iter().map(|(observed, _)| *observed).collect::<Vec<_>>());
+        let missed_stages = mean(&residuals.iter().map(|residual| residual.missed_share).collect::<Vec<_>>());
+        let mut objective = missed_stages;
+        if !stage_errors.is_empty() {
+            objective += stage_rmse_days / mean_day.abs().max(1.0);
+        }
+        if !yield_errors.is_empty() {
+            objective += yield_rmse_kg / mean_yield.abs().max(f64::EPSILON);
+        }
+        Self {
+            stage_rmse_days,
+            stage_bias_days: mean(&stage_errors),
+            yield_rmse_kg,
+            yield_bias_kg: mean(&yield_errors),
+            missed_stages,
+            objective,
+        }
+    }
+}
+
+/// Fits catalog parameters of the crops to stage dates and yields observed in trials.
+///
+/// Every candidate parameter set is run for `replicates` seeds, the same for every candidate, and
+/// the search is a Nelder-Mead simplex within the parameter ranges.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CalibrationStudy {
+    pub name: String,
+    /// CSV file of the observations
+    pub observations: String,
+    /// Day the trial was sown, and the first day of the runs. Observed stages count their days from
+    /// it, simulated ones from the day the run sows the crops
+    pub start_date: NaiveDate,
+    #[serde(default = "CalibrationStudy::default_replicates")]
+    pub replicates: u32,
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default = "CalibrationStudy::default_max_evaluations")]
+    pub max_evaluations: usize,
+    pub parameters: Vec<ParameterRange>,
+}
+
+impl CalibrationStudy {
+    fn default_replicates() -> u32 {
+        5
+    }
+
+    fn default_max_evaluations() -> usize {
+        100
+    }
+
+    /// Reads a study from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AgroException> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", path.display(), err)))?;
+        let study: CalibrationStudy = serde_json::from_str(&content)
+            .map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", path.display(), err)))?;
+        study.validate()?;
+        Ok(study)
+    }
+
+    fn validate(&self) -> Result<(), AgroException> {
+        if self.parameters.is_empty() {
+            return Err(AgroException::InvalidExperiment("no parameters to fit".to_string()));
+        }
+        if self.replicates == 0 {
+            return Err(AgroException::InvalidExperiment("at least 1 replicate is needed".to_string()));
+        }
+        self.parameters.iter().try_for_each(ParameterRange::validate)
+    }
+
+    /// Fits the parameters on fresh farms from `farm`.
+    pub fn run(&self, farm: impl Fn() -> Result<Farm, AgroException> + Sync) -> Result<CalibrationReport, AgroException> {
+        self.validate()?;
+        let observations = Observation::import_csv(&self.observations)?;
+        if observations.is_empty() {
+            return Err(AgroException::InvalidObservations(format!("{} has no observations", self.observations)));
+        }
+
+        let initial: Vec<f64> = self.parameters.iter()
+            .map(|range| (range.parameter.nominal().clamp(range.min, range.max) - range.min) / (range.max - range.min))
+            .collect();
+        let mut error = None;
+        let minimum = nelder_mead(
+            |point| match self.residuals(&self.model_parameters(point), &observations, &farm) {
+                Ok(residuals) => GoodnessOfFit::from_residuals(&residuals).objective,
+                Err(err) => {
+                    error.get_or_insert(err);
+                    f64::INFINITY
+                },
+            },
+            &initial,
+            INITIAL_STEP,
+            self.max_evaluations,
+            TOLERANCE,
+        );
+        if let Some(err) = error {
+            return Err(err);
+        }
+
+        let initial_fit = GoodnessOfFit::from_residuals(&self.residuals(&self.model_parameters(&initial), &observations, &farm)?);
+        let fitted_parameters = self.model_parameters(&minimum.point);
+        let residuals = self.residuals(&fitted_parameters, &observations, &farm)?;
+        let fitted = self.parameters.iter().zip(initial.iter().zip(minimum.point.iter()))
+            .map(|(range, (initial, fitted))| FittedParameter {
+                parameter: range.parameter.to_string(),
+                initial: range.scale(*initial),
+                fitted: self.value(range, *fitted),
+            })
+            .collect();
+
+        Ok(CalibrationReport {
+            name: self.name.to_string(),
+            evaluations: minimum.evaluations,
+            fitted,
+            parameters: fitted_parameters,
+            initial_fit,
+            fit: GoodnessOfFit::from_residuals(&residuals),
+            residuals,
+        })
+    }
+
+    /// Value of a parameter at `unit` within its range, rounded if it only takes whole values.
+    fn value(&self, range: &ParameterRange, unit: f64) -> f64 {
+        let value = range.scale(unit);
+        if range.parameter.is_discrete() { value.round() } else { value }
+    }
+
+    fn model_parameters(&self, point: &[f64]) -> ModelParameters {
+        let mut policy = ManagementPolicy::default();
+        let mut parameters = ModelParameters::default();
+        for (range, unit) in self.parameters.iter().zip(point.iter()) {
+            range.parameter.apply(self.value(range, *unit), &mut policy, &mut parameters);
+        }
+        parameters
+    }
+
+    /// Runs one season per replicate and sets the mean simulated stage days and yields against the observations.
+    fn residuals(
+        &self,
+        parameters: &ModelParameters,
+        observations: &[Observation],
+        farm: &(impl Fn() -> Result<Farm, AgroException> + Sync),
+    ) -> Result<Vec<Residual>, AgroException> {
+        let runs: Vec<RunReport> = (0..self.replicates).into_par_iter()
+            .map(|replicate| {
+                let report = RotationRunner::new(farm()?, 1, 1)
+                    .starting_on(self.start_date)
+                    .quiet()
+                    .with_parameters(parameters.clone())
+                    .with_seed(self.seed.wrapping_add(replicate as u64))
+                    .run()?;
+                Ok(report.seasons.into_iter().next().unwrap_or_default())
+            })
+            .collect::<Result<_, AgroException>>()?;
+
+        Ok(observations.iter()
+            .map(|observation| {
+                let days: Vec<f64> = runs.iter()
+                    .filter_map(|run| Self::simulated_day(run, observation))
+                    .collect();
+                let simulated_yield_kg = observation.yield_kg.map(|_| {
+                    mean(&runs.iter().map(|run| Self::yield_per_plant(run, &observation.crop)).collect::<Vec<_>>())
+                });
+                Residual {
+                    crop: observation.crop.to_string(),
+                    stage: observation.stage.clone(),
+                    observed_day: (observation.date - self.start_date).num_days() as f64,
+                    simulated_day: if days.is_empty() { None } else { Some(mean(&days)) },
+                    missed_share: 1.0 - days.len() as f64 / runs.len() as f64,
+                    observed_yield_kg: observation.yield_kg.map(|kg| kg as f64),
+                    simulated_yield_kg,
+                }
+            })
+            .collect())
+    }
+
+    /// Mean number of days from sowing the crops of the observation took to reach its stage, if any did.
+    fn simulated_day(run: &RunReport, observation: &Observation) -> Option<f64> {
+        let sowing_date = run.events.iter()
+            .find(|event| event.kind == EventKind::Action(ManagementAction::Planting))?
+            .date;
+        let days: Vec<f64> = run.events.iter()
+            .filter_map(|event| match &event.kind {
+                EventKind::StageChanged { crop_name, stage }
+                    if crop_name.eq_ignore_ascii_case(&observation.crop) && *stage == observation.stage => {
+                    Some((event.date - sowing_date).num_days() as f64)
+                },
+                _ => None,
+            })
+            .collect();
+        if days.is_empty() { None } else { Some(mean(&days)) }
+    }
+
+    fn yield_per_plant(run: &RunReport, crop: &str) -> f64 {
+        run.crops.iter()
+            .find(|(crop_name, _)| crop_name.eq_ignore_ascii_case(crop))
+            .filter(|(_, summary)| summary.harvested > 0)
+            .map_or(0.0, |(_, summary)| summary.yield_kg as f64 / summary.harvested as f64)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FittedParameter {
+    pub parameter: String,
+    /// Where the search started: the catalog value, brought within the range
+    pub initial: f64,
+    pub fitted: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CalibrationReport {
+    pub name: String,
+    pub evaluations: usize,
+    pub fitted: Vec<FittedParameter>,
+    /// The fitted parameter set, ready to be given to a run
+    pub parameters: ModelParameters,
+    pub initial_fit: GoodnessOfFit,
+    pub fit: GoodnessOfFit,
+    pub residuals: Vec<Residual>,
+}
+
+impl fmt::Display for CalibrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Calibration: {} ({} evaluations)", self.name, self.evaluations)?;
+        writeln!(f, "  {:<28} {:>9} {:>9}", "Parameter", "Initial", "Fitted")?;
+        for parameter in self.fitted.iter() {
+            writeln!(f, "  {:<28} {:>9.3} {:>9.3}", parameter.parameter, parameter.initial, parameter.fitted)?;
+        }
+        writeln!(f, "  {:<28} {:>9} {:>9}", "Goodness of fit", "Initial", "Fitted")?;
+        for (name, initial, fitted) in [
+            ("Stage RMSE (days)", self.initial_fit.stage_rmse_days, self.fit.stage_rmse_days),
+            ("Stage bias (days)", self.initial_fit.stage_bias_days, self.fit.stage_bias_days),
+            ("Yield RMSE (kg/plant)", self.initial_fit.yield_rmse_kg, self.fit.yield_rmse_kg),
+            ("Yield bias (kg/plant)", self.initial_fit.yield_bias_kg, self.fit.yield_bias_kg),
+            ("Stages missed (share)", self.initial_fit.missed_stages, self.fit.missed_stages),
+            ("Objective", self.initial_fit.objective, self.fit.objective),
+        ] {
+            writeln!(f, "  {:<28} {:>9.3} {:>9.3}", name, initial, fitted)?;
+        }
+        for residual in self.residuals.iter() {
+            write!(f, "  {} {:?}: observed day {:.0}, ", residual.crop, residual.stage, residual.observed_day)?;
+            match residual.simulated_day {
+                Some(simulated_day) => write!(f, "simulated {:.1}", simulated_day)?,
+                None => write!(f, "never reached")?,
+            }
+            if residual.simulated_day.is_some() && residual.missed_share > 0.0 {
+                write!(f, " (never reached in {:.0}% of the replicates)", residual.missed_share * 100.0)?;
+            }
+            if let (Some(observed), Some(simulated)) = (residual.observed_yield_kg, residual.simulated_yield_kg) {
+                write!(f, " | yield observed {:.3} kg, simulated {:.3} kg", observed, simulated)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -1,19 +1,17 @@
-use clap::{Arg, Command, Parser, Subcommand};
+#![allow(dead_code)]
+
+use clap::{Parser, Subcommand};
 
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
-struct AgroCLI {
-    #[arg(short, long)]
-    name: String,
-    #[arg(short, long)]
-    is_good: Option<bool>,
+pub struct AgroCLI {
     #[command(subcommand)]
-    command: Option<Commands>,
+    pub command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
-enum Commands {
+pub enum Commands {
    Crop {
 
        #[arg(short, long)]
@@ -21,7 +19,68 @@ enum Commands {
 
        #[arg(short, long)]
        get: Option<String>,
-   }
+   },
+   /// Serves the JSON API to submit and run simulations
+   Serve {
+
+       #[arg(short, long, default_value = "127.0.0.1:8080")]
+       address: String,
+
+       /// Runs carried out at the same time
+       #[arg(short, long, default_value_t = 4)]
+       workers: usize,
+   },
+   /// Runs the rotations of all the farms in the registry at the same time and adds up the results
+   Region {
+
+       /// Every farm sees the same weather
+       #[arg(short, long)]
+       shared_weather: bool,
+   },
+   /// Compares variants of a scenario with the baseline, under the same seeds and weather
+   Experiment {
+
+       /// JSON file with the baseline and the variants
+       path: String,
+   },
+   /// Works out which parameters the outcome of a rotation is most sensitive to
+   Sensitivity {
+
+       /// JSON file with the parameter ranges and the outputs
+       path: String,
+   },
+   /// Fits crop parameters to stage dates and yields observed in trials
+   Calibrate {
+
+       /// JSON file with the observations and the parameter ranges
+       path: String,
+
+       /// Where to write the fitted parameters as JSON
+       #[arg(short, long)]
+       output: Option<String>,
+   },
+   /// Plans when and how much to irrigate within a water budget
+   Irrigation {
+
+       /// JSON file with the budget, the objective and the forecast
+       path: String,
+
+       /// Where to write the plan as JSON
+       #[arg(short, long)]
+       output: Option<String>,
+   },
+   /// Recommends fertilizer from a soil test and a target yield, and simulates the yield response
+   Fertilizer {
+
+       /// JSON file with the crop, the target yield and the soil test
+       path: String,
+   },
+   /// Follows the rotation of the farm on a terminal dashboard
+   Dashboard {
+
+       #[arg(short, long, default_value_t = 4.0)]
+       days_per_second: f32,
+   },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
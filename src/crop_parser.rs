@@ -82,6 +82,6 @@ pub fn split_a_crop() {
     println!("Splits: {:?}", splits);
     println!("----------------------------------------");
     println!("Length of crops after split: {}", crops.len());
-    let x_crop = first_crop.simulate_growth();
-    println!("First crop: {:?}", x_crop);
+    first_crop.simulate_growth();
+    println!("First crop: {:?}", first_crop);
 }
@@ -0,0 +1,250 @@
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::io::{self, Stderr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, List, ListItem, Paragraph, Wrap};
+use ratatui::{Frame, Terminal};
+
+use crate::dto::GrowthStage;
+use crate::errors::AgroException;
+use crate::progress::DayFrame;
+use crate::rotation::RotationRunner;
+
+
+/// Events kept in the action log.
+const LOG_LENGTH: usize = 200;
+const MIN_DAYS_PER_SECOND: f32 = 0.5;
+const MAX_DAYS_PER_SECOND: f32 = 64.0;
+
+fn stage_color(stage: &GrowthStage) -> Color {
+    match stage {
+        GrowthStage::Seed => Color::DarkGray,
+        GrowthStage::Germination => Color::Gray,
+        GrowthStage::Seedling => Color::LightGreen,
+        GrowthStage::Vegetative => Color::Green,
+        GrowthStage::Flowering => Color::Magenta,
+        GrowthStage::Fruiting => Color::LightRed,
+        GrowthStage::Maturity => Color::Yellow,
+        GrowthStage::Harvest => Color::LightYellow,
+        GrowthStage::Failed => Color::Red,
+    }
+}
+
+/// Terminal dashboard following a rotation day by day, at an adjustable number of days a second.
+///
+/// Keys: `space` pauses and resumes, `n` steps one day while paused, `+`/`-` speed up and
+/// slow down, `q` quits and stops the run.
+pub struct Dashboard {
+    frames: Option<Receiver<DayFrame>>,
+    current: Option<DayFrame>,
+    season: u32,
+    log: VecDeque<String>,
+    days_per_second: f32,
+    paused: bool,
+    /// Set once the run has sent its last day
+    finished: bool,
+    /// Harvested and failed crops of the seasons before the current one
+    harvested_before: u32,
+    failed_before: u32,
+}
+
+impl Dashboard {
+    pub fn new(days_per_second: f32) -> Self {
+        Self {
+            frames: None,
+            current: None,
+            season: 1,
+            log: VecDeque::new(),
+            days_per_second: days_per_second.clamp(MIN_DAYS_PER_SECOND, MAX_DAYS_PER_SECOND),
+            paused: false,
+            finished: false,
+            harvested_before: 0,
+            failed_before: 0,
+        }
+    }
+
+    /// Carries out the rotation on its own thread and takes over the terminal until the user quits.
+    /// The runner should be quiet, or its output ends up across the dashboard.
+    pub fn run(mut self, runner: RotationRunner) -> io::Result<()> {
+        let (progress, frames) = mpsc::channel();
+        let cancellation = Arc::new(AtomicBool::new(false));
+        let runner = runner.with_progress(progress).with_cancellation(cancellation.clone());
+        let simulation = thread::spawn(move || runner.run());
+        self.frames = Some(frames);
+
+        enable_raw_mode()?;
+        execute!(io::stderr(), EnterAlternateScreen)?;
+        let result = Terminal::new(CrosstermBackend::new(io::stderr())).and_then(|mut terminal| self.event_loop(&mut terminal));
+        disable_raw_mode()?;
+        execute!(io::stderr(), LeaveAlternateScreen)?;
+
+        cancellation.store(true, Ordering::Relaxed);
+        match simulation.join() {
+            Ok(Err(AgroException::Cancelled)) | Ok(Ok(_)) => (),
+            Ok(Err(err)) => eprintln!("Error: {}", err),
+            Err(_) => eprintln!("Error: the simulation panicked"),
+        }
+        result
+    }
+
+    fn event_loop(&mut self, terminal: &mut Terminal<CrosstermBackend<Stderr>>) -> io::Result<()> {
+        let mut next_day = Instant::now();
+        loop {
+            terminal.draw(|frame| self.render(frame))?;
+            let timeout = match self.paused || self.finished {
+                true => Duration::from_millis(250),
+                false => next_day.saturating_duration_since(Instant::now()),
+            };
+            if event::poll(timeout)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Char(' ') | KeyCode::Char('p') => self.paused = !self.paused,
+                        KeyCode::Char('n') if self.paused => self.advance(Duration::from_millis(250)),
+                        KeyCode::Char('+') | KeyCode::Char('=') => {
+                            self.days_per_second = (self.days_per_second * 2.0).min(MAX_DAYS_PER_SECOND)
+                        },
+                        KeyCode::Char('-') => self.days_per_second = (self.days_per_second / 2.0).max(MIN_DAYS_PER_SECOND),
+                        _ => (),
+                    }
+                }
+            } else if !self.paused && Instant::now() >= next_day {
+                self.advance(Duration::from_millis(50));
+                next_day = Instant::now() + Duration::from_secs_f32(1.0 / self.days_per_second);
+            }
+        }
+    }
+
+    /// Moves on to the next day, if the simulation has got that far within `wait`.
+    fn advance(&mut self, wait: Duration) {
+        let frames = match self.frames.as_ref() {
+            Some(frames) if !self.finished => frames,
+            _ => return,
+        };
+        let frame = match frames.recv_timeout(wait) {
+            Ok(frame) => frame,
+            Err(RecvTimeoutError::Timeout) => return,
+            Err(RecvTimeoutError::Disconnected) => {
+                self.finished = true;
+                self.paused = true;
+                return;
+            },
+        };
+        if let Some(previous) = self.current.as_ref().filter(|previous| frame.day < previous.day) {
+            // Day 1 again: the rotation has moved on to the next season.
+            self.season += 1;
+            self.harvested_before += previous.harvested;
+            self.failed_before += previous.failed;
+            self.log.push_front(format!("Season {} starts on {}", self.season, frame.date));
+        }
+        for event in frame.events.iter() {
+            self.log.push_front(format!("Day {:>3} {}: {}", frame.day, frame.date, event));
+        }
+        self.log.truncate(LOG_LENGTH);
+        self.current = Some(frame);
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([Constraint::Length(3), Constraint::Min(8), Constraint::Length(1)]).areas(frame.area());
+        let [left, log] = Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(body);
+        let [farm, moisture] = Layout::vertical([Constraint::Min(5), Constraint::Length(self.moisture_height())]).areas(left);
+
+        self.render_header(frame, header);
+        self.render_farm(frame, farm);
+        self.render_moisture(frame, moisture);
+        let items: Vec<ListItem> = self.log.iter().map(|line| ListItem::new(line.as_str())).collect();
+        frame.render_widget(List::new(items).block(Block::bordered().title(" Action log ")), log);
+
+        let state = match (self.finished, self.paused) {
+            (true, _) => "finished".to_string(),
+            (false, true) => "paused".to_string(),
+            (false, false) => format!("{} days/s", self.days_per_second),
+        };
+        let help = format!(" [space] pause  [n] step day  [+/-] speed  [q] quit  | {}", state);
+        frame.render_widget(Paragraph::new(help).style(Style::default().add_modifier(Modifier::REVERSED)), footer);
+    }
+
+    fn render_header(&self, frame: &mut Frame, area: Rect) {
+        let text = match self.current.as_ref() {
+            Some(day) => Line::from(vec![
+                Span::styled(format!("Season {} day {} ", self.season, day.day), Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(format!("({})   ", day.date)),
+                Span::raw(format!("{:?}, {:.1} °C   ", day.weather, day.mean_temperature)),
+                Span::styled(format!("harvested: {}  ", self.harvested_before + day.harvested), Style::default().fg(Color::Green)),
+                Span::styled(format!("failed: {}", self.failed_before + day.failed), Style::default().fg(Color::Red)),
+            ]),
+            None => Line::from("Waiting for the first day..."),
+        };
+        frame.render_widget(Paragraph::new(text).block(Block::bordered().title(" Weather ")), area);
+    }
+
+    /// One row per plot (and the nursery), with a cell per crop coloured by its growth stage.
+    fn render_farm(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        if let Some(day) = self.current.as_ref() {
+            let mut rows: Vec<String> = day.crops.iter()
+                .map(|crop| if crop.in_nursery { "Nursery".to_string() } else { crop.plot.clone().unwrap_or("No plot".to_string()) })
+                .collect();
+            rows.sort();
+            rows.dedup();
+            for row in rows {
+                let mut spans = vec![Span::raw(format!("{:<10}", row))];
+                for crop in day.crops.iter().filter(|crop| {
+                    let crop_row = if crop.in_nursery { Some("Nursery".to_string()) } else { crop.plot.clone() };
+                    crop_row.unwrap_or("No plot".to_string()) == row
+                }) {
+                    spans.push(Span::styled("██", Style::default().fg(stage_color(&crop.stage))));
+                    spans.push(Span::raw(" "));
+                }
+                lines.push(Line::from(spans));
+            }
+            lines.push(Line::from(""));
+            for (stage, count) in day.stages.iter() {
+                lines.push(Line::from(vec![
+                    Span::styled("██ ", Style::default().fg(stage_color(stage))),
+                    Span::raw(format!("{:?}: {}", stage, count)),
+                ]));
+            }
+        }
+        let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false }).block(Block::bordered().title(" Farm "));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn moisture_height(&self) -> u16 {
+        self.current.as_ref().map_or(0, |day| day.soil_moisture.len() as u16) + 2
+    }
+
+    fn render_moisture(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Soil moisture ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let plots = match self.current.as_ref() {
+            Some(day) => &day.soil_moisture,
+            None => return,
+        };
+        let rows = Layout::vertical(vec![Constraint::Length(1); plots.len()]).split(inner);
+        for ((plot, moisture), row) in plots.iter().zip(rows.iter()) {
+            let color = if *moisture < 0.3 { Color::Red } else { Color::Blue };
+            let gauge = Gauge::default()
+                .gauge_style(Style::default().fg(color))
+                .ratio(moisture.clamp(0.0, 1.0) as f64)
+                .label(format!("{}: {:.0}%", plot, moisture * 100.0));
+            frame.render_widget(gauge, *row);
+        }
+    }
+}
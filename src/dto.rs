@@ -2,9 +2,20 @@
 
 use std::{collections::HashMap, fmt::Debug};
 use rand::prelude::*;
-use serde::Deserialize;
-
-use crate::seeds::SeedType;
+use serde::{Deserialize, Serialize};
+
+use crate::access::SecurityCode;
+use crate::errors::AgroException;
+use crate::failure::{FailureCause, FailureRisk};
+use crate::greenhouse::Greenhouse;
+use crate::growth::{CropGrowth, GrowthConditions};
+use crate::inventory::SeedInventory;
+use crate::nursery::Nursery;
+use crate::parameters::CropParameters;
+use crate::rotation::{RotationPlan, RotationStep, SeasonRecord};
+use crate::seeds::{SeedLot, SeedType};
+use crate::soil::SoilState;
+use crate::storage::PostHarvestStore;
 
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
@@ -20,12 +31,27 @@ pub struct Crop {
     pub date_rot_detected: Option<String>,
     pub split_size: Option<f32>,
     pub days_in_stage: Option<u32>,
+    pub plot_id: Option<u32>,
+    /// Field plot a seedling raised in the nursery is planted out on. Set while the crop is in the nursery
+    pub transplant_plot: Option<u32>,
+    pub yield_kg: Option<f32>,
+    pub growth: Option<CropGrowth>,
+    pub failure_cause: Option<FailureCause>,
+    pub risk: Option<FailureRisk>,
+    pub seed_lot: Option<SeedLot>,
+    /// Whether the seed comes up, drawn at sowing from its lot and the seedbed
+    pub emerged: Option<bool>,
+    /// Values where the crop differs from the catalog values of its seed type
+    #[serde(default)]
+    pub parameters: CropParameters,
     #[serde(flatten)]
     pub current_stage: Option<GrowthStage>,
 }
 
+/// A photoperiod sensitive crop waits at most this many days past its vegetative stage for an inductive day length.
+const MAX_PHOTOPERIOD_DELAY: u32 = 21;
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Location {
     pub address: Address,
     pub is_virtual: bool,
@@ -33,7 +59,7 @@ pub struct Location {
     pub latitude : Option<f32>,
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Address {
     pub house_number: u32,
     pub post_code: String,
@@ -42,14 +68,14 @@ pub struct Address {
     pub country: String,
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FarmSize {
     pub width: u32,
     pub length: u32
 }
 
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct UserInfo {
     pub first_name: String,
     pub last_name: String,
@@ -60,13 +86,35 @@ pub struct UserInfo {
 }
 
 
+/// A section of the farm with its own soil and rotation plan.
+#[derive(Debug)]
+pub struct Plot {
+    pub id: u32,
+    pub name: String,
+    pub size: FarmSize,
+    pub soil: SoilState,
+    pub rotation: RotationPlan,
+    pub history: Vec<SeasonRecord>,
+    /// Set when the plot is grown under glass
+    pub greenhouse: Option<Greenhouse>,
+}
+
 #[derive(Debug)]
 pub struct Farm {
+    /// Id of the farm in the registry
+    pub id: u32,
     pub crops: Vec<Crop>,
+    pub plots: Vec<Plot>,
+    /// Seed lots in the shed
+    pub inventory: SeedInventory,
+    /// Stores where the harvest is kept until it is sold
+    pub storage: PostHarvestStore,
+    /// Propagation house for the crops raised as seedlings
+    pub nursery: Option<Nursery>,
     pub location: Location,
     pub size: FarmSize,
     pub owner: UserInfo,
-    pub security_code: String,
+    pub security_code: SecurityCode,
     pub is_active: bool,
     pub is_trackable: Option<bool>,
     pub is_plant_ready: Option<bool>,
@@ -77,9 +125,9 @@ impl Crop {
     pub fn new(bot_name: String, verbose_name: String, species: String, description: Option<String>) -> Self {
         Self {
             botanica_name: bot_name,
-            verbose_name: verbose_name,
-            species: species,
-            description: description,
+            verbose_name,
+            species,
+            description,
             is_harvestable: true,
             is_sown: false,
             is_gmo: false,
@@ -87,11 +135,20 @@ impl Crop {
             date_rot_detected: None,
             split_size: Some(1.0),
             days_in_stage: Some(0),
+            plot_id: None,
+            transplant_plot: None,
+            yield_kg: None,
+            growth: Some(CropGrowth::default()),
+            failure_cause: None,
+            risk: None,
+            seed_lot: None,
+            emerged: None,
+            parameters: CropParameters::default(),
             current_stage: Some(GrowthStage::Seed),
         }
     }
 
-    pub fn simulate_growth(&mut self) -> () {
+    pub fn simulate_growth(&mut self) {
         let mut rng = thread_rng();
         let rand_days = rng.gen_range(1..5); // Generate a random number between 1 and 5
 
@@ -99,26 +156,70 @@ impl Crop {
         self.grow(rand_days as u32);
     }
 
-    pub fn grow(&mut self, days: u32) -> () {
-        match self.days_in_stage {
-            Some(val) => {
-                self.days_in_stage = Some(val + days);
-            }
-            _ => (),
+    pub fn grow(&mut self, days: u32) {
+        if let Some(val) = self.days_in_stage {
+            self.days_in_stage = Some(val + days);
         }
     }
 
-    pub fn advance_to_next_stage(&mut self) -> () {
+    /// One day of biomass and leaf area growth in the current stage.
+    pub fn grow_biomass(&mut self, conditions: &GrowthConditions) {
         let seed_type = SeedType::from_str(&self.verbose_name).unwrap();
-        let max_growth_days = self.current_stage.as_ref().unwrap().get_days(seed_type);
+        if let (Some(growth), Some(stage)) = (self.growth.as_mut(), self.current_stage.as_ref()) {
+            growth.grow_day(&seed_type, &self.parameters, stage, conditions);
+        }
+    }
+
+    pub fn advance_to_next_stage(&mut self) {
+        let max_growth_days = self.stage_days(self.current_stage.as_ref().unwrap());
         let current_stage = self.current_stage.as_ref().unwrap();
-        if self.days_in_stage.unwrap() == max_growth_days && !self.is_inactive() {
-            let next_stage = GrowthStage::next(&current_stage);
-            println!("Transiting from {:?} -> {:?}",current_stage, next_stage);
+        if self.days_in_stage.unwrap() >= max_growth_days && !self.is_inactive() {
+            let next_stage = GrowthStage::next(current_stage, GrowthEvent::Sync);
             self.current_stage = Some(next_stage);
         }
     }
 
+    /// Whether the day length lets the crop move from the vegetative stage into flowering.
+    /// Crops without a photoperiod response, or without a known day length, are always induced.
+    pub fn is_flowering_induced(&self, day_length: Option<f32>) -> bool {
+        let seed_type = SeedType::from_str(&self.verbose_name).unwrap();
+        let (response, day_length) = match (seed_type.photoperiod_response(), day_length) {
+            (Some(response), Some(day_length)) => (response, day_length),
+            _ => return true,
+        };
+        let days_past_stage = self.days_in_stage.unwrap_or(0)
+            .saturating_sub(self.stage_days(&GrowthStage::Vegetative));
+        response.is_inductive(day_length) || days_past_stage >= MAX_PHOTOPERIOD_DELAY
+    }
+
+    /// Days the crop spends in `stage`.
+    pub fn stage_days(&self, stage: &GrowthStage) -> u32 {
+        match self.parameters.stage_days.get(stage) {
+            Some(days) => *days,
+            None => stage.get_days(SeedType::from_str(&self.verbose_name).unwrap()),
+        }
+    }
+
+    /// Whether the crop has spent the required number of days in its current stage.
+    pub fn is_stage_complete(&self) -> bool {
+        match self.current_stage.as_ref() {
+            Some(stage) => self.days_in_stage.unwrap_or(0) >= self.stage_days(stage),
+            None => false,
+        }
+    }
+
+    /// A seed that was drawn not to emerge fails once it has spent its days in the soil.
+    pub fn fails_to_emerge(&self) -> bool {
+        self.current_stage == Some(GrowthStage::Seed) && self.emerged == Some(false) && self.is_stage_complete()
+    }
+
+    /// Moves the crop to the failed stage and records why.
+    pub fn fail(&mut self, cause: FailureCause) {
+        let stage = self.current_stage.clone().unwrap_or_default();
+        self.current_stage = Some(GrowthStage::next(&stage, GrowthEvent::Fail));
+        self.failure_cause = Some(cause);
+    }
+
     pub fn is_inactive(&self) -> bool {
         self.current_stage == Some(GrowthStage::Failed) || self.current_stage == Some(GrowthStage::Harvest)
     }
@@ -140,21 +241,57 @@ impl Crop {
         splits
     }
 
-    pub fn sow(&mut self) -> () {
+    pub fn sow(&mut self) {
         self.is_sown = true;
     }
 
+    pub fn is_in_nursery(&self) -> bool {
+        self.transplant_plot.is_some()
+    }
+
+    /// Plot the crop grows on, or will be planted out on once it leaves the nursery.
+    pub fn field_plot(&self) -> Option<u32> {
+        self.plot_id.or(self.transplant_plot)
+    }
+
+    /// Sows the crop in the nursery instead of on its plot.
+    pub fn raise_in_nursery(&mut self) {
+        if let Some(plot_id) = self.plot_id.take() {
+            self.transplant_plot = Some(plot_id);
+        }
+    }
+
+    /// Moves the seedling from the nursery to its field plot.
+    pub fn transplant(&mut self) {
+        let seed_type = SeedType::from_str(&self.verbose_name).unwrap();
+        self.plot_id = self.transplant_plot.take();
+        if let Some(growth) = self.growth.as_mut() {
+            growth.transplant(seed_type.transplant_shock());
+        }
+    }
+
+    /// A failure that has been detected and recorded.
     pub fn has_issues(&self) -> bool {
         self.date_rot_detected.is_some() && self.current_stage == Some(GrowthStage::Failed)
     }
+
+    /// Harvested fresh weight (kg) of this crop, from the storage organ it has built up,
+    /// scaled by its split size and the rotation history of its plot.
+    pub fn harvest_yield(&self, rotation_factor: f32) -> f32 {
+        let seed_type = SeedType::from_str(&self.verbose_name).unwrap();
+        let fresh_yield = self.growth.as_ref().map_or(0.0, |growth| growth.fresh_yield(&seed_type));
+        fresh_yield * self.split_size.unwrap_or(1.0) * rotation_factor
+    }
 }
 
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+/// Stages in the order a crop goes through them, `Failed` last.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub enum GrowthStage {
+    #[default]
     Seed,
-    Seedling,
     Germination,
+    Seedling,
     Vegetative,
     Flowering,
     Fruiting,
@@ -163,19 +300,40 @@ pub enum GrowthStage {
     Failed,
 }
 
-impl Default for GrowthStage {
-    fn default() -> Self {
-        Self::Seed
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum GrowthEvent {
     Sync,
     Fail,
 }
 
+impl GrowthEvent {
+    /// Constant chance (3 in 51) of failing, regardless of the conditions. Used by the naive failure model.
+    pub fn coin_flip(rng: &mut impl Rng) -> Self {
+        let event_idx = rng.gen_range(0..=50);
+        if event_idx % 21 == 0 {
+            GrowthEvent::Fail
+        } else {
+            GrowthEvent::Sync
+        }
+    }
+}
+
 impl GrowthStage {
+    pub fn from_str(stage: &str) -> Result<GrowthStage, String> {
+        match stage.to_lowercase().as_str() {
+            "seed" => Ok(GrowthStage::Seed),
+            "germination" => Ok(GrowthStage::Germination),
+            "seedling" => Ok(GrowthStage::Seedling),
+            "vegetative" => Ok(GrowthStage::Vegetative),
+            "flowering" => Ok(GrowthStage::Flowering),
+            "fruiting" => Ok(GrowthStage::Fruiting),
+            "maturity" => Ok(GrowthStage::Maturity),
+            "harvest" => Ok(GrowthStage::Harvest),
+            "failed" => Ok(GrowthStage::Failed),
+            _ => Err(format!("Invalid stage {}", stage)),
+        }
+    }
+
     fn get_stage(&self) -> String {
         match self {
             GrowthStage::Seed => String::from("seed"),
@@ -252,16 +410,7 @@ impl GrowthStage {
         }
     }
 
-    pub fn next(instance: &GrowthStage) -> GrowthStage {
-        let mut rng = rand::thread_rng();
-        let event_idx = rng.gen_range(0..=50);
-        let rot_idx =  event_idx % 21 == 0;
-        let event = if rot_idx {
-            GrowthEvent::Fail
-        } else {
-            GrowthEvent::Sync
-        };
-        println!("{:?} -> {:?}", instance, event);
+    pub fn next(instance: &GrowthStage, event: GrowthEvent) -> GrowthStage {
         match instance {
             GrowthStage::Seed => match event {
                 GrowthEvent::Sync => GrowthStage::Germination,
@@ -296,6 +445,57 @@ impl GrowthStage {
     }
 }
 
+impl Location {
+    /// Latitude and longitude of a physical location.
+    pub fn coordinates(&self) -> Result<(f32, f32), AgroException> {
+        if self.is_virtual {
+            return Err(AgroException::InvalidLocation(format!("{} is virtual and has no coordinates", self.address.city)));
+        }
+        match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) => Ok((latitude, longitude)),
+            _ => Err(AgroException::InvalidLocation(format!("{} has no latitude/longitude", self.address.city))),
+        }
+    }
+}
+
+impl Plot {
+    pub fn new(id: u32, name: &str, size: FarmSize, rotation: RotationPlan) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            size,
+            soil: SoilState::default(),
+            rotation,
+            history: Vec::new(),
+            greenhouse: None,
+        }
+    }
+
+    pub fn with_greenhouse(mut self, greenhouse: Greenhouse) -> Self {
+        self.greenhouse = Some(greenhouse);
+        self
+    }
+
+    /// Floor area in square metres.
+    pub fn area(&self) -> f32 {
+        (self.size.width * self.size.length) as f32
+    }
+
+    /// Yield multiplier coming from what was grown on the plot in the previous season.
+    /// Growing the same family twice in a row carries pests and diseases over,
+    /// while a rested plot gives the next crop a head start.
+    pub fn rotation_factor(&self, species: &str) -> f32 {
+        match self.history.last() {
+            Some(record) => match &record.step {
+                RotationStep::Crop(_) if record.species.as_deref() == Some(species) => 0.8,
+                RotationStep::Crop(_) => 1.0,
+                RotationStep::CoverCrop(_) | RotationStep::Fallow => 1.05,
+            },
+            None => 1.0,
+        }
+    }
+}
+
 impl FarmSize {
     fn new(width: u32, length: u32) -> FarmSize {
         FarmSize {
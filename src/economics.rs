@@ -0,0 +1,444 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::Serialize;
+
+use crate::fertilizer::Product;
+use crate::seeds::SeedType;
+
+
+/// Square metres in a hectare.
+pub const SQUARE_METRES_PER_HA: f32 = 10_000.0;
+/// Hours of hand labour needed to pick a kilogram of produce.
+const HARVEST_LABOUR_PER_KG: f32 = 0.05;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CostCategory {
+    Seed,
+    Water,
+    Fertilizer,
+    Fumigation,
+    Labour,
+    Machinery,
+    Energy,
+}
+
+impl fmt::Display for CostCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CostCategory::Seed => write!(f, "seed"),
+            CostCategory::Water => write!(f, "water"),
+            CostCategory::Fertilizer => write!(f, "fertilizer"),
+            CostCategory::Fumigation => write!(f, "fumigation"),
+            CostCategory::Labour => write!(f, "labour"),
+            CostCategory::Machinery => write!(f, "machinery"),
+            CostCategory::Energy => write!(f, "energy"),
+        }
+    }
+}
+
+/// Field operations carried out by `PlantService`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManagementAction {
+    Tillage,
+    Planting,
+    Irrigation,
+    Weeding,
+    Fertilizing,
+    Fumigation,
+    Transplanting,
+    Harvest,
+}
+
+impl ManagementAction {
+    /// Labour and machinery hours needed per hectare.
+    pub fn hours_per_ha(&self) -> (f32, f32) {
+        match self {
+            ManagementAction::Tillage => (2.0, 2.0),
+            ManagementAction::Planting => (6.0, 1.5),
+            ManagementAction::Irrigation => (1.0, 0.5),
+            ManagementAction::Weeding => (12.0, 0.0),
+            ManagementAction::Fertilizing => (0.5, 0.5),
+            ManagementAction::Fumigation => (1.0, 0.5),
+            ManagementAction::Transplanting => (2.0, 0.5),
+            ManagementAction::Harvest => (2.0, 1.0),
+        }
+    }
+
+    /// Extra labour hours for every plant the action handles.
+    pub fn hours_per_crop(&self) -> f32 {
+        match self {
+            ManagementAction::Planting => 0.05,
+            ManagementAction::Transplanting => 0.05,
+            ManagementAction::Weeding => 0.02,
+            ManagementAction::Harvest => 0.1,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Unit costs of the farm inputs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostTable {
+    /// Price of a single seed, by lower case verbose name
+    seed_prices: BTreeMap<String, f32>,
+    pub water_per_m3: f32,
+    pub fertilizer_per_kg: f32,
+    /// Price of a kg of the products a fertilizer plan spreads. Others cost `fertilizer_per_kg`
+    product_prices: BTreeMap<Product, f32>,
+    /// Fertilizer spread per application (kg/ha)
+    pub fertilizer_rate: f32,
+    /// Product cost of one fumigation (per ha)
+    pub fumigation_per_ha: f32,
+    pub labour_per_hour: f32,
+    pub machinery_per_hour: f32,
+    /// Greenhouse heating
+    pub energy_per_kwh: f32,
+}
+
+impl Default for CostTable {
+    fn default() -> Self {
+        let seed_prices = [("sun flower", 0.02), ("pea", 0.01), ("carrot", 0.002), ("tomato", 0.15), ("broccoli", 0.05)]
+            .into_iter()
+            .map(|(name, price)| (name.to_string(), price))
+            .collect();
+        let product_prices = [
+            (Product::Urea, 0.55),
+            (Product::CalciumAmmoniumNitrate, 0.45),
+            (Product::TripleSuperphosphate, 0.7),
+            (Product::MuriateOfPotash, 0.5),
+            (Product::GroundLimestone, 0.05),
+        ].into_iter().collect();
+        Self {
+            seed_prices,
+            water_per_m3: 0.8,
+            fertilizer_per_kg: 0.6,
+            product_prices,
+            fertilizer_rate: 150.0,
+            fumigation_per_ha: 60.0,
+            labour_per_hour: 15.0,
+            machinery_per_hour: 40.0,
+            energy_per_kwh: 0.25,
+        }
+    }
+}
+
+impl CostTable {
+    pub fn seed_price(&self, verbose_name: &str) -> f32 {
+        self.seed_prices.get(&verbose_name.to_lowercase()).copied().unwrap_or(0.0)
+    }
+
+    pub fn set_seed_price(&mut self, verbose_name: &str, price: f32) {
+        self.seed_prices.insert(verbose_name.to_lowercase(), price);
+    }
+
+    pub fn product_price(&self, product: Product) -> f32 {
+        self.product_prices.get(&product).copied().unwrap_or(self.fertilizer_per_kg)
+    }
+
+    /// Labour and machinery cost of an action over `area_ha`.
+    pub fn action_costs(&self, action: ManagementAction, area_ha: f32) -> [(CostCategory, f32); 2] {
+        let (labour_hours, machinery_hours) = action.hours_per_ha();
+        [
+            (CostCategory::Labour, labour_hours * area_ha * self.labour_per_hour),
+            (CostCategory::Machinery, machinery_hours * area_ha * self.machinery_per_hour),
+        ]
+    }
+
+    pub fn harvest_labour(&self, harvested_kg: f32) -> f32 {
+        harvested_kg * HARVEST_LABOUR_PER_KG * self.labour_per_hour
+    }
+}
+
+/// Market grade of the harvested produce.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QualityGrade {
+    Premium,
+    Standard,
+    Processing,
+}
+
+impl fmt::Display for QualityGrade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QualityGrade::Premium => write!(f, "premium"),
+            QualityGrade::Standard => write!(f, "standard"),
+            QualityGrade::Processing => write!(f, "processing"),
+        }
+    }
+}
+
+impl QualityGrade {
+    /// Well grown plants reach the premium grade, stunted ones only sell for processing.
+    pub fn from_yield(seed_type: &SeedType, yield_per_plant: f32) -> Self {
+        let relative_yield = yield_per_plant / seed_type.reference_yield();
+        if relative_yield >= 0.8 {
+            QualityGrade::Premium
+        } else if relative_yield >= 0.4 {
+            QualityGrade::Standard
+        } else {
+            QualityGrade::Processing
+        }
+    }
+}
+
+/// Farm gate prices per kg of fresh produce, by crop and grade.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceTable {
+    prices: BTreeMap<(String, QualityGrade), f32>,
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        let mut table = Self { prices: BTreeMap::new() };
+        for (name, standard_price) in [("sun flower", 0.6), ("pea", 2.5), ("carrot", 0.8), ("tomato", 1.8), ("broccoli", 2.2)] {
+            table.set_price(name, QualityGrade::Premium, standard_price * 1.3);
+            table.set_price(name, QualityGrade::Standard, standard_price);
+            table.set_price(name, QualityGrade::Processing, standard_price * 0.4);
+        }
+        table
+    }
+}
+
+impl PriceTable {
+    pub fn price(&self, verbose_name: &str, grade: QualityGrade) -> f32 {
+        self.prices.get(&(verbose_name.to_lowercase(), grade)).copied().unwrap_or(0.0)
+    }
+
+    pub fn set_price(&mut self, verbose_name: &str, grade: QualityGrade, price: f32) {
+        self.prices.insert((verbose_name.to_lowercase(), grade), price);
+    }
+}
+
+/// Money spent on and earned by one crop.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct CropAccount {
+    pub area_ha: f32,
+    pub harvested_kg: f32,
+    pub revenue: f32,
+    pub sold_kg: BTreeMap<QualityGrade, f32>,
+    pub costs: BTreeMap<CostCategory, f32>,
+}
+
+impl CropAccount {
+    pub fn total_costs(&self) -> f32 {
+        self.costs.values().sum()
+    }
+
+    /// Revenue minus the variable costs.
+    pub fn gross_margin(&self) -> f32 {
+        self.revenue - self.total_costs()
+    }
+
+    pub fn gross_margin_per_ha(&self) -> Option<f32> {
+        if self.area_ha > 0.0 {
+            Some(self.gross_margin() / self.area_ha)
+        } else {
+            None
+        }
+    }
+}
+
+/// Profit and loss of a run. Costs that cannot be tied to a crop, e.g. irrigating a fallow plot, are overheads.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct ProfitAndLoss {
+    pub crops: BTreeMap<String, CropAccount>,
+    pub overheads: BTreeMap<CostCategory, f32>,
+}
+
+impl ProfitAndLoss {
+    pub fn record_cost(&mut self, crop_name: Option<&str>, category: CostCategory, amount: f32) {
+        let costs = match crop_name {
+            Some(crop_name) => &mut self.crops.entry(crop_name.to_string()).or_default().costs,
+            None => &mut self.overheads,
+        };
+        *costs.entry(category).or_insert(0.0) += amount;
+    }
+
+    pub fn record_sale(&mut self, crop_name: &str, grade: QualityGrade, kg: f32, price: f32) {
+        let account = self.crops.entry(crop_name.to_string()).or_default();
+        account.harvested_kg += kg;
+        account.revenue += kg * price;
+        *account.sold_kg.entry(grade).or_insert(0.0) += kg;
+    }
+
+    pub fn add_area(&mut self, crop_name: &str, area_ha: f32) {
+        self.crops.entry(crop_name.to_string()).or_default().area_ha += area_ha;
+    }
+
+    /// Adds the costs, sales and area of `other`, e.g. another season or another farm.
+    pub fn merge(&mut self, other: &ProfitAndLoss) {
+        for (crop_name, other_account) in other.crops.iter() {
+            let account = self.crops.entry(crop_name.to_string()).or_default();
+            account.area_ha += other_account.area_ha;
+            account.harvested_kg += other_account.harvested_kg;
+            account.revenue += other_account.revenue;
+            for (grade, kg) in other_account.sold_kg.iter() {
+                *account.sold_kg.entry(*grade).or_insert(0.0) += kg;
+            }
+            for (category, amount) in other_account.costs.iter() {
+                *account.costs.entry(*category).or_insert(0.0) += amount;
+            }
+        }
+        for (category, amount) in other.overheads.iter() {
+            *self.overheads.entry(*category).or_insert(0.0) += amount;
+        }
+    }
+
+    pub fn revenue(&self) -> f32 {
+        self.crops.values().map(|account| account.revenue).sum()
+    }
+
+    pub fn costs_by_category(&self) -> BTreeMap<CostCategory, f32> {
+        let mut costs = self.overheads.clone();
+        for account in self.crops.values() {
+            for (category, amount) in account.costs.iter() {
+                *costs.entry(*category).or_insert(0.0) += amount;
+            }
+        }
+        costs
+    }
+
+    pub fn total_costs(&self) -> f32 {
+        self.costs_by_category().values().sum()
+    }
+
+    pub fn profit(&self) -> f32 {
+        self.revenue() - self.total_costs()
+    }
+
+    /// Profit per hectare of cropped land.
+    pub fn gross_margin_per_ha(&self) -> Option<f32> {
+        let area_ha: f32 = self.crops.values().map(|account| account.area_ha).sum();
+        if area_ha > 0.0 {
+            Some(self.profit() / area_ha)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for ProfitAndLoss {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Revenue: {:.2}", self.revenue())?;
+        for (category, amount) in self.costs_by_category() {
+            writeln!(f, "  {:<11} -{:.2}", category.to_string(), amount)?;
+        }
+        writeln!(f, "Profit: {:.2}", self.profit())?;
+        if let Some(margin) = self.gross_margin_per_ha() {
+            writeln!(f, "Gross margin: {:.2}/ha", margin)?;
+        }
+        for (crop_name, account) in self.crops.iter() {
+            write!(
+                f,
+                "  {:<10} revenue: {:>8.2} costs: {:>8.2} margin: {:>8.2}",
+                crop_name,
+                account.revenue,
+                account.total_costs(),
+                account.gross_margin(),
+            )?;
+            match account.gross_margin_per_ha() {
+                Some(margin) => writeln!(f, " ({:.2}/ha)", margin)?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() < tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    #[test]
+    fn action_costs_scale_with_the_area() {
+        let table = CostTable::default();
+
+        let [(labour, labour_cost), (machinery, machinery_cost)] = table.action_costs(ManagementAction::Tillage, 0.5);
+
+        assert_eq!((labour, machinery), (CostCategory::Labour, CostCategory::Machinery));
+        assert_close(labour_cost, 2.0 * 0.5 * 15.0, 1e-4);
+        assert_close(machinery_cost, 2.0 * 0.5 * 40.0, 1e-4);
+        let [(_, weeding_labour), (_, weeding_machinery)] = table.action_costs(ManagementAction::Weeding, 2.0);
+        assert_close(weeding_labour, 12.0 * 2.0 * 15.0, 1e-4);
+        assert_eq!(weeding_machinery, 0.0);
+    }
+
+    #[test]
+    fn unknown_seeds_are_free_and_listed_products_keep_their_own_price() {
+        let mut table = CostTable::default();
+        assert_eq!(table.seed_price("Pea"), 0.01);
+        assert_eq!(table.seed_price("Clover"), 0.0);
+
+        table.set_seed_price("Clover", 0.003);
+        table.fertilizer_per_kg = 0.9;
+
+        assert_eq!(table.seed_price("clover"), 0.003);
+        assert_eq!(table.product_price(Product::Urea), 0.55);
+    }
+
+    #[test]
+    fn the_grade_follows_the_yield_against_the_reference() {
+        let pea = SeedType::from_str("Pea").unwrap();
+
+        assert_eq!(QualityGrade::from_yield(&pea, 0.05), QualityGrade::Premium);
+        assert_eq!(QualityGrade::from_yield(&pea, 0.03), QualityGrade::Standard);
+        assert_eq!(QualityGrade::from_yield(&pea, 0.01), QualityGrade::Processing);
+        let prices = PriceTable::default();
+        assert!(prices.price("Pea", QualityGrade::Premium) > prices.price("Pea", QualityGrade::Standard));
+        assert!(prices.price("Pea", QualityGrade::Standard) > prices.price("Pea", QualityGrade::Processing));
+    }
+
+    #[test]
+    fn profit_is_revenue_less_crop_costs_and_overheads() {
+        let mut pnl = ProfitAndLoss::default();
+        pnl.add_area("Pea", 0.5);
+        pnl.record_sale("Pea", QualityGrade::Premium, 100.0, 3.0);
+        pnl.record_sale("Pea", QualityGrade::Standard, 50.0, 2.0);
+        pnl.record_cost(Some("Pea"), CostCategory::Seed, 40.0);
+        pnl.record_cost(Some("Pea"), CostCategory::Seed, 10.0);
+        pnl.record_cost(None, CostCategory::Water, 30.0);
+
+        let pea = &pnl.crops["Pea"];
+        assert_close(pea.harvested_kg, 150.0, 1e-4);
+        assert_close(pea.revenue, 400.0, 1e-4);
+        assert_close(pea.gross_margin(), 350.0, 1e-4);
+        assert_close(pea.gross_margin_per_ha().unwrap(), 700.0, 1e-3);
+        assert_close(pnl.costs_by_category()[&CostCategory::Seed], 50.0, 1e-4);
+        assert_close(pnl.profit(), 320.0, 1e-4);
+        assert_close(pnl.gross_margin_per_ha().unwrap(), 640.0, 1e-3);
+    }
+
+    #[test]
+    fn there_is_no_margin_per_hectare_without_cropped_land() {
+        let mut pnl = ProfitAndLoss::default();
+        pnl.record_cost(None, CostCategory::Energy, 12.0);
+
+        assert_eq!(pnl.gross_margin_per_ha(), None);
+        assert_close(pnl.profit(), -12.0, 1e-4);
+    }
+
+    #[test]
+    fn merging_adds_up_both_accounts() {
+        let mut season = ProfitAndLoss::default();
+        season.add_area("Pea", 0.5);
+        season.record_sale("Pea", QualityGrade::Standard, 10.0, 2.0);
+        season.record_cost(Some("Pea"), CostCategory::Labour, 5.0);
+        season.record_cost(None, CostCategory::Water, 1.0);
+        let mut total = season.clone();
+
+        total.merge(&season);
+
+        let pea = &total.crops["Pea"];
+        assert_close(pea.area_ha, 1.0, 1e-4);
+        assert_close(pea.sold_kg[&QualityGrade::Standard], 20.0, 1e-4);
+        assert_close(pea.costs[&CostCategory::Labour], 10.0, 1e-4);
+        assert_close(total.overheads[&CostCategory::Water], 2.0, 1e-4);
+        assert_close(total.profit(), 2.0 * season.profit(), 1e-4);
+    }
+}
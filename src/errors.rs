@@ -1,15 +1,31 @@
+#![allow(dead_code)]
+
 use std::fmt;
-use collections::HashMap;
+use std::collections::HashMap;
 
 
-enum AgroException {
+#[derive(Debug)]
+pub enum AgroException {
     InvalidInput,
     InvalidFarmError(String),
     InvalidCropError(String),
+    InvalidLocation(String),
     FarmNotFound,
     Unauthorized(String),
     InvalidSeasonCropError(String, Option<HashMap<String, String>>),
     PermissionDenied,
+    InvalidInventory(String),
+    InsufficientStock(String),
+    StorageFull(String),
+    RegistryUnavailable(String),
+    InvalidExperiment(String),
+    InvalidObservations(String),
+    InvalidForecast(String),
+    /// A fertilizer request with a soil test or target yield out of range
+    InvalidFertilizerRequest(String),
+    /// The run was stopped before it finished
+    Cancelled,
+    ServerError(String),
 }
 
 impl fmt::Display for AgroException {
@@ -18,12 +34,23 @@ impl fmt::Display for AgroException {
             AgroException::InvalidInput => write!(f, "Invalid input"),
             AgroException::InvalidFarmError(msg) => write!(f, "Invalid farm: {}", msg),
             AgroException::InvalidCropError(msg) => write!(f, "Invalid crop: {}", msg),
+            AgroException::InvalidLocation(msg) => write!(f, "Invalid location: {}", msg),
             AgroException::FarmNotFound => write!(f, "Farm not found"),
             AgroException::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             AgroException::InvalidSeasonCropError(msg, params) => {
-                write!(f, "Invalid season crop: {} with params: {}", msg, params)
+                write!(f, "Invalid season crop: {} with params: {:?}", msg, params.as_ref().unwrap_or(&HashMap::new()))
             },
             AgroException::PermissionDenied => write!(f, "Permission denied"),
+            AgroException::InvalidInventory(msg) => write!(f, "Invalid inventory: {}", msg),
+            AgroException::InsufficientStock(msg) => write!(f, "Insufficient seed stock: {}", msg),
+            AgroException::StorageFull(msg) => write!(f, "Storage full: {}", msg),
+            AgroException::RegistryUnavailable(msg) => write!(f, "Farm registry unavailable: {}", msg),
+            AgroException::InvalidExperiment(msg) => write!(f, "Invalid experiment: {}", msg),
+            AgroException::InvalidObservations(msg) => write!(f, "Invalid observations: {}", msg),
+            AgroException::InvalidForecast(msg) => write!(f, "Invalid forecast: {}", msg),
+            AgroException::InvalidFertilizerRequest(msg) => write!(f, "Invalid fertilizer request: {}", msg),
+            AgroException::Cancelled => write!(f, "Simulation cancelled"),
+            AgroException::ServerError(msg) => write!(f, "Server error: {}", msg),
         }
     }
 }
@@ -0,0 +1,359 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use chrono::NaiveDate;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::Hemisphere;
+use crate::dto::Farm;
+use crate::errors::AgroException;
+use crate::fertilizer::FertilizerPlan;
+use crate::irrigation::IrrigationPlan;
+use crate::plant_service::ManagementPolicy;
+use crate::rotation::{RotationReport, RotationRunner, RotationStep};
+use crate::statistics::{mean, PairedTTest};
+use crate::weather_service::WeatherSeries;
+
+
+/// Differences with a p-value below this are flagged as significant.
+pub const SIGNIFICANCE_LEVEL: f64 = 0.05;
+/// Gap between the seeds of two replicates, so the seasons of one never reuse the seeds of the next.
+pub const REPLICATE_SEED_STRIDE: u64 = 1000;
+
+/// Names of the cash crops the farm sows in the first `seasons` seasons of its rotation.
+pub fn sown_crops(farm: &Farm, seasons: u32) -> Vec<String> {
+    (0..seasons)
+        .flat_map(|season| farm.plots.iter().map(move |plot| plot.rotation.step_for(season)))
+        .filter_map(|step| match step {
+            RotationStep::Crop(name) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Outcome of a rotation compared between the arms of an experiment.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Harvested produce (kg)
+    Yield,
+    /// Crops lost before the harvest
+    Failures,
+    /// Irrigation water (m³)
+    WaterUse,
+    /// All the costs of the run
+    Cost,
+    Profit,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 5] = [Metric::Yield, Metric::Failures, Metric::WaterUse, Metric::Cost, Metric::Profit];
+
+    pub fn of(&self, report: &RotationReport) -> f64 {
+        report.seasons.iter()
+            .map(|season| match self {
+                Metric::Yield => season.total_yield() as f64,
+                Metric::Failures => season.total_failed() as f64,
+                Metric::WaterUse => season.water_m3 as f64,
+                Metric::Cost => season.economics.total_costs() as f64,
+                Metric::Profit => season.economics.profit() as f64,
+            })
+            .sum()
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Metric::Yield => write!(f, "Yield (kg)"),
+            Metric::Failures => write!(f, "Failures"),
+            Metric::WaterUse => write!(f, "Water use (m³)"),
+            Metric::Cost => write!(f, "Cost"),
+            Metric::Profit => write!(f, "Profit"),
+        }
+    }
+}
+
+/// Management parameters a scenario changes. Those left out keep the value they are given by the
+/// baseline, or the default policy for the baseline itself.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Overrides {
+    pub irrigation_interval: Option<u32>,
+    pub weeding_interval: Option<u32>,
+    pub fertilizing_interval: Option<u32>,
+    pub fertilizing_until: Option<u32>,
+    pub fumigation_day: Option<u32>,
+    /// Crops sown from GMO (true) or conventional (false) seed
+    #[serde(default)]
+    pub gmo_seed: BTreeMap<String, bool>,
+    /// Irrigations planned day by day, e.g. by an irrigation study, instead of the interval
+    pub irrigation_plan: Option<IrrigationPlan>,
+    pub water_budget_m3: Option<f32>,
+    /// Fertilizer applications by crop name, e.g. as recommended from a soil test, instead of the interval
+    #[serde(default)]
+    pub fertilizer_plans: BTreeMap<String, FertilizerPlan>,
+}
+
+impl Overrides {
+    pub fn apply(&self, policy: &ManagementPolicy) -> ManagementPolicy {
+        let mut policy = policy.clone();
+        policy.irrigation_interval = self.irrigation_interval.unwrap_or(policy.irrigation_interval);
+        policy.weeding_interval = self.weeding_interval.unwrap_or(policy.weeding_interval);
+        policy.fertilizing_interval = self.fertilizing_interval.unwrap_or(policy.fertilizing_interval);
+        policy.fertilizing_until = self.fertilizing_until.unwrap_or(policy.fertilizing_until);
+        policy.fumigation_day = self.fumigation_day.unwrap_or(policy.fumigation_day);
+        policy.gmo_seed.extend(self.gmo_seed.iter().map(|(crop_name, gmo)| (crop_name.to_string(), *gmo)));
+        if let Some(plan) = self.irrigation_plan.as_ref() {
+            plan.apply(&mut policy);
+        }
+        policy.water_budget_m3 = self.water_budget_m3.or(policy.water_budget_m3);
+        policy.fertilizer_plans.extend(self.fertilizer_plans.iter().map(|(crop_name, plan)| (crop_name.to_string(), plan.clone())));
+        policy
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    #[serde(flatten)]
+    pub overrides: Overrides,
+}
+
+/// A baseline scenario and variants of it, each run on the same farm for a number of replicates.
+///
+/// Replicate `n` of every arm gets the same seeds and the same weather, so the arms differ only by
+/// their overrides (common random numbers) and are compared replicate by replicate.
+///
+/// ```json
+/// {
+///   "name": "Irrigation interval",
+///   "start_date": "2025-03-01",
+///   "replicates": 30,
+///   "variants": [{"name": "Every 2 days", "irrigation_interval": 2}]
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Experiment {
+    pub name: String,
+    pub start_date: NaiveDate,
+    #[serde(default = "Experiment::default_seasons")]
+    pub seasons: u32,
+    #[serde(default = "Experiment::default_seasons_per_year")]
+    pub seasons_per_year: u32,
+    #[serde(default = "Experiment::default_replicates")]
+    pub replicates: u32,
+    /// Seed of the first replicate
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub baseline: Overrides,
+    pub variants: Vec<Variant>,
+}
+
+impl Experiment {
+    fn default_seasons() -> u32 {
+        1
+    }
+
+    fn default_seasons_per_year() -> u32 {
+        2
+    }
+
+    fn default_replicates() -> u32 {
+        20
+    }
+
+    /// Reads an experiment from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AgroException> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", path.display(), err)))?;
+        let experiment: Experiment = serde_json::from_str(&content)
+            .map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", path.display(), err)))?;
+        experiment.validate()?;
+        Ok(experiment)
+    }
+
+    fn validate(&self) -> Result<(), AgroException> {
+        if self.replicates < 2 {
+            return Err(AgroException::InvalidExperiment("at least 2 replicates are needed to compare the variants".to_string()));
+        }
+        if self.variants.is_empty() {
+            return Err(AgroException::InvalidExperiment("no variants to compare with the baseline".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Name and management policy of the baseline followed by every variant.
+    pub fn arms(&self) -> Vec<(String, ManagementPolicy)> {
+        let baseline = self.baseline.apply(&ManagementPolicy::default());
+        let mut arms = vec![("Baseline".to_string(), baseline.clone())];
+        arms.extend(self.variants.iter().map(|variant| (variant.name.to_string(), variant.overrides.apply(&baseline))));
+        arms
+    }
+
+    /// Fails if an arm overrides a crop the farm does not sow in any season of the run, as the
+    /// override would change nothing.
+    fn check_crops(&self, farm: &Farm) -> Result<(), AgroException> {
+        let sown = sown_crops(farm, self.seasons);
+        let arms = std::iter::once(("Baseline", &self.baseline))
+            .chain(self.variants.iter().map(|variant| (variant.name.as_str(), &variant.overrides)));
+        for (name, overrides) in arms {
+            let crops = overrides.gmo_seed.keys().chain(overrides.fertilizer_plans.keys());
+            if let Some(crop) = crops.into_iter().find(|crop| !sown.iter().any(|sown| sown.eq_ignore_ascii_case(crop))) {
+                return Err(AgroException::InvalidExperiment(format!(
+                    "{} overrides {}, which the farm does not sow in the {} season(s) of the run",
+                    name,
+                    crop,
+                    self.seasons
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs every arm for every replicate on a fresh farm from `farm`, the replicates in parallel.
+    pub fn run(&self, farm: impl Fn() -> Result<Farm, AgroException> + Sync) -> Result<ExperimentReport, AgroException> {
+        self.validate()?;
+        self.check_crops(&farm()?)?;
+        let arms = self.arms();
+        let replicates: Vec<Vec<RotationReport>> = (0..self.replicates).into_par_iter()
+            .map(|replicate| self.run_replicate(replicate, &arms, &farm))
+            .collect::<Result<_, _>>()?;
+
+        let results: Vec<ArmResult> = arms.into_iter().enumerate()
+            .map(|(arm, (name, policy))| {
+                let outcomes = Metric::ALL.iter()
+                    .map(|metric| (*metric, replicates.iter().map(|reports| metric.of(&reports[arm])).collect()))
+                    .collect();
+                ArmResult { name, policy, outcomes }
+            })
+            .collect();
+        let comparisons = results.iter().skip(1)
+            .flat_map(|variant| Metric::ALL.iter().map(|metric| Comparison::new(&results[0], variant, *metric)))
+            .collect();
+
+        Ok(ExperimentReport {
+            name: self.name.to_string(),
+            replicates: self.replicates,
+            arms: results,
+            comparisons,
+        })
+    }
+
+    fn run_replicate(
+        &self,
+        replicate: u32,
+        arms: &[(String, ManagementPolicy)],
+        farm: &(impl Fn() -> Result<Farm, AgroException> + Sync),
+    ) -> Result<Vec<RotationReport>, AgroException> {
+        let seed = self.seed.wrapping_add(replicate as u64 * REPLICATE_SEED_STRIDE);
+        let mut weather_series = None;
+        let mut reports = Vec::new();
+        for (_, policy) in arms {
+            let runner = RotationRunner::new(farm()?, self.seasons, self.seasons_per_year)
+                .starting_on(self.start_date)
+                .quiet()
+                .with_policy(policy.clone())
+                .with_seed(seed);
+            let weather_series = weather_series.get_or_insert_with(|| {
+                let hemisphere = Hemisphere::from_location(&runner.farm().location);
+                let mut rng = StdRng::seed_from_u64(seed);
+                Arc::new(WeatherSeries::generate(runner.start_date(), runner.end_date(), hemisphere, &mut rng))
+            });
+            reports.push(runner.with_weather(weather_series.clone()).run()?);
+        }
+        Ok(reports)
+    }
+}
+
+/// Outcomes of every replicate of one arm.
+#[derive(Serialize, Debug, Clone)]
+pub struct ArmResult {
+    pub name: String,
+    pub policy: ManagementPolicy,
+    pub outcomes: BTreeMap<Metric, Vec<f64>>,
+}
+
+impl ArmResult {
+    pub fn mean(&self, metric: Metric) -> f64 {
+        self.outcomes.get(&metric).map_or(0.0, |values| mean(values))
+    }
+}
+
+/// A variant against the baseline on one metric.
+#[derive(Serialize, Debug, Clone)]
+pub struct Comparison {
+    pub variant: String,
+    pub metric: Metric,
+    pub baseline_mean: f64,
+    pub variant_mean: f64,
+    pub test: PairedTTest,
+}
+
+impl Comparison {
+    fn new(baseline: &ArmResult, variant: &ArmResult, metric: Metric) -> Self {
+        let empty = Vec::new();
+        Self {
+            variant: variant.name.to_string(),
+            metric,
+            baseline_mean: baseline.mean(metric),
+            variant_mean: variant.mean(metric),
+            test: PairedTTest::new(
+                baseline.outcomes.get(&metric).unwrap_or(&empty),
+                variant.outcomes.get(&metric).unwrap_or(&empty),
+            ),
+        }
+    }
+
+    pub fn is_significant(&self) -> bool {
+        self.test.is_significant(SIGNIFICANCE_LEVEL)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ExperimentReport {
+    pub name: String,
+    pub replicates: u32,
+    pub arms: Vec<ArmResult>,
+    pub comparisons: Vec<Comparison>,
+}
+
+impl fmt::Display for ExperimentReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Experiment: {} ({} replicates, common random numbers)", self.name, self.replicates)?;
+        write!(f, "  {:<16}", "Mean")?;
+        for arm in self.arms.iter() {
+            write!(f, " {:>14}", arm.name)?;
+        }
+        writeln!(f)?;
+        for metric in Metric::ALL {
+            write!(f, "  {:<16}", metric.to_string())?;
+            for arm in self.arms.iter() {
+                write!(f, " {:>14.3}", arm.mean(metric))?;
+            }
+            writeln!(f)?;
+        }
+        for comparison in self.comparisons.iter() {
+            writeln!(
+                f,
+                "  {} vs {}: {:<16} {:>+10.3} (t = {:>6.2}, p = {:.4}){}",
+                comparison.variant,
+                self.arms[0].name,
+                comparison.metric.to_string(),
+                comparison.test.mean_difference,
+                comparison.test.t,
+                comparison.test.p_value,
+                if comparison.is_significant() { " *" } else { "" },
+            )?;
+        }
+        writeln!(f, "  * significant at p < {}", SIGNIFICANCE_LEVEL)
+    }
+}
@@ -0,0 +1,303 @@
+#![allow(dead_code)]
+
+use std::fmt;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::dto::{Crop, GrowthEvent, GrowthStage};
+use crate::growth::GrowthParameters;
+use crate::seeds::SeedType;
+use crate::weather_service::WeatherCondition;
+
+
+/// Soil moisture (fraction of field capacity) below which the crop is short of water.
+const DROUGHT_THRESHOLD: f32 = 0.2;
+/// Days after fumigation during which pests are kept in check.
+const FUMIGATION_PROTECTION: u32 = 21;
+/// Share of the pest pressure insect resistant (GMO) varieties still suffer
+const GMO_PEST_FACTOR: f32 = 0.4;
+/// Causes with a daily hazard: drought, waterlogging, frost, heat, disease and pests.
+const HAZARD_CAUSES: usize = 6;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FailureCause {
+    Drought,
+    Waterlogging,
+    Frost,
+    Heat,
+    Disease,
+    Pest,
+    GerminationFailure,
+    /// Failure drawn by the naive model, which does not look at the conditions
+    Unknown,
+}
+
+impl fmt::Display for FailureCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FailureCause::Drought => write!(f, "drought"),
+            FailureCause::Waterlogging => write!(f, "waterlogging"),
+            FailureCause::Frost => write!(f, "frost"),
+            FailureCause::Heat => write!(f, "heat"),
+            FailureCause::Disease => write!(f, "disease"),
+            FailureCause::Pest => write!(f, "pest"),
+            FailureCause::GerminationFailure => write!(f, "germination failure"),
+            FailureCause::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// How crop failures are decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailureModel {
+    /// Daily failure probabilities driven by weather, soil and management
+    #[default]
+    Causal,
+    /// The original model: a constant chance of failing at every stage transition
+    Naive,
+}
+
+/// Conditions on the farm that are shared by every crop on a given day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DailyEnvironment {
+    pub weather: WeatherCondition,
+    pub min_temperature: f32,
+    pub max_temperature: f32,
+    pub days_since_weeding: u32,
+    pub days_since_fumigation: Option<u32>,
+    /// Under glass, where rain does not reach the crop
+    pub sheltered: bool,
+}
+
+impl DailyEnvironment {
+    pub fn is_wet(&self) -> bool {
+        self.weather.is_wet() && !self.sheltered
+    }
+}
+
+/// Conditions of the plot a crop grows on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlotEnvironment {
+    pub soil_moisture: f32,
+    pub compaction: f32,
+    /// Below 1.0 when the same family was grown on the plot last season
+    pub rotation_factor: f32,
+}
+
+impl Default for PlotEnvironment {
+    fn default() -> Self {
+        Self {
+            soil_moisture: 1.0,
+            compaction: 0.0,
+            rotation_factor: 1.0,
+        }
+    }
+}
+
+/// Consecutive days of stress a crop has been through. Hazards grow the longer a stress lasts.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct FailureRisk {
+    pub dry_days: u32,
+    pub waterlogged_days: u32,
+    pub wet_days: u32,
+}
+
+impl FailureRisk {
+    fn update(&mut self, daily: &DailyEnvironment, plot: &PlotEnvironment) {
+        self.dry_days = if plot.soil_moisture < DROUGHT_THRESHOLD { self.dry_days + 1 } else { 0 };
+        self.waterlogged_days = if plot.soil_moisture >= 1.0 && daily.is_wet() { self.waterlogged_days + 1 } else { 0 };
+        self.wet_days = if daily.is_wet() { self.wet_days + 1 } else { 0 };
+    }
+}
+
+impl FailureModel {
+    /// Decides whether the crop fails today, and why.
+    pub fn assess(&self, crop: &mut Crop, daily: &DailyEnvironment, plot: &PlotEnvironment, rng: &mut impl Rng) -> Option<FailureCause> {
+        let stage = crop.current_stage.clone()?;
+        if crop.is_inactive() {
+            return None;
+        }
+        match self {
+            FailureModel::Naive => {
+                // Flipped every day, so that runs sharing a seed keep drawing the same numbers.
+                let event = GrowthEvent::coin_flip(rng);
+                if crop.is_stage_complete() && event == GrowthEvent::Fail {
+                    Some(FailureCause::Unknown)
+                } else {
+                    None
+                }
+            },
+            FailureModel::Causal => {
+                let seed_type = SeedType::from_str(&crop.verbose_name).ok()?;
+                let risk = crop.risk.get_or_insert_with(FailureRisk::default);
+                risk.update(daily, plot);
+                let hazards = Self::hazards(&seed_type, &stage, crop.is_gmo, risk, daily, plot);
+                // One draw per cause, whatever the outcome, so that runs sharing a seed keep drawing the same numbers.
+                let draws: [f32; HAZARD_CAUSES] = std::array::from_fn(|_| rng.gen());
+                hazards.into_iter().zip(draws).find(|((_, probability), draw)| draw < probability).map(|((cause, _), _)| cause)
+            },
+        }
+    }
+
+    /// Daily failure probability of each cause, always in the same order.
+    fn hazards(
+        seed_type: &SeedType,
+        stage: &GrowthStage,
+        is_gmo: bool,
+        risk: &FailureRisk,
+        daily: &DailyEnvironment,
+        plot: &PlotEnvironment,
+    ) -> [(FailureCause, f32); HAZARD_CAUSES] {
+        let frost_tolerance = seed_type.frost_tolerance();
+        let frost = (0.15 * (frost_tolerance - daily.min_temperature)).clamp(0.0, 1.0);
+
+        let maximum_temperature = GrowthParameters::for_seed_type(seed_type).maximum_temperature;
+        let sensitivity = if *stage == GrowthStage::Flowering { 0.15 } else { 0.03 };
+        let heat = sensitivity * (daily.max_temperature - maximum_temperature).max(0.0);
+
+        // Leaf diseases spread in long wet spells, and faster when the previous crop hosted them.
+        let disease = (0.0005 + 0.002 * risk.wet_days as f32) / plot.rotation_factor.powi(3);
+
+        let fumigated = daily.days_since_fumigation.is_some_and(|days| days <= FUMIGATION_PROTECTION);
+        let pest = (0.0005 + 0.0002 * daily.days_since_weeding as f32)
+            * if fumigated { 0.2 } else { 1.0 }
+            * if is_gmo { GMO_PEST_FACTOR } else { 1.0 };
+
+        let hazards = [
+            (FailureCause::Drought, 0.01 * risk.dry_days as f32),
+            (FailureCause::Waterlogging, 0.02 * risk.waterlogged_days as f32 * (1.0 + plot.compaction)),
+            (FailureCause::Frost, frost),
+            (FailureCause::Heat, heat),
+            (FailureCause::Disease, disease),
+            (FailureCause::Pest, pest),
+        ];
+        // Whether a seed comes up is drawn at sowing by the germination model.
+        if *stage == GrowthStage::Seed {
+            return hazards.map(|(cause, _)| (cause, 0.0));
+        }
+        hazards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use super::*;
+    use crate::seeds::SeedBag;
+    use crate::setup::crops_from_bag;
+
+    fn mild_day() -> DailyEnvironment {
+        DailyEnvironment {
+            weather: WeatherCondition::Sunny,
+            min_temperature: 12.0,
+            max_temperature: 22.0,
+            days_since_weeding: 0,
+            days_since_fumigation: None,
+            sheltered: false,
+        }
+    }
+
+    fn crop(crop_name: &str, stage: GrowthStage) -> Crop {
+        let bag = SeedBag::new(1, crop_name, crop_name.to_string()).unwrap();
+        let mut crop = crops_from_bag(&bag, 1, Some(1)).remove(0);
+        crop.current_stage = Some(stage);
+        crop
+    }
+
+    fn hazard(cause: FailureCause, crop_name: &str, stage: GrowthStage, risk: &FailureRisk, daily: &DailyEnvironment) -> f32 {
+        let seed_type = SeedType::from_str(crop_name).unwrap();
+        FailureModel::hazards(&seed_type, &stage, false, risk, daily, &PlotEnvironment::default()).into_iter()
+            .find(|(hazard_cause, _)| *hazard_cause == cause)
+            .map(|(_, probability)| probability)
+            .unwrap()
+    }
+
+    #[test]
+    fn frost_only_threatens_below_the_tolerance_of_the_crop() {
+        let frost = |crop_name: &str, min_temperature: f32| {
+            let daily = DailyEnvironment { min_temperature, ..mild_day() };
+            hazard(FailureCause::Frost, crop_name, GrowthStage::Vegetative, &FailureRisk::default(), &daily)
+        };
+
+        assert_eq!(frost("Tomato", 1.0), 0.0);
+        assert!((frost("Tomato", -2.0) - 0.3).abs() < 1e-6);
+        assert_eq!(frost("Tomato", -10.0), 1.0);
+        // Peas stand down to -6 °C.
+        assert_eq!(frost("Pea", -4.0), 0.0);
+    }
+
+    #[test]
+    fn drought_grows_with_the_dry_spell_and_ends_with_it() {
+        let mut risk = FailureRisk::default();
+        let dry = PlotEnvironment { soil_moisture: 0.1, ..PlotEnvironment::default() };
+        for _ in 0..5 {
+            risk.update(&mild_day(), &dry);
+        }
+        assert!((hazard(FailureCause::Drought, "Pea", GrowthStage::Vegetative, &risk, &mild_day()) - 0.05).abs() < 1e-6);
+
+        risk.update(&mild_day(), &PlotEnvironment::default());
+        assert_eq!(hazard(FailureCause::Drought, "Pea", GrowthStage::Vegetative, &risk, &mild_day()), 0.0);
+    }
+
+    #[test]
+    fn heat_hurts_most_at_flowering() {
+        // 5 °C above the 30 °C maximum of the pea.
+        let daily = DailyEnvironment { max_temperature: 35.0, ..mild_day() };
+        let vegetative = hazard(FailureCause::Heat, "Pea", GrowthStage::Vegetative, &FailureRisk::default(), &daily);
+        let flowering = hazard(FailureCause::Heat, "Pea", GrowthStage::Flowering, &FailureRisk::default(), &daily);
+
+        assert!((vegetative - 0.15).abs() < 1e-6);
+        assert!((flowering - 0.75).abs() < 1e-6);
+        assert_eq!(hazard(FailureCause::Heat, "Pea", GrowthStage::Flowering, &FailureRisk::default(), &mild_day()), 0.0);
+    }
+
+    #[test]
+    fn fumigation_and_insect_resistance_keep_pests_down() {
+        let seed_type = SeedType::from_str("Carrot").unwrap();
+        let pest = |is_gmo: bool, days_since_fumigation: Option<u32>| {
+            let daily = DailyEnvironment { days_since_weeding: 10, days_since_fumigation, ..mild_day() };
+            let hazards = FailureModel::hazards(&seed_type, &GrowthStage::Vegetative, is_gmo, &FailureRisk::default(), &daily, &PlotEnvironment::default());
+            hazards.into_iter().find(|(cause, _)| *cause == FailureCause::Pest).unwrap().1
+        };
+
+        let untreated = pest(false, None);
+        assert!((untreated - 0.0025).abs() < 1e-7);
+        assert!((pest(false, Some(5)) - 0.2 * untreated).abs() < 1e-7);
+        assert!((pest(false, Some(FUMIGATION_PROTECTION + 1)) - untreated).abs() < 1e-7);
+        assert!((pest(true, None) - GMO_PEST_FACTOR * untreated).abs() < 1e-7);
+    }
+
+    #[test]
+    fn seeds_in_the_ground_do_not_fail_of_the_weather() {
+        let daily = DailyEnvironment { min_temperature: -20.0, max_temperature: 45.0, ..mild_day() };
+        let seed_type = SeedType::from_str("Tomato").unwrap();
+        let hazards = FailureModel::hazards(&seed_type, &GrowthStage::Seed, false, &FailureRisk::default(), &daily, &PlotEnvironment::default());
+
+        assert!(hazards.iter().all(|(_, probability)| *probability == 0.0));
+    }
+
+    #[test]
+    fn a_hard_frost_kills_a_tender_crop() {
+        let daily = DailyEnvironment { min_temperature: -10.0, ..mild_day() };
+        let mut crop = crop("Tomato", GrowthStage::Vegetative);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let cause = FailureModel::Causal.assess(&mut crop, &daily, &PlotEnvironment::default(), &mut rng);
+
+        assert_eq!(cause, Some(FailureCause::Frost));
+    }
+
+    #[test]
+    fn every_assessment_draws_the_same_numbers() {
+        // A crop that fails and one that does not leave the generator in the same state.
+        let mut doomed = crop("Tomato", GrowthStage::Vegetative);
+        let mut healthy = crop("Tomato", GrowthStage::Vegetative);
+        let (mut first, mut second) = (StdRng::seed_from_u64(2), StdRng::seed_from_u64(2));
+
+        let frost = DailyEnvironment { min_temperature: -10.0, ..mild_day() };
+        assert!(FailureModel::Causal.assess(&mut doomed, &frost, &PlotEnvironment::default(), &mut first).is_some());
+        assert!(FailureModel::Causal.assess(&mut healthy, &mild_day(), &PlotEnvironment::default(), &mut second).is_none());
+
+        assert_eq!(first.gen::<u64>(), second.gen::<u64>());
+    }
+}
@@ -0,0 +1,444 @@
+#![allow(dead_code)]
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use chrono::NaiveDate;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::Hemisphere;
+use crate::dto::{Farm, GrowthStage};
+use crate::economics::{CostCategory, CostTable};
+use crate::errors::AgroException;
+use crate::experiment::REPLICATE_SEED_STRIDE;
+use crate::plant_service::ManagementPolicy;
+use crate::rotation::{RotationRunner, RotationStep};
+use crate::seeds::SeedType;
+use crate::soil::SoilState;
+use crate::statistics::{mean, PairedTTest};
+use crate::weather_service::WeatherSeries;
+
+
+/// Nitrogen (kg/ha) released over a season by each percent of organic matter.
+const MINERALISATION_PER_OM: f32 = 10.0;
+/// Legumes fix their own nitrogen and only get a starter dose.
+const LEGUME_STARTER_N: f32 = 30.0;
+/// Below this much nitrogen (kg/ha) it is all given at sowing.
+const MIN_SPLIT_N: f32 = 40.0;
+/// Phosphate (P2O5) and potash (K2O) taken off with the crop, per kg of nitrogen it needs.
+const PHOSPHATE_PER_N: f32 = 0.4;
+const POTASH_PER_N: f32 = 1.2;
+/// Olsen P (mg/kg) and exchangeable K (mg/kg) below which the soil is built up, and above twice
+/// which it needs nothing.
+const CRITICAL_PHOSPHORUS: f32 = 16.0;
+const CRITICAL_POTASSIUM: f32 = 120.0;
+/// Fertilizer (kg/ha) it takes to raise the soil test by 1 mg/kg.
+const PHOSPHATE_BUILD_UP: f32 = 5.0;
+const POTASH_BUILD_UP: f32 = 2.0;
+/// Lime (t/ha) that raises the pH by one unit, plus this much per percent of organic matter.
+const LIME_PER_PH: f32 = 1.5;
+const LIME_PER_PH_AND_OM: f32 = 0.5;
+/// Urea loses too much ammonia on soils above this pH.
+const UREA_MAX_PH: f32 = 7.0;
+/// Products are not spread below this rate (kg/ha).
+const MIN_RATE: f32 = 1.0;
+
+/// Laboratory analysis of a soil sample.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SoilTest {
+    /// Mineral nitrogen (kg/ha)
+    pub nitrogen: f32,
+    /// Olsen phosphorus (mg/kg)
+    pub phosphorus: f32,
+    /// Exchangeable potassium (mg/kg)
+    pub potassium: f32,
+    pub ph: f32,
+    /// Organic matter (%)
+    pub organic_matter: f32,
+}
+
+impl SoilTest {
+    fn validate(&self) -> Result<(), AgroException> {
+        if [self.nitrogen, self.phosphorus, self.potassium, self.organic_matter].iter().any(|value| *value < 0.0) {
+            return Err(AgroException::InvalidFertilizerRequest("soil test values cannot be negative".to_string()));
+        }
+        if !(3.0..=10.0).contains(&self.ph) {
+            return Err(AgroException::InvalidFertilizerRequest(format!("soil test pH {} is out of range", self.ph)));
+        }
+        Ok(())
+    }
+
+    /// Sets the soil of a plot to the values of the test.
+    pub fn apply(&self, soil: &mut SoilState) {
+        soil.nitrogen = self.nitrogen;
+        soil.organic_matter = self.organic_matter;
+        soil.ph = self.ph;
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Product {
+    Urea,
+    CalciumAmmoniumNitrate,
+    TripleSuperphosphate,
+    MuriateOfPotash,
+    GroundLimestone,
+}
+
+impl Product {
+    /// Share of N, P2O5 and K2O by weight.
+    pub fn analysis(&self) -> (f32, f32, f32) {
+        match self {
+            Product::Urea => (0.46, 0.0, 0.0),
+            Product::CalciumAmmoniumNitrate => (0.27, 0.0, 0.0),
+            Product::TripleSuperphosphate => (0.0, 0.46, 0.0),
+            Product::MuriateOfPotash => (0.0, 0.0, 0.6),
+            Product::GroundLimestone => (0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Spreads `rate` kg/ha on the soil. Only the nitrogen and the lime change the soil state, and of
+    /// the two only the nitrogen changes the growth of the crops.
+    pub fn apply(&self, rate: f32, soil: &mut SoilState) {
+        match self {
+            Product::GroundLimestone => soil.ph += rate / 1000.0 / (LIME_PER_PH + LIME_PER_PH_AND_OM * soil.organic_matter),
+            _ => soil.nitrogen += rate * self.analysis().0,
+        }
+    }
+}
+
+impl fmt::Display for Product {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Product::Urea => write!(f, "Urea (46-0-0)"),
+            Product::CalciumAmmoniumNitrate => write!(f, "Calcium ammonium nitrate (27-0-0)"),
+            Product::TripleSuperphosphate => write!(f, "Triple superphosphate (0-46-0)"),
+            Product::MuriateOfPotash => write!(f, "Muriate of potash (0-0-60)"),
+            Product::GroundLimestone => write!(f, "Ground limestone"),
+        }
+    }
+}
+
+/// A product spread once the crop reaches a stage. `Seed` is at sowing or planting out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Application {
+    pub stage: GrowthStage,
+    pub product: Product,
+    /// kg/ha
+    pub rate: f32,
+}
+
+/// Fertilizer applications of a crop over a season.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FertilizerPlan {
+    pub applications: Vec<Application>,
+}
+
+impl FertilizerPlan {
+    /// Applications due once a crop is at `stage`, by their index in the plan.
+    pub fn due(&self, stage: &GrowthStage) -> impl Iterator<Item = (usize, &Application)> + '_ {
+        let stage = stage.clone();
+        self.applications.iter()
+            .enumerate()
+            .filter(move |(_, application)| stage != GrowthStage::Failed && application.stage <= stage)
+    }
+
+    /// Nitrogen, phosphate and potash (kg/ha) over all the applications.
+    pub fn nutrients(&self) -> (f32, f32, f32) {
+        self.applications.iter().fold((0.0, 0.0, 0.0), |(n, p, k), application| {
+            let (share_n, share_p, share_k) = application.product.analysis();
+            (n + application.rate * share_n, p + application.rate * share_p, k + application.rate * share_k)
+        })
+    }
+
+    pub fn cost_per_ha(&self, costs: &CostTable) -> f32 {
+        self.applications.iter().map(|application| application.rate * costs.product_price(application.product)).sum()
+    }
+}
+
+/// A crop, what it should yield and the soil it grows in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FertilizerRequest {
+    pub crop: String,
+    /// Fresh weight (kg) to harvest from a plant
+    pub target_yield_kg: f32,
+    pub soil_test: SoilTest,
+}
+
+/// What the crop needs on top of what the soil supplies.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct NutrientBalance {
+    /// Nitrogen the crop takes up for the target yield (kg/ha)
+    pub nitrogen_demand: f32,
+    /// Mineral nitrogen and what the organic matter releases over the season (kg/ha)
+    pub nitrogen_supply: f32,
+    pub nitrogen: f32,
+    /// P2O5 (kg/ha)
+    pub phosphate: f32,
+    /// K2O (kg/ha)
+    pub potash: f32,
+    /// t/ha
+    pub lime: f32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Recommendation {
+    pub crop: String,
+    pub target_yield_kg: f32,
+    pub balance: NutrientBalance,
+    pub plan: FertilizerPlan,
+    /// Cost of the products (per ha)
+    pub cost_per_ha: f32,
+}
+
+impl Recommendation {
+    /// Works out the fertilizer a crop needs for the target yield by nutrient balance: the demand
+    /// of the crop less the supply of the soil for nitrogen, the offtake plus what brings the soil
+    /// up to its critical level for phosphate and potash, and lime to reach the pH the crop likes.
+    pub fn new(request: &FertilizerRequest) -> Result<Self, AgroException> {
+        let seed_type = SeedType::catalog().into_iter()
+            .find(|seed_type| seed_type.get_verbose_name().eq_ignore_ascii_case(&request.crop))
+            .ok_or(AgroException::InvalidCropError(format!("{} is not in the catalog", request.crop)))?;
+        if request.target_yield_kg <= 0.0 {
+            return Err(AgroException::InvalidFertilizerRequest("the target yield must be positive".to_string()));
+        }
+        let test = &request.soil_test;
+        test.validate()?;
+
+        let nitrogen_demand = seed_type.nitrogen_demand() * request.target_yield_kg / seed_type.reference_yield();
+        let nitrogen_supply = test.nitrogen + test.organic_matter * MINERALISATION_PER_OM;
+        let mut nitrogen = (nitrogen_demand - nitrogen_supply).max(0.0);
+        if seed_type.is_legume() {
+            nitrogen = nitrogen.min(LEGUME_STARTER_N);
+        }
+        let maintenance = |offtake: f32, level: f32, critical: f32, build_up: f32| {
+            if level < critical {
+                offtake + (critical - level) * build_up
+            } else if level < 2.0 * critical {
+                offtake
+            } else {
+                0.0
+            }
+        };
+        let phosphate = maintenance(nitrogen_demand * PHOSPHATE_PER_N, test.phosphorus, CRITICAL_PHOSPHORUS, PHOSPHATE_BUILD_UP);
+        let potash = maintenance(nitrogen_demand * POTASH_PER_N, test.potassium, CRITICAL_POTASSIUM, POTASH_BUILD_UP);
+        let lime = (Self::target_ph(&seed_type) - test.ph).max(0.0) * (LIME_PER_PH + LIME_PER_PH_AND_OM * test.organic_matter);
+        let balance = NutrientBalance { nitrogen_demand, nitrogen_supply, nitrogen, phosphate, potash, lime };
+
+        let mut applications = Vec::new();
+        let mut spread = |stage: GrowthStage, product: Product, rate: f32| {
+            let rate = rate.round();
+            if rate >= MIN_RATE {
+                applications.push(Application { stage, product, rate });
+            }
+        };
+        spread(GrowthStage::Seed, Product::GroundLimestone, lime * 1000.0);
+        spread(GrowthStage::Seed, Product::TripleSuperphosphate, phosphate / Product::TripleSuperphosphate.analysis().1);
+        spread(GrowthStage::Seed, Product::MuriateOfPotash, potash / Product::MuriateOfPotash.analysis().2);
+        let nitrogen_product = if test.ph > UREA_MAX_PH {
+            Product::CalciumAmmoniumNitrate
+        } else {
+            Product::Urea
+        };
+        let splits = if nitrogen < MIN_SPLIT_N { vec![(GrowthStage::Seed, 1.0)] } else { Self::nitrogen_splits(&seed_type) };
+        for (stage, share) in splits {
+            spread(stage, nitrogen_product, nitrogen * share / nitrogen_product.analysis().0);
+        }
+
+        let plan = FertilizerPlan { applications };
+        Ok(Self {
+            crop: seed_type.get_verbose_name(),
+            target_yield_kg: request.target_yield_kg,
+            balance,
+            cost_per_ha: plan.cost_per_ha(&CostTable::default()),
+            plan,
+        })
+    }
+
+    /// pH the crop grows best at.
+    fn target_ph(seed_type: &SeedType) -> f32 {
+        match seed_type {
+            SeedType::Sunflower(..) => 6.0,
+            SeedType::Pea(..) => 6.5,
+            SeedType::Carrot(..) => 6.0,
+            SeedType::Tomato(..) => 6.5,
+            SeedType::Broccoli(..) => 6.8,
+        }
+    }
+
+    /// Share of the nitrogen given at each stage, so that it comes when the crop takes it up.
+    fn nitrogen_splits(seed_type: &SeedType) -> Vec<(GrowthStage, f32)> {
+        match seed_type {
+            SeedType::Sunflower(..) => vec![(GrowthStage::Seed, 0.5), (GrowthStage::Vegetative, 0.5)],
+            SeedType::Pea(..) => vec![(GrowthStage::Seed, 1.0)],
+            SeedType::Carrot(..) => vec![(GrowthStage::Seed, 0.5), (GrowthStage::Vegetative, 0.5)],
+            SeedType::Tomato(..) => vec![(GrowthStage::Seed, 0.3), (GrowthStage::Vegetative, 0.4), (GrowthStage::Flowering, 0.3)],
+            SeedType::Broccoli(..) => vec![(GrowthStage::Seed, 0.3), (GrowthStage::Vegetative, 0.7)],
+        }
+    }
+}
+
+impl fmt::Display for Recommendation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let balance = &self.balance;
+        writeln!(f, "Fertilizer for {} at {:.2} kg per plant", self.crop, self.target_yield_kg)?;
+        writeln!(
+            f,
+            "  Nitrogen: {:.0} kg/ha demand, {:.0} kg/ha from the soil, {:.0} kg/ha to apply",
+            balance.nitrogen_demand,
+            balance.nitrogen_supply,
+            balance.nitrogen,
+        )?;
+        writeln!(f, "  Phosphate: {:.0} kg/ha P2O5, potash: {:.0} kg/ha K2O, lime: {:.1} t/ha", balance.phosphate, balance.potash, balance.lime)?;
+        for application in self.plan.applications.iter() {
+            writeln!(f, "  {:<12} {:<36} {:>7.0} kg/ha", format!("{:?}", application.stage), application.product.to_string(), application.rate)?;
+        }
+        writeln!(f, "  Cost: {:.2} per ha", self.cost_per_ha)
+    }
+}
+
+/// A recommendation for a crop of the farm, followed by runs of the first season with and without
+/// the plan to see how much it adds to the yield. Both runs of a replicate get the same seeds and
+/// weather, and the soil of every plot is that of the test.
+///
+/// The growth model is limited by nitrogen only, so the response is that of the nitrogen in the
+/// plan. Phosphate, potash and lime are spread and costed but do not change the simulated yield.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FertilizerTrial {
+    #[serde(flatten)]
+    pub request: FertilizerRequest,
+    pub start_date: NaiveDate,
+    #[serde(default = "FertilizerTrial::default_replicates")]
+    pub replicates: u32,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl FertilizerTrial {
+    fn default_replicates() -> u32 {
+        10
+    }
+
+    /// Reads a trial from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AgroException> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", path.display(), err)))?;
+        serde_json::from_str(&content)
+            .map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", path.display(), err)))
+    }
+
+    /// Recommends the fertilizer and simulates it on fresh farms from `farm`.
+    pub fn run(&self, farm: impl Fn() -> Result<Farm, AgroException> + Sync) -> Result<YieldResponse, AgroException> {
+        if self.replicates == 0 {
+            return Err(AgroException::InvalidExperiment("at least 1 replicate is needed".to_string()));
+        }
+        let recommendation = Recommendation::new(&self.request)?;
+        let grows_crop = farm()?.plots.iter()
+            .any(|plot| matches!(plot.rotation.step_for(0), RotationStep::Crop(name) if name.eq_ignore_ascii_case(&recommendation.crop)));
+        if !grows_crop {
+            return Err(AgroException::InvalidExperiment(format!("the farm does not grow {} in its first season", recommendation.crop)));
+        }
+
+        let unfertilized = ManagementPolicy {
+            fertilizing_interval: 0,
+            ..Default::default()
+        };
+        let mut fertilized = ManagementPolicy::default();
+        fertilized.fertilizer_plans.insert(recommendation.crop.to_string(), recommendation.plan.clone());
+
+        let farm = || -> Result<Farm, AgroException> {
+            let mut farm = farm()?;
+            farm.plots.iter_mut().for_each(|plot| self.request.soil_test.apply(&mut plot.soil));
+            Ok(farm)
+        };
+        let results: Vec<[(f64, f64); 2]> = (0..self.replicates).into_par_iter()
+            .map(|replicate| {
+                let seed = self.seed.wrapping_add(replicate as u64 * REPLICATE_SEED_STRIDE);
+                let mut weather_series = None;
+                let mut outcomes = [(0.0, 0.0); 2];
+                for (outcome, policy) in outcomes.iter_mut().zip([&unfertilized, &fertilized]) {
+                    let runner = RotationRunner::new(farm()?, 1, 1)
+                        .starting_on(self.start_date)
+                        .quiet()
+                        .with_policy(policy.clone())
+                        .with_seed(seed);
+                    let weather_series = weather_series.get_or_insert_with(|| {
+                        let hemisphere = Hemisphere::from_location(&runner.farm().location);
+                        let mut rng = StdRng::seed_from_u64(seed);
+                        Arc::new(WeatherSeries::generate(runner.start_date(), runner.end_date(), hemisphere, &mut rng))
+                    });
+                    let report = runner.with_weather(weather_series.clone()).run()?;
+                    *outcome = report.seasons.iter()
+                        .map(|season| {
+                            let is_crop = |crop_name: &&String| crop_name.eq_ignore_ascii_case(&recommendation.crop);
+                            let yield_kg = season.crops.iter()
+                                .filter(|(crop_name, _)| is_crop(crop_name))
+                                .map(|(_, summary)| summary.yield_kg)
+                                .sum::<f32>();
+                            let fertilizer = season.economics.crops.iter()
+                                .filter(|(crop_name, _)| is_crop(crop_name))
+                                .filter_map(|(_, account)| account.costs.get(&CostCategory::Fertilizer))
+                                .sum::<f32>();
+                            (yield_kg as f64, fertilizer as f64)
+                        })
+                        .fold((0.0, 0.0), |(yield_kg, cost), (season_yield, season_cost)| (yield_kg + season_yield, cost + season_cost));
+                }
+                Ok(outcomes)
+            })
+            .collect::<Result<_, AgroException>>()?;
+
+        let unfertilized: Vec<f64> = results.iter().map(|[(yield_kg, _), _]| *yield_kg).collect();
+        let fertilized: Vec<f64> = results.iter().map(|[_, (yield_kg, _)]| *yield_kg).collect();
+        let cost: Vec<f64> = results.iter().map(|[_, (_, cost)]| *cost).collect();
+        Ok(YieldResponse {
+            replicates: self.replicates,
+            unfertilized_yield_kg: mean(&unfertilized),
+            fertilized_yield_kg: mean(&fertilized),
+            fertilizer_cost: mean(&cost),
+            test: PairedTTest::new(&unfertilized, &fertilized),
+            recommendation,
+        })
+    }
+}
+
+/// Yield of the crop over the farm with the recommended plan against none at all. Only the nitrogen
+/// of the plan changes the yield.
+#[derive(Serialize, Debug, Clone)]
+pub struct YieldResponse {
+    pub recommendation: Recommendation,
+    pub replicates: u32,
+    pub unfertilized_yield_kg: f64,
+    pub fertilized_yield_kg: f64,
+    /// Mean cost of the products spread on the farm
+    pub fertilizer_cost: f64,
+    pub test: PairedTTest,
+}
+
+impl YieldResponse {
+    pub fn response_kg(&self) -> f64 {
+        self.fertilized_yield_kg - self.unfertilized_yield_kg
+    }
+}
+
+impl fmt::Display for YieldResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.recommendation)?;
+        writeln!(f, "Yield response over {} replicates", self.replicates)?;
+        writeln!(f, "  Without fertilizer:   {:>9.3} kg", self.unfertilized_yield_kg)?;
+        writeln!(f, "  With the plan:        {:>9.3} kg", self.fertilized_yield_kg)?;
+        let relative = if self.unfertilized_yield_kg > 0.0 {
+            format!(" ({:+.1}%)", 100.0 * self.response_kg() / self.unfertilized_yield_kg)
+        } else {
+            String::new()
+        };
+        writeln!(f, "  Response:             {:>+9.3} kg{} (t = {:.2}, p = {:.4})", self.response_kg(), relative, self.test.t, self.test.p_value)?;
+        writeln!(f, "  Fertilizer cost:      {:>9.2}", self.fertilizer_cost)?;
+        writeln!(f, "  Only the nitrogen of the plan is simulated; phosphate, potash and lime are costed but do not change the yield.")
+    }
+}
@@ -1,5 +1,7 @@
+#![allow(dead_code)]
+
 use std::path::Path;
-use crate::os::{Open, FsBlockSize, OpenOptions};
+use crate::os::{LockMode, Open};
 use std::fs::File;
 
 
@@ -14,7 +16,19 @@ pub(crate) fn read_file(path: impl AsRef<Path>) -> std::io::Result<File> {
         .lock(true)
         .open(path)?;
 
-    file.sync_all();
+    file.sync_all()?;
 
     Ok(file)
-}
\ No newline at end of file
+}
+
+/// Opens, creating it if needed, a file that only serves as a lock and takes the lock in `mode`.
+/// The lock is held until the file is dropped.
+pub(crate) fn lock_file(path: impl AsRef<Path>, mode: LockMode) -> std::io::Result<File> {
+    crate::os::LocalFs::options()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .lock_mode(mode)
+        .open(path)
+}
@@ -0,0 +1,137 @@
+#![allow(dead_code)]
+
+use chrono::NaiveDate;
+use rand::prelude::*;
+
+use crate::seeds::{SeedLot, SeedType};
+
+
+/// Soil moisture (fraction of field capacity) at which the seeds take up water freely.
+const OPTIMAL_SEEDBED_MOISTURE: f32 = 0.4;
+/// Above this soil moisture the seedbed is short of oxygen and part of the seeds rot.
+const WATERLOGGED_SEEDBED: f32 = 0.95;
+/// Degrees above the germination temperature at which the seeds germinate at full speed.
+const GERMINATION_TEMPERATURE_RANGE: f32 = 5.0;
+
+/// State of the seedbed on the day of sowing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeedbedConditions {
+    pub soil_temperature: f32,
+    /// Fraction of field capacity (0.0 - 1.0)
+    pub soil_moisture: f32,
+}
+
+impl SeedbedConditions {
+    /// 1.0 in a warm and moist seedbed, down to 0.0 when it is too cold or too dry to germinate.
+    fn suitability(&self, seed_type: &SeedType) -> f32 {
+        let temperature_factor = ((self.soil_temperature - seed_type.germination_temperature()) / GERMINATION_TEMPERATURE_RANGE)
+            .clamp(0.0, 1.0);
+        let moisture_factor = if self.soil_moisture > WATERLOGGED_SEEDBED {
+            0.5
+        } else {
+            (self.soil_moisture / OPTIMAL_SEEDBED_MOISTURE).clamp(0.0, 1.0)
+        };
+        temperature_factor.min(moisture_factor)
+    }
+}
+
+/// Chance that a seed sown on `sowing_date` emerges. Seeds without a known lot are taken to be fresh and vigorous.
+pub fn emergence_probability(seed_type: &SeedType, lot: Option<&SeedLot>, sowing_date: NaiveDate, seedbed: &SeedbedConditions) -> f32 {
+    let (viability, vigor) = match lot {
+        Some(lot) => (lot.viability_on(seed_type, sowing_date), lot.vigor),
+        None => (1.0, 1.0),
+    };
+    // Vigorous seeds make up for half of the stress of a poor seedbed.
+    let stress = 1.0 - seedbed.suitability(seed_type);
+    viability * (1.0 - stress * (1.0 - 0.5 * vigor))
+}
+
+/// Draws whether a single seed emerges.
+pub fn sample_emergence(seed_type: &SeedType, lot: Option<&SeedLot>, sowing_date: NaiveDate, seedbed: &SeedbedConditions, rng: &mut impl Rng) -> bool {
+    rng.gen::<f32>() < emergence_probability(seed_type, lot, sowing_date, seedbed)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use super::*;
+    use crate::seeds::StorageCondition;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() < tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    fn sowing_date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 4, 15).unwrap()
+    }
+
+    fn lot(germination_rate: f32, vigor: f32, packed_on: NaiveDate, storage: StorageCondition) -> SeedLot {
+        SeedLot { lot_number: "L1".to_string(), germination_rate, vigor, packed_on, storage }
+    }
+
+    fn seedbed(soil_temperature: f32) -> SeedbedConditions {
+        SeedbedConditions { soil_temperature, soil_moisture: OPTIMAL_SEEDBED_MOISTURE }
+    }
+
+    #[test]
+    fn emergence_rises_with_the_soil_temperature() {
+        // Tomatoes germinate from 10 °C and at full speed from 15 °C.
+        let tomato = SeedType::from_str("Tomato").unwrap();
+        let emergence = |temperature: f32| emergence_probability(&tomato, None, sowing_date(), &seedbed(temperature));
+
+        assert_eq!(emergence(8.0), 0.5);
+        assert_close(emergence(12.5), 0.75, 1e-6);
+        assert_eq!(emergence(15.0), 1.0);
+        assert_eq!(emergence(25.0), 1.0);
+        // Peas come up in soil too cold for tomatoes.
+        assert_eq!(emergence_probability(&SeedType::from_str("Pea").unwrap(), None, sowing_date(), &seedbed(9.0)), 1.0);
+    }
+
+    #[test]
+    fn a_dry_or_waterlogged_seedbed_holds_emergence_back() {
+        let pea = SeedType::from_str("Pea").unwrap();
+        let emergence = |soil_moisture: f32| {
+            emergence_probability(&pea, None, sowing_date(), &SeedbedConditions { soil_temperature: 15.0, soil_moisture })
+        };
+
+        assert_eq!(emergence(0.0), 0.5);
+        assert_close(emergence(0.2), 0.75, 1e-6);
+        assert_eq!(emergence(0.8), 1.0);
+        assert_close(emergence(1.0), 0.75, 1e-6);
+    }
+
+    #[test]
+    fn vigorous_seeds_make_up_for_a_poor_seedbed() {
+        let tomato = SeedType::from_str("Tomato").unwrap();
+        let cold = seedbed(8.0);
+        let emergence = |vigor: f32| emergence_probability(&tomato, Some(&lot(1.0, vigor, sowing_date(), StorageCondition::Ambient)), sowing_date(), &cold);
+
+        assert_eq!(emergence(0.0), 0.0);
+        assert_close(emergence(1.0), 0.5, 1e-6);
+    }
+
+    #[test]
+    fn old_seeds_lose_viability_faster_in_poor_storage() {
+        let pea = SeedType::from_str("Pea").unwrap();
+        // Three years: one half-life of the pea at room conditions.
+        let packed_on = NaiveDate::from_ymd_opt(2022, 4, 15).unwrap();
+        let emergence = |storage: StorageCondition| {
+            emergence_probability(&pea, Some(&lot(0.9, 1.0, packed_on, storage)), sowing_date(), &seedbed(15.0))
+        };
+
+        assert_close(emergence(StorageCondition::Ambient), 0.45, 1e-3);
+        assert_close(emergence(StorageCondition::ColdDry), 0.9 * 0.5_f32.powf(1.0 / 3.0), 1e-3);
+        assert!(emergence(StorageCondition::Humid) < 0.2);
+    }
+
+    #[test]
+    fn sampled_emergence_follows_the_probability() {
+        let tomato = SeedType::from_str("Tomato").unwrap();
+        let mut rng = StdRng::seed_from_u64(5);
+        let emerged = (0..10_000)
+            .filter(|_| sample_emergence(&tomato, None, sowing_date(), &seedbed(12.5), &mut rng))
+            .count();
+
+        assert_close(emerged as f32 / 10_000.0, 0.75, 0.02);
+    }
+}
@@ -0,0 +1,132 @@
+#![allow(dead_code)]
+
+use std::fmt;
+use serde::Serialize;
+
+
+/// Envelope (walls and roof) area of a greenhouse per square metre of floor.
+const ENVELOPE_PER_FLOOR_AREA: f32 = 1.5;
+/// Daily temperature swing (°C) under glass, smaller than outdoors.
+const DAILY_TEMPERATURE_RANGE: f32 = 6.0;
+
+/// A glasshouse over a plot, with the setpoints its climate computer works to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Greenhouse {
+    /// Heating comes on below this temperature (°C)
+    pub heating_setpoint: f32,
+    /// Vents open above this temperature (°C)
+    pub ventilation_setpoint: f32,
+    /// Screens close above this outdoor radiation (MJ/m²/day)
+    pub shading_threshold: f32,
+    /// Share of the light the screens let through
+    pub shading_transmission: f32,
+    /// Share of the outdoor light that reaches the crop
+    pub light_transmission: f32,
+    /// Heat loss through the envelope (W/m²K)
+    pub heat_loss_coefficient: f32,
+    /// Warming (°C) from every MJ/m² of light let in
+    pub solar_gain: f32,
+}
+
+impl Default for Greenhouse {
+    /// Single glazed house, heated for tomatoes.
+    fn default() -> Self {
+        Self {
+            heating_setpoint: 16.0,
+            ventilation_setpoint: 26.0,
+            shading_threshold: 22.0,
+            shading_transmission: 0.6,
+            light_transmission: 0.7,
+            heat_loss_coefficient: 6.0,
+            solar_gain: 0.8,
+        }
+    }
+}
+
+/// Climate inside a greenhouse over one day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Microclimate {
+    pub mean_temperature: f32,
+    pub min_temperature: f32,
+    pub max_temperature: f32,
+    /// Light reaching the crop (MJ/m²/day)
+    pub radiation: f32,
+    pub heating_kwh: f32,
+    pub ventilated: bool,
+    pub shaded: bool,
+}
+
+impl Greenhouse {
+    pub fn with_setpoints(mut self, heating_setpoint: f32, ventilation_setpoint: f32) -> Self {
+        self.heating_setpoint = heating_setpoint;
+        self.ventilation_setpoint = ventilation_setpoint.max(heating_setpoint);
+        self
+    }
+
+    pub fn with_shading(mut self, shading_threshold: f32, shading_transmission: f32) -> Self {
+        self.shading_threshold = shading_threshold;
+        self.shading_transmission = shading_transmission;
+        self
+    }
+
+    /// Climate under glass given the outdoor mean temperature (°C) and radiation (MJ/m²/day)
+    /// over `floor_area` square metres. The house warms up with the light let in, is vented
+    /// down to its ventilation setpoint and heated up to its heating setpoint.
+    pub fn microclimate(&self, outdoor_temperature: f32, outdoor_radiation: f32, floor_area: f32) -> Microclimate {
+        let shaded = outdoor_radiation > self.shading_threshold;
+        let radiation = outdoor_radiation * self.light_transmission * if shaded { self.shading_transmission } else { 1.0 };
+        let passive_temperature = outdoor_temperature + self.solar_gain * radiation;
+
+        let ventilated = passive_temperature > self.ventilation_setpoint;
+        let mut mean_temperature = if ventilated {
+            self.ventilation_setpoint.max(outdoor_temperature)
+        } else {
+            passive_temperature
+        };
+
+        let mut heating_kwh = 0.0;
+        if mean_temperature < self.heating_setpoint {
+            let envelope = floor_area * ENVELOPE_PER_FLOOR_AREA;
+            heating_kwh = self.heat_loss_coefficient * envelope * (self.heating_setpoint - mean_temperature) * 24.0 / 1000.0;
+            mean_temperature = self.heating_setpoint;
+        }
+
+        Microclimate {
+            mean_temperature,
+            min_temperature: (mean_temperature - DAILY_TEMPERATURE_RANGE / 2.0).max(self.heating_setpoint),
+            max_temperature: mean_temperature + DAILY_TEMPERATURE_RANGE / 2.0,
+            radiation,
+            heating_kwh,
+            ventilated,
+            shaded,
+        }
+    }
+}
+
+/// Energy use and climate control of one greenhouse over a run.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct GreenhouseSummary {
+    pub heating_kwh: f32,
+    pub heated_days: u32,
+    pub ventilated_days: u32,
+    pub shaded_days: u32,
+}
+
+impl GreenhouseSummary {
+    pub fn record(&mut self, microclimate: &Microclimate) {
+        self.heating_kwh += microclimate.heating_kwh;
+        self.heated_days += (microclimate.heating_kwh > 0.0) as u32;
+        self.ventilated_days += microclimate.ventilated as u32;
+        self.shaded_days += microclimate.shaded as u32;
+    }
+}
+
+impl fmt::Display for GreenhouseSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} kWh heating over {} days, vented {} days, shaded {} days",
+            self.heating_kwh, self.heated_days, self.ventilated_days, self.shaded_days
+        )
+    }
+}
@@ -0,0 +1,229 @@
+#![allow(dead_code)]
+
+use serde::Deserialize;
+
+use crate::dto::GrowthStage;
+use crate::parameters::CropParameters;
+use crate::seeds::SeedType;
+
+
+/// Share of the photosynthetically active radiation in the global solar radiation.
+const PAR_FRACTION: f32 = 0.5;
+/// Daily leaf area loss once the crop is mature.
+const LEAF_SENESCENCE: f32 = 0.05;
+/// Share of the transplant shock still felt the next day.
+const TRANSPLANT_RECOVERY: f32 = 0.8;
+/// Plants start closing their stomata once half of the available water is used.
+const WATER_STRESS_THRESHOLD: f32 = 0.5;
+
+/// Crop specific constants of the radiation-use-efficiency model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrowthParameters {
+    /// Dry matter produced per unit of intercepted PAR (g/MJ)
+    pub radiation_use_efficiency: f32,
+    /// Light extinction coefficient of the canopy (Beer's law)
+    pub extinction_coefficient: f32,
+    /// Leaf area per gram of leaf dry matter (m²/g)
+    pub specific_leaf_area: f32,
+    /// Leaf area of a plant at emergence (m²)
+    pub initial_leaf_area: f32,
+    /// Cardinal temperatures (°C) for growth: base, optimum and maximum
+    pub base_temperature: f32,
+    pub optimum_temperature: f32,
+    pub maximum_temperature: f32,
+    /// Fraction of dry matter in the harvested product
+    pub dry_matter_content: f32,
+    /// Soil moisture (fraction of field capacity) below which growth slows down
+    pub water_stress_threshold: f32,
+}
+
+impl GrowthParameters {
+    pub fn for_seed_type(seed_type: &SeedType) -> Self {
+        match seed_type {
+            SeedType::Sunflower(..) => Self {
+                radiation_use_efficiency: 2.8,
+                extinction_coefficient: 0.85,
+                specific_leaf_area: 0.03,
+                initial_leaf_area: 0.015,
+                base_temperature: 6.0,
+                optimum_temperature: 26.0,
+                maximum_temperature: 38.0,
+                dry_matter_content: 0.92,
+                water_stress_threshold: WATER_STRESS_THRESHOLD,
+            },
+            SeedType::Pea(..) => Self {
+                radiation_use_efficiency: 2.5,
+                extinction_coefficient: 0.6,
+                specific_leaf_area: 0.03,
+                initial_leaf_area: 0.003,
+                base_temperature: 4.0,
+                optimum_temperature: 20.0,
+                maximum_temperature: 30.0,
+                dry_matter_content: 0.22,
+                water_stress_threshold: WATER_STRESS_THRESHOLD,
+            },
+            SeedType::Carrot(..) => Self {
+                radiation_use_efficiency: 2.6,
+                extinction_coefficient: 0.6,
+                specific_leaf_area: 0.03,
+                initial_leaf_area: 0.0015,
+                base_temperature: 4.0,
+                optimum_temperature: 20.0,
+                maximum_temperature: 30.0,
+                dry_matter_content: 0.12,
+                water_stress_threshold: WATER_STRESS_THRESHOLD,
+            },
+            SeedType::Tomato(..) => Self {
+                radiation_use_efficiency: 2.7,
+                extinction_coefficient: 0.7,
+                specific_leaf_area: 0.03,
+                initial_leaf_area: 0.05,
+                base_temperature: 10.0,
+                optimum_temperature: 25.0,
+                maximum_temperature: 35.0,
+                dry_matter_content: 0.06,
+                water_stress_threshold: WATER_STRESS_THRESHOLD,
+            },
+            SeedType::Broccoli(..) => Self {
+                radiation_use_efficiency: 2.6,
+                extinction_coefficient: 0.7,
+                specific_leaf_area: 0.03,
+                initial_leaf_area: 0.027,
+                base_temperature: 4.0,
+                optimum_temperature: 18.0,
+                maximum_temperature: 30.0,
+                dry_matter_content: 0.10,
+                water_stress_threshold: WATER_STRESS_THRESHOLD,
+            },
+        }
+    }
+
+    /// The catalog values of the seed type, with those set in `overrides` instead.
+    pub fn for_crop(seed_type: &SeedType, overrides: &CropParameters) -> Self {
+        let mut parameters = Self::for_seed_type(seed_type);
+        parameters.base_temperature = overrides.base_temperature.unwrap_or(parameters.base_temperature);
+        parameters.radiation_use_efficiency = overrides.radiation_use_efficiency.unwrap_or(parameters.radiation_use_efficiency);
+        parameters.water_stress_threshold = overrides.water_stress_threshold.unwrap_or(parameters.water_stress_threshold);
+        parameters
+    }
+
+    /// Growth reduction (0.0 - 1.0) from a triangular response to the mean daily temperature.
+    pub fn temperature_factor(&self, temperature: f32) -> f32 {
+        if temperature <= self.base_temperature || temperature >= self.maximum_temperature {
+            0.0
+        } else if temperature <= self.optimum_temperature {
+            (temperature - self.base_temperature) / (self.optimum_temperature - self.base_temperature)
+        } else {
+            (self.maximum_temperature - temperature) / (self.maximum_temperature - self.optimum_temperature)
+        }
+    }
+}
+
+/// Environment a plant grows in on a given day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GrowthConditions {
+    /// Global solar radiation reaching the canopy (MJ m-2 day-1)
+    pub radiation: f32,
+    pub mean_temperature: f32,
+    /// Fraction of field capacity (0.0 - 1.0)
+    pub soil_moisture: f32,
+    /// Share of the nitrogen demand the soil can supply (0.0 - 1.0)
+    pub nitrogen_factor: f32,
+}
+
+impl GrowthConditions {
+    /// Growth reduction (0.0 - 1.0) once the soil dries out below `threshold`.
+    pub fn water_factor(&self, threshold: f32) -> f32 {
+        (self.soil_moisture / threshold.max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+}
+
+/// Dry matter (g) and leaf area (m²) of a single plant.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CropGrowth {
+    pub leaf_area: f32,
+    pub leaves: f32,
+    pub stems: f32,
+    pub roots: f32,
+    /// Harvested organ: fruits, heads, seeds or tap root depending on the crop
+    pub storage: f32,
+    /// Most limiting stress factor of the last simulated day (1.0 means no stress)
+    pub stress: f32,
+    /// Share of the growth lost while the roots re-establish after planting out
+    #[serde(default)]
+    pub transplant_shock: f32,
+}
+
+impl CropGrowth {
+    pub fn biomass(&self) -> f32 {
+        self.leaves + self.stems + self.roots + self.storage
+    }
+
+    pub fn leaf_area_index(&self, ground_area: f32) -> f32 {
+        if ground_area <= 0.0 {
+            return 0.0;
+        }
+        self.leaf_area / ground_area
+    }
+
+    /// Fraction of the incoming light intercepted by the canopy (Beer's law).
+    pub fn light_interception(&self, parameters: &GrowthParameters, ground_area: f32) -> f32 {
+        1.0 - (-parameters.extinction_coefficient * self.leaf_area_index(ground_area)).exp()
+    }
+
+    /// Planting out tears part of the roots, and growth slows down until they have grown back.
+    pub fn transplant(&mut self, shock: f32) {
+        self.roots *= 1.0 - shock / 2.0;
+        self.transplant_shock = shock.clamp(0.0, 1.0);
+    }
+
+    /// Simulates one day of growth. Seeds live off their reserves, so nothing happens before the seedling stage.
+    pub fn grow_day(&mut self, seed_type: &SeedType, overrides: &CropParameters, stage: &GrowthStage, conditions: &GrowthConditions) -> f32 {
+        let parameters = GrowthParameters::for_crop(seed_type, overrides);
+        let (leaves, stems, roots, storage) = match partitioning(stage) {
+            Some(fractions) => fractions,
+            None => return 0.0,
+        };
+        if self.leaf_area == 0.0 {
+            self.leaf_area = parameters.initial_leaf_area;
+        }
+
+        let ground_area = seed_type.plant_area();
+        let intercepted_par = PAR_FRACTION * conditions.radiation * self.light_interception(&parameters, ground_area) * ground_area;
+        self.stress = conditions.water_factor(parameters.water_stress_threshold)
+            .min(parameters.temperature_factor(conditions.mean_temperature))
+            .min(conditions.nitrogen_factor)
+            * (1.0 - self.transplant_shock);
+        self.transplant_shock *= TRANSPLANT_RECOVERY;
+        let dry_matter = parameters.radiation_use_efficiency * intercepted_par * self.stress;
+
+        self.leaves += dry_matter * leaves;
+        self.stems += dry_matter * stems;
+        self.roots += dry_matter * roots;
+        self.storage += dry_matter * storage;
+        self.leaf_area += dry_matter * leaves * parameters.specific_leaf_area;
+        if *stage == GrowthStage::Maturity {
+            self.leaf_area *= 1.0 - LEAF_SENESCENCE;
+        }
+        dry_matter
+    }
+
+    /// Fresh weight (kg) of the harvested organ.
+    pub fn fresh_yield(&self, seed_type: &SeedType) -> f32 {
+        let parameters = GrowthParameters::for_seed_type(seed_type);
+        self.storage / 1000.0 / parameters.dry_matter_content
+    }
+}
+
+/// Fractions of the daily dry matter going to leaves, stems, roots and the storage organ.
+/// `None` for the stages in which the plant does not photosynthesise.
+pub fn partitioning(stage: &GrowthStage) -> Option<(f32, f32, f32, f32)> {
+    match stage {
+        GrowthStage::Seedling => Some((0.5, 0.2, 0.3, 0.0)),
+        GrowthStage::Vegetative => Some((0.45, 0.3, 0.2, 0.05)),
+        GrowthStage::Flowering => Some((0.25, 0.35, 0.1, 0.3)),
+        GrowthStage::Fruiting => Some((0.1, 0.15, 0.05, 0.7)),
+        GrowthStage::Maturity => Some((0.0, 0.05, 0.0, 0.95)),
+        _ => None,
+    }
+}
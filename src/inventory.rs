@@ -0,0 +1,402 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fmt;
+use chrono::NaiveDate;
+use csv::{ReaderBuilder, WriterBuilder};
+
+use crate::dto::Crop;
+use crate::errors::AgroException;
+use crate::seeds::SeedBag;
+use crate::setup::crops_from_bag;
+
+
+/// A lot is running low once less than this many full bags are left.
+const LOW_STOCK_BAGS: u32 = 1;
+
+const CSV_HEADERS: [&str; 15] = [
+    "botanica_name",
+    "verbose_name",
+    "species",
+    "description",
+    "is_gmo",
+    "quantity_per_bag",
+    "lot_number",
+    "germination_rate",
+    "vigor",
+    "packed_on",
+    "storage",
+    "supplier",
+    "purchased_on",
+    "expires_on",
+    "seeds_in_stock",
+];
+
+/// Seeds of one lot kept in the shed. `bag.quantity` is the number of seeds in a full bag,
+/// while `seeds` is what is left of the lot, opened bag included.
+#[derive(Debug, Clone)]
+pub struct StockEntry {
+    pub bag: SeedBag,
+    pub seeds: u32,
+    pub supplier: String,
+    pub purchased_on: NaiveDate,
+    pub expires_on: NaiveDate,
+}
+
+impl StockEntry {
+    pub fn verbose_name(&self) -> String {
+        self.bag.seed_type.get_verbose_name()
+    }
+
+    pub fn lot_number(&self) -> String {
+        self.bag.lot.as_ref().map_or(String::new(), |lot| lot.lot_number.to_string())
+    }
+
+    pub fn full_bags(&self) -> u32 {
+        self.seeds / self.bag.quantity.max(1)
+    }
+
+    /// Seeds left in the bag that has been opened, if any.
+    pub fn opened_bag(&self) -> u32 {
+        self.seeds % self.bag.quantity.max(1)
+    }
+
+    pub fn is_expired(&self, date: NaiveDate) -> bool {
+        date > self.expires_on
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.full_bags() < LOW_STOCK_BAGS
+    }
+}
+
+/// Seeds needed for one sowing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedDemand {
+    pub verbose_name: String,
+    pub seeds: u32,
+    pub sowing_date: NaiveDate,
+}
+
+/// A sowing that cannot be covered by the seeds in stock.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shortfall {
+    pub verbose_name: String,
+    pub sowing_date: NaiveDate,
+    pub required: u32,
+    pub available: u32,
+}
+
+impl fmt::Display for Shortfall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} on {}: {} seeds required, {} usable in stock",
+            self.verbose_name, self.sowing_date, self.required, self.available
+        )
+    }
+}
+
+/// Seed lots in the farm's shed.
+#[derive(Debug, Clone, Default)]
+pub struct SeedInventory {
+    pub entries: Vec<StockEntry>,
+}
+
+impl SeedInventory {
+    pub fn add(&mut self, entry: StockEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Seeds of `verbose_name` that have not expired by `date`.
+    pub fn available(&self, verbose_name: &str, date: NaiveDate) -> u32 {
+        self.entries.iter()
+            .filter(|entry| entry.verbose_name().eq_ignore_ascii_case(verbose_name) && !entry.is_expired(date))
+            .map(|entry| entry.seeds)
+            .sum()
+    }
+
+    /// Number of seeds in a full bag of `verbose_name`, taken from the first lot in stock.
+    pub fn bag_size(&self, verbose_name: &str) -> Option<u32> {
+        self.entries.iter()
+            .find(|entry| entry.verbose_name().eq_ignore_ascii_case(verbose_name))
+            .map(|entry| entry.bag.quantity)
+    }
+
+    /// Seeds of `verbose_name` sown on a plot in one season: a full bag, or a single seed if none is in stock.
+    pub fn sowing_size(&self, verbose_name: &str) -> u32 {
+        self.bag_size(verbose_name).unwrap_or(1)
+    }
+
+    /// Takes the seeds of a sowing out of stock and returns them as crops on `plot_id`, with a warning
+    /// for every lot it leaves running low.
+    pub fn draw(&mut self, demand: &SeedDemand, plot_id: Option<u32>) -> Result<(Vec<Crop>, Vec<String>), AgroException> {
+        let available = self.available(&demand.verbose_name, demand.sowing_date);
+        if available < demand.seeds {
+            let shortfall = Shortfall {
+                verbose_name: demand.verbose_name.to_string(),
+                sowing_date: demand.sowing_date,
+                required: demand.seeds,
+                available,
+            };
+            return Err(AgroException::InsufficientStock(shortfall.to_string()));
+        }
+
+        let mut crops = Vec::new();
+        let mut warnings = Vec::new();
+        for (idx, taken) in self.take(demand) {
+            let entry = &self.entries[idx];
+            crops.extend(crops_from_bag(&entry.bag, taken, plot_id));
+            if entry.is_low() {
+                warnings.push(Self::low_stock_warning(entry));
+            }
+        }
+        Ok((crops, warnings))
+    }
+
+    /// Takes the seeds of a sowing out of the lots closest to their expiry date first, as far as
+    /// they go, and returns how many were taken from each lot, by index. A bag may be left half used.
+    fn take(&mut self, demand: &SeedDemand) -> Vec<(usize, u32)> {
+        let mut lots: Vec<(usize, &mut StockEntry)> = self.entries.iter_mut()
+            .enumerate()
+            .filter(|(_, entry)| entry.verbose_name().eq_ignore_ascii_case(&demand.verbose_name) && !entry.is_expired(demand.sowing_date))
+            .collect();
+        lots.sort_by_key(|(_, entry)| entry.expires_on);
+
+        let mut taken_from = Vec::new();
+        let mut remaining = demand.seeds;
+        for (idx, entry) in lots {
+            if remaining == 0 {
+                break;
+            }
+            let taken = remaining.min(entry.seeds);
+            entry.seeds -= taken;
+            remaining -= taken;
+            taken_from.push((idx, taken));
+        }
+        taken_from
+    }
+
+    /// Sowings of the plan that cannot be covered, drawing the demands in order from what is in stock now.
+    pub fn shortfalls(&self, demands: &[SeedDemand]) -> Vec<Shortfall> {
+        let mut stock = self.clone();
+        let mut shortfalls = Vec::new();
+        for demand in demands {
+            let available = stock.available(&demand.verbose_name, demand.sowing_date);
+            if available < demand.seeds {
+                shortfalls.push(Shortfall {
+                    verbose_name: demand.verbose_name.to_string(),
+                    sowing_date: demand.sowing_date,
+                    required: demand.seeds,
+                    available,
+                });
+            } else {
+                stock.take(demand);
+            }
+        }
+        shortfalls
+    }
+
+    pub fn is_feasible(&self, demands: &[SeedDemand]) -> bool {
+        self.shortfalls(demands).is_empty()
+    }
+
+    /// Warnings for the lots that are running low.
+    pub fn low_stock_warnings(&self) -> Vec<String> {
+        self.entries.iter()
+            .filter(|entry| entry.is_low())
+            .map(Self::low_stock_warning)
+            .collect()
+    }
+
+    fn low_stock_warning(entry: &StockEntry) -> String {
+        format!(
+            "{} lot {}: {} seeds left, less than {} full bag(s)",
+            entry.verbose_name(),
+            entry.lot_number(),
+            entry.seeds,
+            LOW_STOCK_BAGS
+        )
+    }
+
+    pub fn import_csv(path: &str) -> Result<SeedInventory, AgroException> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .from_path(path)
+            .map_err(|err| AgroException::InvalidInventory(err.to_string()))?;
+        let headers = reader.headers()
+            .map_err(|err| AgroException::InvalidInventory(err.to_string()))?
+            .clone();
+
+        let mut inventory = SeedInventory::default();
+        for (line, result) in reader.records().enumerate() {
+            let record = result.map_err(|err| AgroException::InvalidInventory(err.to_string()))?;
+            let map: HashMap<String, String> = headers.iter()
+                .zip(record.iter())
+                .map(|(header, value)| (header.to_string(), value.to_string()))
+                .collect();
+            let entry = Self::entry_from_record(&map)
+                .map_err(|err| AgroException::InvalidInventory(format!("line {}: {}", line + 2, err)))?;
+            inventory.add(entry);
+        }
+        Ok(inventory)
+    }
+
+    fn entry_from_record(record: &HashMap<String, String>) -> Result<StockEntry, String> {
+        let field = |name: &str| record.get(name).ok_or(format!("Missing column {}", name));
+        let date = |name: &str| NaiveDate::parse_from_str(field(name)?, "%Y-%m-%d").map_err(|err| err.to_string());
+        Ok(StockEntry {
+            bag: SeedBag::from_record(record)?,
+            seeds: field("seeds_in_stock")?.parse::<u32>().map_err(|err| err.to_string())?,
+            supplier: field("supplier")?.to_string(),
+            purchased_on: date("purchased_on")?,
+            expires_on: date("expires_on")?,
+        })
+    }
+
+    pub fn export_csv(&self, path: &str) -> Result<(), AgroException> {
+        let mut writer = WriterBuilder::new()
+            .from_path(path)
+            .map_err(|err| AgroException::InvalidInventory(err.to_string()))?;
+        writer.write_record(CSV_HEADERS)
+            .map_err(|err| AgroException::InvalidInventory(err.to_string()))?;
+
+        for entry in self.entries.iter() {
+            let bag = &entry.bag;
+            let lot = bag.lot.as_ref()
+                .ok_or(AgroException::InvalidInventory(format!("{} has no seed lot", entry.verbose_name())))?;
+            let record = [
+                bag.seed_type.get_botanica_name(),
+                bag.seed_type.get_verbose_name(),
+                bag.species.to_string(),
+                bag.description.clone().unwrap_or_default(),
+                bag.is_gmo.unwrap_or(false).to_string(),
+                bag.quantity.to_string(),
+                lot.lot_number.to_string(),
+                lot.germination_rate.to_string(),
+                lot.vigor.to_string(),
+                lot.packed_on.to_string(),
+                lot.storage.to_string(),
+                entry.supplier.to_string(),
+                entry.purchased_on.to_string(),
+                entry.expires_on.to_string(),
+                entry.seeds.to_string(),
+            ];
+            writer.write_record(&record)
+                .map_err(|err| AgroException::InvalidInventory(err.to_string()))?;
+        }
+        writer.flush().map_err(|err| AgroException::InvalidInventory(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeds::{SeedLot, StorageCondition};
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    fn entry(lot_number: &str, bag_size: u32, seeds: u32, expires_on: &str) -> StockEntry {
+        let mut bag = SeedBag::new(bag_size, "Pea", "Fabaceae".to_string()).unwrap();
+        bag.lot = Some(SeedLot {
+            lot_number: lot_number.to_string(),
+            germination_rate: 0.9,
+            vigor: 0.8,
+            packed_on: date("2024-01-01"),
+            storage: StorageCondition::Ambient,
+        });
+        StockEntry {
+            bag,
+            seeds,
+            supplier: "Thompson Seeds".to_string(),
+            purchased_on: date("2024-02-01"),
+            expires_on: date(expires_on),
+        }
+    }
+
+    fn demand(seeds: u32, sowing_date: &str) -> SeedDemand {
+        SeedDemand { verbose_name: "Pea".to_string(), seeds, sowing_date: date(sowing_date) }
+    }
+
+    fn inventory(entries: Vec<StockEntry>) -> SeedInventory {
+        SeedInventory { entries }
+    }
+
+    #[test]
+    fn a_draw_beyond_the_stock_fails_and_takes_nothing() {
+        let mut stock = inventory(vec![entry("PEA-1", 2, 3, "2026-12-31")]);
+
+        let result = stock.draw(&demand(4, "2025-04-01"), Some(1));
+
+        assert!(matches!(result, Err(AgroException::InsufficientStock(_))));
+        assert_eq!(stock.entries[0].seeds, 3);
+    }
+
+    #[test]
+    fn draws_never_take_the_stock_below_zero() {
+        let mut stock = inventory(vec![entry("PEA-1", 2, 3, "2026-12-31"), entry("PEA-2", 2, 2, "2027-12-31")]);
+
+        let mut sown = 0;
+        while let Ok((crops, _)) = stock.draw(&demand(2, "2025-04-01"), Some(1)) {
+            sown += crops.len();
+        }
+
+        assert_eq!(sown, 4);
+        assert_eq!(stock.available("Pea", date("2025-04-01")), 1);
+        assert!(stock.draw(&demand(1, "2025-04-01"), Some(1)).is_ok());
+        assert_eq!(stock.entries.iter().map(|entry| entry.seeds).sum::<u32>(), 0);
+        assert!(stock.draw(&demand(1, "2025-04-01"), Some(1)).is_err());
+    }
+
+    #[test]
+    fn the_lot_closest_to_expiry_is_drawn_first_and_expired_lots_are_skipped() {
+        let mut stock = inventory(vec![
+            entry("PEA-LATE", 2, 4, "2027-12-31"),
+            entry("PEA-SOON", 2, 2, "2025-12-31"),
+            entry("PEA-OLD", 2, 10, "2025-01-31"),
+        ]);
+
+        assert_eq!(stock.available("pea", date("2025-04-01")), 6);
+        let (crops, _) = stock.draw(&demand(3, "2025-04-01"), Some(7)).unwrap();
+
+        assert_eq!(crops.len(), 3);
+        assert!(crops.iter().all(|crop| crop.plot_id == Some(7)));
+        assert_eq!(stock.entries[1].seeds, 0);
+        assert_eq!(stock.entries[0].seeds, 3);
+        assert_eq!(stock.entries[2].seeds, 10);
+    }
+
+    #[test]
+    fn a_lot_left_with_less_than_a_full_bag_is_reported_low() {
+        let mut stock = inventory(vec![entry("PEA-1", 4, 6, "2026-12-31")]);
+        assert!(stock.low_stock_warnings().is_empty());
+
+        let (_, warnings) = stock.draw(&demand(3, "2025-04-01"), None).unwrap();
+
+        assert_eq!(stock.entries[0].full_bags(), 0);
+        assert_eq!(stock.entries[0].opened_bag(), 3);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("PEA-1"));
+        assert_eq!(stock.low_stock_warnings(), warnings);
+    }
+
+    #[test]
+    fn shortfalls_count_the_earlier_sowings_of_the_plan() {
+        let stock = inventory(vec![entry("PEA-1", 2, 5, "2026-12-31")]);
+        let plan = [demand(3, "2025-04-01"), demand(3, "2025-06-01"), demand(2, "2025-08-01")];
+
+        let shortfalls = stock.shortfalls(&plan);
+
+        assert_eq!(shortfalls, vec![Shortfall {
+            verbose_name: "Pea".to_string(),
+            sowing_date: date("2025-06-01"),
+            required: 3,
+            available: 2,
+        }]);
+        assert!(!stock.is_feasible(&plan));
+        assert!(stock.is_feasible(&plan[..1]));
+        assert_eq!(stock.entries[0].seeds, 5);
+    }
+}
@@ -0,0 +1,334 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use chrono::{Duration, NaiveDate};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::Hemisphere;
+use crate::dto::Farm;
+use crate::errors::AgroException;
+use crate::experiment::Metric;
+use crate::optimize::nelder_mead;
+use crate::plant_service::ManagementPolicy;
+use crate::rotation::RotationRunner;
+use crate::statistics::{mean, standard_deviation};
+use crate::weather_service::WeatherSeries;
+
+
+/// Size of the first simplex, as a share of the largest irrigation.
+const INITIAL_STEP: f64 = 0.25;
+/// The search stops once the expected outcome differs by less than this across the simplex.
+const TOLERANCE: f64 = 1e-3;
+/// Irrigations smaller than this (mm) are left out of the plan.
+const MIN_DEPTH_MM: f32 = 0.5;
+
+/// Searches for the irrigation schedule of a season that does best on average over an ensemble of
+/// weather, without using more water than the budget.
+///
+/// The plan irrigates every `interval` days; the search sets how much water each irrigation gives,
+/// the same for all those of a block of `block_days` days. The ensemble members follow the
+/// forecast, if any, for the days it covers and draw the weather of the other days.
+///
+/// ```json
+/// {
+///   "name": "Dry spring",
+///   "start_date": "2025-03-01",
+///   "water_budget_m3": 20,
+///   "objective": "profit",
+///   "forecast": "test_data/forecast.csv"
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IrrigationStudy {
+    pub name: String,
+    pub start_date: NaiveDate,
+    /// Irrigation water (m³) the farm may use over the season
+    pub water_budget_m3: f32,
+    /// What the plan maximizes: yield or profit
+    #[serde(default = "IrrigationStudy::default_objective")]
+    pub objective: Metric,
+    /// Weather scenarios every candidate schedule is run under
+    #[serde(default = "IrrigationStudy::default_members")]
+    pub members: u32,
+    /// Seed of the first member
+    #[serde(default)]
+    pub seed: u64,
+    /// CSV file of the forecast weather, with the columns `date,condition`
+    pub forecast: Option<String>,
+    /// Soil moisture (fraction of field capacity) of every plot at the start, if not the farm's own
+    pub soil_moisture: Option<f32>,
+    /// Days between irrigations
+    #[serde(default = "IrrigationStudy::default_interval")]
+    pub interval: u32,
+    #[serde(default = "IrrigationStudy::default_block_days")]
+    pub block_days: u32,
+    /// Most water (mm) a single irrigation gives
+    #[serde(default = "IrrigationStudy::default_max_depth_mm")]
+    pub max_depth_mm: f32,
+    #[serde(default = "IrrigationStudy::default_max_evaluations")]
+    pub max_evaluations: usize,
+}
+
+impl IrrigationStudy {
+    fn default_objective() -> Metric {
+        Metric::Profit
+    }
+
+    fn default_members() -> u32 {
+        8
+    }
+
+    fn default_interval() -> u32 {
+        3
+    }
+
+    fn default_block_days() -> u32 {
+        7
+    }
+
+    fn default_max_depth_mm() -> f32 {
+        40.0
+    }
+
+    fn default_max_evaluations() -> usize {
+        150
+    }
+
+    /// Reads a study from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AgroException> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", path.display(), err)))?;
+        let study: IrrigationStudy = serde_json::from_str(&content)
+            .map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", path.display(), err)))?;
+        study.validate()?;
+        Ok(study)
+    }
+
+    fn validate(&self) -> Result<(), AgroException> {
+        if !matches!(self.objective, Metric::Yield | Metric::Profit) {
+            return Err(AgroException::InvalidExperiment(format!("cannot maximize {}", self.objective)));
+        }
+        if self.water_budget_m3 < 0.0 {
+            return Err(AgroException::InvalidExperiment("the water budget cannot be negative".to_string()));
+        }
+        if self.members == 0 {
+            return Err(AgroException::InvalidExperiment("at least 1 ensemble member is needed".to_string()));
+        }
+        if self.interval == 0 || self.block_days == 0 {
+            return Err(AgroException::InvalidExperiment("the interval and the blocks must last at least a day".to_string()));
+        }
+        if self.max_depth_mm <= 0.0 {
+            return Err(AgroException::InvalidExperiment("irrigations must be able to give some water".to_string()));
+        }
+        if let Some(moisture) = self.soil_moisture {
+            if !(0.0..=1.0).contains(&moisture) {
+                return Err(AgroException::InvalidExperiment(format!("soil moisture {} is not between 0 and 1", moisture)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Plans the irrigations of a season on fresh farms from `farm`, and compares the plan with the
+    /// fixed irrigation interval of the default policy under the same budget.
+    pub fn run(&self, farm: impl Fn() -> Result<Farm, AgroException> + Sync) -> Result<IrrigationReport, AgroException> {
+        self.validate()?;
+        let farm = || -> Result<Farm, AgroException> {
+            let mut farm = farm()?;
+            if let Some(moisture) = self.soil_moisture {
+                farm.plots.iter_mut().for_each(|plot| plot.soil.moisture = moisture);
+            }
+            Ok(farm)
+        };
+        let ensemble = self.ensemble(farm()?)?;
+
+        let baseline_policy = ManagementPolicy {
+            water_budget_m3: Some(self.water_budget_m3),
+            ..Default::default()
+        };
+        let (baseline, days) = self.outlook(&baseline_policy, &ensemble, &farm)?;
+        let blocks = days.div_ceil(self.block_days) as usize;
+
+        let mut error = None;
+        let minimum = nelder_mead(
+            |point| match self.outlook(&self.policy(&self.plan(point, days), &baseline_policy), &ensemble, &farm) {
+                Ok((outlook, _)) => -outlook.mean,
+                Err(err) => {
+                    error.get_or_insert(err);
+                    f64::INFINITY
+                },
+            },
+            &vec![0.5; blocks],
+            INITIAL_STEP,
+            self.max_evaluations,
+            TOLERANCE,
+        );
+        if let Some(err) = error {
+            return Err(err);
+        }
+
+        let plan = self.plan(&minimum.point, days);
+        let (planned, _) = self.outlook(&self.policy(&plan, &baseline_policy), &ensemble, &farm)?;
+        Ok(IrrigationReport {
+            name: self.name.to_string(),
+            objective: self.objective,
+            members: self.members,
+            evaluations: minimum.evaluations,
+            plan,
+            baseline,
+            planned,
+        })
+    }
+
+    /// Weather of every member over the season, the forecast days included.
+    fn ensemble(&self, farm: Farm) -> Result<Vec<Arc<WeatherSeries>>, AgroException> {
+        let forecast = self.forecast.as_deref().map(WeatherSeries::import_csv).transpose()?;
+        let runner = RotationRunner::new(farm, 1, 1).starting_on(self.start_date);
+        let hemisphere = Hemisphere::from_location(&runner.farm().location);
+        Ok((0..self.members)
+            .map(|member| {
+                let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(member as u64));
+                let mut series = WeatherSeries::generate(runner.start_date(), runner.end_date(), hemisphere, &mut rng);
+                if let Some(forecast) = forecast.as_ref() {
+                    series.overlay(forecast);
+                }
+                Arc::new(series)
+            })
+            .collect())
+    }
+
+    /// Runs a season under every member with `policy`.
+    /// Returns the outlook and the length of the longest season.
+    fn outlook(
+        &self,
+        policy: &ManagementPolicy,
+        ensemble: &[Arc<WeatherSeries>],
+        farm: &(impl Fn() -> Result<Farm, AgroException> + Sync),
+    ) -> Result<(Outlook, u32), AgroException> {
+        let runs: Vec<(f64, f64, u32)> = ensemble.par_iter().enumerate()
+            .map(|(member, weather_series)| {
+                let report = RotationRunner::new(farm()?, 1, 1)
+                    .starting_on(self.start_date)
+                    .quiet()
+                    .with_policy(policy.clone())
+                    .with_seed(self.seed.wrapping_add(member as u64))
+                    .with_weather(weather_series.clone())
+                    .run()?;
+                let days = report.seasons.iter().map(|season| season.days).max().unwrap_or(0);
+                Ok((self.objective.of(&report), Metric::WaterUse.of(&report), days))
+            })
+            .collect::<Result<_, AgroException>>()?;
+
+        let outcomes: Vec<f64> = runs.iter().map(|(outcome, _, _)| *outcome).collect();
+        let water: Vec<f64> = runs.iter().map(|(_, water, _)| *water).collect();
+        let outlook = Outlook {
+            mean: mean(&outcomes),
+            standard_deviation: standard_deviation(&outcomes),
+            water_m3: mean(&water),
+        };
+        Ok((outlook, runs.iter().map(|(_, _, days)| *days).max().unwrap_or(0)))
+    }
+
+    fn policy(&self, plan: &IrrigationPlan, baseline: &ManagementPolicy) -> ManagementPolicy {
+        let mut policy = baseline.clone();
+        plan.apply(&mut policy);
+        policy
+    }
+
+    /// Schedule of a season of `days` days with the irrigations of every block at `point` within
+    /// [0, `max_depth_mm`].
+    fn plan(&self, point: &[f64], days: u32) -> IrrigationPlan {
+        let irrigations = (1..=days)
+            .step_by(self.interval as usize)
+            .filter_map(|day| {
+                let unit = point.get(((day - 1) / self.block_days) as usize).copied().unwrap_or(0.0);
+                let depth_mm = (unit as f32 * self.max_depth_mm * 10.0).round() / 10.0;
+                (depth_mm >= MIN_DEPTH_MM).then(|| PlannedIrrigation {
+                    day,
+                    date: self.start_date + Duration::days(day as i64 - 1),
+                    depth_mm,
+                })
+            })
+            .collect();
+        IrrigationPlan { water_budget_m3: self.water_budget_m3, irrigations }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PlannedIrrigation {
+    /// Day of the season
+    pub day: u32,
+    pub date: NaiveDate,
+    pub depth_mm: f32,
+}
+
+/// Irrigations of a season, day by day.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IrrigationPlan {
+    pub water_budget_m3: f32,
+    pub irrigations: Vec<PlannedIrrigation>,
+}
+
+impl IrrigationPlan {
+    /// Has the crew irrigate by the plan, within its budget, instead of every few days.
+    pub fn apply(&self, policy: &mut ManagementPolicy) {
+        policy.irrigation_schedule = self.irrigations.iter()
+            .map(|irrigation| (irrigation.day, irrigation.depth_mm))
+            .collect::<BTreeMap<u32, f32>>();
+        policy.water_budget_m3 = Some(self.water_budget_m3);
+    }
+
+    pub fn total_depth_mm(&self) -> f32 {
+        self.irrigations.iter().map(|irrigation| irrigation.depth_mm).sum()
+    }
+}
+
+/// Expected outcome of a season over the ensemble.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Outlook {
+    pub mean: f64,
+    pub standard_deviation: f64,
+    /// Mean irrigation water used (m³)
+    pub water_m3: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct IrrigationReport {
+    pub name: String,
+    pub objective: Metric,
+    pub members: u32,
+    pub evaluations: usize,
+    pub plan: IrrigationPlan,
+    /// Outlook of the fixed irrigation interval under the same budget
+    pub baseline: Outlook,
+    pub planned: Outlook,
+}
+
+impl fmt::Display for IrrigationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Irrigation plan: {} ({} members, {} evaluations, budget {:.1} m³)",
+            self.name,
+            self.members,
+            self.evaluations,
+            self.plan.water_budget_m3,
+        )?;
+        writeln!(f, "  {:<16} {:>12} {:>12} {:>10}", "", self.objective.to_string(), "Std dev", "Water (m³)")?;
+        for (name, outlook) in [("Fixed interval", &self.baseline), ("Plan", &self.planned)] {
+            writeln!(f, "  {:<16} {:>12.2} {:>12.2} {:>10.2}", name, outlook.mean, outlook.standard_deviation, outlook.water_m3)?;
+        }
+        writeln!(f, "  {} irrigations, {:.1} mm in all:", self.plan.irrigations.len(), self.plan.total_depth_mm())?;
+        for irrigation in self.plan.irrigations.iter() {
+            writeln!(f, "    Day {:>3} ({}): {:>5.1} mm", irrigation.day, irrigation.date, irrigation.depth_mm)?;
+        }
+        Ok(())
+    }
+}
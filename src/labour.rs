@@ -0,0 +1,287 @@
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use serde::Serialize;
+
+use crate::economics::ManagementAction;
+
+
+/// Work left below this many hours counts as done.
+const HOURS_TOLERANCE: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Worker,
+    Equipment,
+}
+
+/// A worker or a machine, and the hours it can put in every day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resource {
+    pub name: String,
+    pub kind: ResourceKind,
+    pub hours_per_day: f32,
+}
+
+impl Resource {
+    pub fn worker(name: &str, hours_per_day: f32) -> Self {
+        Self { name: name.to_string(), kind: ResourceKind::Worker, hours_per_day }
+    }
+
+    pub fn equipment(name: &str, hours_per_day: f32) -> Self {
+        Self { name: name.to_string(), kind: ResourceKind::Equipment, hours_per_day }
+    }
+}
+
+/// A management action waiting for labour and machinery hours.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    pub action: ManagementAction,
+    pub queued_on: u32,
+    pub labour_hours: f32,
+    pub machinery_hours: f32,
+}
+
+impl Task {
+    /// Duration of an action over `area_ha` of land with `crops` plants on it.
+    pub fn new(action: ManagementAction, queued_on: u32, area_ha: f32, crops: u32) -> Self {
+        let (labour_per_ha, machinery_per_ha) = action.hours_per_ha();
+        Self {
+            action,
+            queued_on,
+            labour_hours: labour_per_ha * area_ha + action.hours_per_crop() * crops as f32,
+            machinery_hours: machinery_per_ha * area_ha,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.labour_hours < HOURS_TOLERANCE && self.machinery_hours < HOURS_TOLERANCE
+    }
+}
+
+/// A task that has been carried out.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CompletedTask {
+    pub action: ManagementAction,
+    pub queued_on: u32,
+    pub completed_on: u32,
+}
+
+impl CompletedTask {
+    /// Days the task waited for capacity.
+    pub fn delay(&self) -> u32 {
+        self.completed_on - self.queued_on
+    }
+}
+
+/// Hours a resource had available and spent over a run.
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct ResourceUsage {
+    pub available_hours: f32,
+    pub used_hours: f32,
+}
+
+impl ResourceUsage {
+    /// Share of the available hours that were used (0.0 - 1.0).
+    pub fn utilization(&self) -> f32 {
+        if self.available_hours <= 0.0 {
+            return 0.0;
+        }
+        self.used_hours / self.available_hours
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct ScheduleReport {
+    pub usage: BTreeMap<String, ResourceUsage>,
+    pub completed: Vec<CompletedTask>,
+}
+
+impl ScheduleReport {
+    pub fn delayed_tasks(&self) -> Vec<&CompletedTask> {
+        self.completed.iter().filter(|task| task.delay() > 0).collect()
+    }
+}
+
+impl fmt::Display for ScheduleReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, usage) in self.usage.iter() {
+            writeln!(
+                f,
+                "  {:<12} {:>6.1} of {:>6.1} h ({:.0}%)",
+                name,
+                usage.used_hours,
+                usage.available_hours,
+                usage.utilization() * 100.0
+            )?;
+        }
+        for task in self.delayed_tasks() {
+            writeln!(f, "  {:?} queued on day {} was delayed by {} days", task.action, task.queued_on, task.delay())?;
+        }
+        Ok(())
+    }
+}
+
+/// Works through the queued tasks in order with the daily hours of the crew.
+/// Without any equipment, machinery hours are left out and the work is done by hand.
+#[derive(Debug, Clone)]
+pub struct LabourScheduler {
+    resources: Vec<Resource>,
+    queue: VecDeque<Task>,
+    report: ScheduleReport,
+}
+
+impl Default for LabourScheduler {
+    fn default() -> Self {
+        Self::new(vec![
+            Resource::worker("Worker 1", 8.0),
+            Resource::worker("Worker 2", 6.0),
+            Resource::equipment("Tractor", 6.0),
+        ])
+    }
+}
+
+impl LabourScheduler {
+    pub fn new(resources: Vec<Resource>) -> Self {
+        Self {
+            resources,
+            queue: VecDeque::new(),
+            report: ScheduleReport::default(),
+        }
+    }
+
+    pub fn schedule(&mut self, task: Task) {
+        self.queue.push_back(task);
+    }
+
+    pub fn is_queued(&self, action: ManagementAction) -> bool {
+        self.queue.iter().any(|task| task.action == action)
+    }
+
+    fn has_equipment(&self) -> bool {
+        self.resources.iter().any(|resource| resource.kind == ResourceKind::Equipment)
+    }
+
+    /// Spends the hours of `day` on the queue, oldest task first. Returns the actions finished today.
+    pub fn run_day(&mut self, day: u32) -> Vec<ManagementAction> {
+        let has_equipment = self.has_equipment();
+        let mut hours_left: Vec<f32> = self.resources.iter().map(|resource| resource.hours_per_day).collect();
+        for task in self.queue.iter_mut() {
+            if !has_equipment {
+                task.machinery_hours = 0.0;
+            }
+            for (resource, hours) in self.resources.iter().zip(hours_left.iter_mut()) {
+                let needed = match resource.kind {
+                    ResourceKind::Worker => &mut task.labour_hours,
+                    ResourceKind::Equipment => &mut task.machinery_hours,
+                };
+                let spent = needed.min(*hours);
+                *needed -= spent;
+                *hours -= spent;
+                self.report.usage.entry(resource.name.to_string()).or_default().used_hours += spent;
+            }
+        }
+        for resource in self.resources.iter() {
+            self.report.usage.entry(resource.name.to_string()).or_default().available_hours += resource.hours_per_day;
+        }
+
+        let mut completed = Vec::new();
+        self.queue.retain(|task| {
+            if task.is_done() {
+                completed.push(task.action);
+                self.report.completed.push(CompletedTask {
+                    action: task.action,
+                    queued_on: task.queued_on,
+                    completed_on: day,
+                });
+                false
+            } else {
+                true
+            }
+        });
+        completed
+    }
+
+    pub fn report(&self) -> ScheduleReport {
+        self.report.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() < tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    fn task(action: ManagementAction, queued_on: u32, labour_hours: f32, machinery_hours: f32) -> Task {
+        Task { action, queued_on, labour_hours, machinery_hours }
+    }
+
+    #[test]
+    fn a_task_needs_hours_for_the_area_and_for_every_plant() {
+        let weeding = Task::new(ManagementAction::Weeding, 3, 0.5, 100);
+
+        assert_close(weeding.labour_hours, 12.0 * 0.5 + 0.02 * 100.0, 1e-4);
+        assert_eq!(weeding.machinery_hours, 0.0);
+        assert_eq!(weeding.queued_on, 3);
+    }
+
+    #[test]
+    fn a_task_waits_for_the_scarcest_resource() {
+        let mut scheduler = LabourScheduler::new(vec![Resource::worker("Worker", 8.0), Resource::equipment("Tractor", 3.0)]);
+        scheduler.schedule(task(ManagementAction::Tillage, 1, 4.0, 4.0));
+
+        assert!(scheduler.run_day(1).is_empty());
+        assert!(scheduler.is_queued(ManagementAction::Tillage));
+        assert_eq!(scheduler.run_day(2), vec![ManagementAction::Tillage]);
+
+        let report = scheduler.report();
+        assert_eq!(report.completed, vec![CompletedTask { action: ManagementAction::Tillage, queued_on: 1, completed_on: 2 }]);
+        assert_eq!(report.delayed_tasks().len(), 1);
+        assert_close(report.usage["Tractor"].used_hours, 4.0, 1e-4);
+        assert_close(report.usage["Worker"].used_hours, 4.0, 1e-4);
+    }
+
+    #[test]
+    fn the_oldest_task_is_served_first() {
+        let mut scheduler = LabourScheduler::new(vec![Resource::worker("Worker", 8.0)]);
+        scheduler.schedule(task(ManagementAction::Planting, 1, 6.0, 0.0));
+        scheduler.schedule(task(ManagementAction::Weeding, 1, 6.0, 0.0));
+
+        assert_eq!(scheduler.run_day(1), vec![ManagementAction::Planting]);
+        assert_eq!(scheduler.run_day(2), vec![ManagementAction::Weeding]);
+        assert_eq!(scheduler.report().delayed_tasks()[0].action, ManagementAction::Weeding);
+    }
+
+    #[test]
+    fn without_equipment_the_machinery_hours_are_dropped() {
+        let mut scheduler = LabourScheduler::new(vec![Resource::worker("Worker", 8.0)]);
+        scheduler.schedule(task(ManagementAction::Harvest, 4, 2.0, 10.0));
+
+        assert_eq!(scheduler.run_day(4), vec![ManagementAction::Harvest]);
+        assert_eq!(scheduler.report().completed[0].delay(), 0);
+    }
+
+    #[test]
+    fn no_resource_works_more_than_its_daily_hours() {
+        let mut scheduler = LabourScheduler::default();
+        for day in 0..3 {
+            scheduler.schedule(Task::new(ManagementAction::Weeding, day, 2.0, 200));
+        }
+
+        for day in 0..5 {
+            scheduler.run_day(day);
+        }
+
+        let report = scheduler.report();
+        assert_close(report.usage["Worker 1"].available_hours, 5.0 * 8.0, 1e-4);
+        assert_close(report.usage["Worker 1"].utilization(), 1.0, 1e-4);
+        assert_close(report.usage["Worker 2"].used_hours, 5.0 * 6.0, 1e-4);
+        assert_eq!(report.usage["Tractor"].used_hours, 0.0);
+        assert!(report.completed.len() < 3);
+        assert_eq!(ResourceUsage::default().utilization(), 0.0);
+    }
+}
@@ -1,22 +1,287 @@
+mod access;
+mod astronomy;
+mod calibration;
+mod calendar;
+mod cli;
 mod crop_parser;
+mod dashboard;
 mod dto;
+mod economics;
+mod errors;
+mod experiment;
+mod failure;
+mod fertilizer;
+mod germination;
+mod greenhouse;
+mod inventory;
+mod irrigation;
+mod labour;
+mod nursery;
+mod optimize;
+mod parameters;
 mod plant_service;
+mod planner;
+mod progress;
+mod region;
+mod registry;
+mod report;
+mod rotation;
+mod server;
 mod setup;
 mod seeds;
+mod sensitivity;
+mod soil;
+mod statistics;
+mod storage;
 mod weather_service;
 mod file_ops;
+mod growth;
 mod os;
 
-use setup::setup_farm;
-use crate::plant_service::PlantService;
+use chrono::NaiveDate;
+use clap::Parser;
+use crate::access::{Operation, Role};
+use crate::calibration::CalibrationStudy;
+use crate::cli::{AgroCLI, Commands};
+use crate::dashboard::Dashboard;
+use crate::dto::Farm;
+use crate::errors::AgroException;
+use crate::experiment::Experiment;
+use crate::fertilizer::FertilizerTrial;
+use crate::irrigation::IrrigationStudy;
+use setup::{demo_credentials, demo_farm, setup_farm};
+use crate::planner::{PlantingPlanner, PlotRequest};
+use crate::region::RegionRunner;
+use crate::registry::{FarmRecord, FarmRegistry};
+use crate::rotation::{RotationRunner, RotationStep};
+use crate::seeds::SeedType;
+use crate::sensitivity::SensitivityStudy;
+use crate::server::ApiServer;
+
+const SEASONS: u32 = 3;
+const SEASONS_PER_YEAR: u32 = 2;
+const REGISTRY_PATH: &str = "farms.json";
 
 
 fn main() {
     // group_crops();
     // split_a_crop();
-    let farm = setup_farm().unwrap_or_else(|err| panic!("Error: {}", err));
+    match AgroCLI::parse().command {
+        Some(Commands::Serve { address, workers }) => {
+            let server = ApiServer::new(FarmRegistry::new(REGISTRY_PATH), workers);
+            if let Err(err) = server.serve(&address) {
+                eprintln!("Error: {}", err);
+            }
+        },
+        Some(Commands::Crop { list, get }) => show_crops(list, get),
+        Some(Commands::Dashboard { days_per_second }) => {
+            let farm = match registered_farm() {
+                Ok((farm, _)) => farm,
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    return;
+                },
+            };
+            let runner = RotationRunner::new(farm, SEASONS, SEASONS_PER_YEAR).starting_on(start_date()).quiet();
+            if let Err(err) = Dashboard::new(days_per_second).run(runner) {
+                eprintln!("Error: {}", err);
+            }
+        },
+        Some(Commands::Region { shared_weather }) => simulate_region(shared_weather),
+        Some(Commands::Experiment { path }) => run_experiment(&path),
+        Some(Commands::Sensitivity { path }) => run_sensitivity(&path),
+        Some(Commands::Calibrate { path, output }) => run_calibration(&path, output.as_deref()),
+        Some(Commands::Irrigation { path, output }) => plan_irrigation(&path, output.as_deref()),
+        Some(Commands::Fertilizer { path }) => recommend_fertilizer(&path),
+        None => simulate(),
+    }
+}
+
+fn show_crops(list: bool, get: Option<String>) {
+    if list {
+        for seed_type in SeedType::catalog() {
+            println!("{}", seed_type.get_verbose_name());
+        }
+    }
+    if let Some(name) = get {
+        match SeedType::from_str(&name) {
+            Ok(seed_type) => {
+                let window = seed_type.sowing_window();
+                println!(
+                    "{}: sown in months {}-{}, {} kg/ha nitrogen, {} kg per plant{}",
+                    seed_type.get_verbose_name(),
+                    window.first_month,
+                    window.last_month,
+                    seed_type.nitrogen_demand(),
+                    seed_type.reference_yield(),
+                    if seed_type.is_transplanted() { ", transplanted" } else { "" },
+                );
+            },
+            Err(err) => eprintln!("Error: {}", err),
+        }
+    }
+}
+
+/// The first active farm of the registry, registering the demo farm on the first run,
+/// and the role of the demo user on it.
+fn registered_farm() -> Result<(Farm, Role), AgroException> {
+    let record = active_records()?.remove(0);
+    let role = record.authorize(&demo_credentials(), Operation::RunSimulation)?;
+    Ok((setup_farm(&record)?, role))
+}
+
+/// The active farms of the registry, registering the demo farm on the first run.
+fn active_records() -> Result<Vec<FarmRecord>, AgroException> {
+    let registry = FarmRegistry::new(REGISTRY_PATH);
+    match registry.list_active()? {
+        records if !records.is_empty() => Ok(records),
+        _ => Ok(vec![registry.create(demo_farm())?]),
+    }
+}
+
+fn start_date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 3, 1).unwrap()
+}
+
+fn simulate() {
+    let (farm, role) = match registered_farm() {
+        Ok(registered) => registered,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return;
+        },
+    };
+    let start_date = start_date();
+
+    // Show how the seeds in the shed would fit on the plots of the first season.
+    let requests: Vec<PlotRequest> = farm.plots.iter()
+        .filter_map(|plot| match plot.rotation.step_for(0) {
+            RotationStep::Crop(name) => Some(PlotRequest { plot_id: plot.id, crops: vec![name] }),
+            _ => None,
+        })
+        .collect();
+    if role.allows(Operation::EditPlan) {
+        match PlantingPlanner::new(&farm, start_date).plan(&requests) {
+            Ok(layout) => print!("{}", layout),
+            Err(err) => eprintln!("Error: {}", err),
+        }
+    }
+
+    match RotationRunner::new(farm, SEASONS, SEASONS_PER_YEAR).starting_on(start_date).run() {
+        Ok(report) if role.allows(Operation::ViewReport) => {
+            println!("Rotation summary:");
+            print!("{}", report);
+        },
+        Ok(_) => eprintln!("Error: {}", AgroException::PermissionDenied),
+        Err(err) => eprintln!("Error: {}", err),
+    }
+}
+
+/// Runs every active farm the demo user may simulate, side by side.
+fn simulate_region(shared_weather: bool) {
+    let credentials = demo_credentials();
+    let records = match active_records() {
+        Ok(records) => records,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return;
+        },
+    };
+    let runners: Vec<RotationRunner> = records.iter()
+        .filter(|record| record.authorize(&credentials, Operation::RunSimulation).is_ok())
+        .filter_map(|record| setup_farm(record).ok())
+        .map(|farm| RotationRunner::new(farm, SEASONS, SEASONS_PER_YEAR).starting_on(start_date()).quiet())
+        .collect();
+    let mut region = RegionRunner::new(runners);
+    if shared_weather {
+        region = region.with_shared_weather();
+    }
+    print!("{}", region.run());
+}
+
+/// The first farm of the registry, if the demo user may run simulations on it.
+fn simulated_record() -> Result<FarmRecord, AgroException> {
+    let record = active_records()?.remove(0);
+    record.authorize(&demo_credentials(), Operation::RunSimulation)?;
+    Ok(record)
+}
+
+/// Runs the experiment in `path` on the first farm of the registry.
+fn run_experiment(path: &str) {
+    let result = simulated_record().and_then(|record| {
+        Experiment::load(path).and_then(|experiment| experiment.run(|| setup_farm(&record)))
+    });
+    match result {
+        Ok(report) => print!("{}", report),
+        Err(err) => eprintln!("Error: {}", err),
+    }
+}
+
+/// Runs the sensitivity study in `path` on the first farm of the registry.
+fn run_sensitivity(path: &str) {
+    let result = simulated_record().and_then(|record| {
+        SensitivityStudy::load(path).and_then(|study| study.run(|| setup_farm(&record)))
+    });
+    match result {
+        Ok(report) => print!("{}", report),
+        Err(err) => eprintln!("Error: {}", err),
+    }
+}
+
+/// Fits the parameters of the calibration study in `path` on the first farm of the registry, and
+/// writes them to `output` if given.
+fn run_calibration(path: &str, output: Option<&str>) {
+    let result = simulated_record().and_then(|record| {
+        CalibrationStudy::load(path).and_then(|study| study.run(|| setup_farm(&record)))
+    });
+    match result {
+        Ok(report) => {
+            print!("{}", report);
+            if let Some(output) = output {
+                let written = serde_json::to_string_pretty(&report.parameters)
+                    .map_err(|err| err.to_string())
+                    .and_then(|json| std::fs::write(output, json).map_err(|err| err.to_string()));
+                match written {
+                    Ok(()) => println!("Fitted parameters written to {}", output),
+                    Err(err) => eprintln!("Error: {}", err),
+                }
+            }
+        },
+        Err(err) => eprintln!("Error: {}", err),
+    }
+}
+
+/// Plans the irrigations of the study in `path` on the first farm of the registry, and writes the
+/// plan to `output` if given.
+fn plan_irrigation(path: &str, output: Option<&str>) {
+    let result = simulated_record().and_then(|record| {
+        IrrigationStudy::load(path).and_then(|study| study.run(|| setup_farm(&record)))
+    });
+    match result {
+        Ok(report) => {
+            print!("{}", report);
+            if let Some(output) = output {
+                let written = serde_json::to_string_pretty(&report.plan)
+                    .map_err(|err| err.to_string())
+                    .and_then(|json| std::fs::write(output, json).map_err(|err| err.to_string()));
+                match written {
+                    Ok(()) => println!("Irrigation plan written to {}", output),
+                    Err(err) => eprintln!("Error: {}", err),
+                }
+            }
+        },
+        Err(err) => eprintln!("Error: {}", err),
+    }
+}
 
-    let planter = PlantService::new(farm);
-    planter.prepare_farm()
-        .run();
+/// Recommends fertilizer from the soil test in `path` and estimates the yield response on the
+/// first farm of the registry.
+fn recommend_fertilizer(path: &str) {
+    let result = simulated_record().and_then(|record| {
+        FertilizerTrial::load(path).and_then(|trial| trial.run(|| setup_farm(&record)))
+    });
+    match result {
+        Ok(response) => print!("{}", response),
+        Err(err) => eprintln!("Error: {}", err),
+    }
 }
@@ -0,0 +1,41 @@
+#![allow(dead_code)]
+
+use crate::greenhouse::{Greenhouse, Microclimate};
+
+
+/// Bench area (m²) taken by one module tray cell.
+const CELL_AREA: f32 = 0.0025;
+
+/// Propagation house where seedlings are raised in module trays before they are planted out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nursery {
+    pub name: String,
+    /// Number of tray cells, i.e. seedlings it can raise at once
+    pub capacity: u32,
+    pub house: Greenhouse,
+}
+
+impl Nursery {
+    /// A propagation house kept warmer than a production greenhouse.
+    pub fn new(name: &str, capacity: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            capacity,
+            house: Greenhouse::default().with_setpoints(18.0, 24.0),
+        }
+    }
+
+    pub fn with_house(mut self, house: Greenhouse) -> Self {
+        self.house = house;
+        self
+    }
+
+    /// Bench area (m²) heated for the trays.
+    pub fn area(&self) -> f32 {
+        self.capacity as f32 * CELL_AREA
+    }
+
+    pub fn microclimate(&self, outdoor_temperature: f32, outdoor_radiation: f32) -> Microclimate {
+        self.house.microclimate(outdoor_temperature, outdoor_radiation, self.area())
+    }
+}
@@ -0,0 +1,135 @@
+#![allow(dead_code)]
+
+
+/// Best point found by a search and the value of the objective there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimum {
+    pub point: Vec<f64>,
+    pub value: f64,
+    pub evaluations: usize,
+}
+
+/// Derivative-free Nelder-Mead simplex search for the minimum of `objective` within the unit cube.
+///
+/// The simplex starts at `start` with a vertex `step` away along every axis, and the search stops
+/// after `max_evaluations` or once the values at the vertices are within `tolerance` of each other.
+/// Points are clamped to [0, 1] before being evaluated.
+pub fn nelder_mead(
+    mut objective: impl FnMut(&[f64]) -> f64,
+    start: &[f64],
+    step: f64,
+    max_evaluations: usize,
+    tolerance: f64,
+) -> Minimum {
+    const REFLECTION: f64 = 1.0;
+    const EXPANSION: f64 = 2.0;
+    const CONTRACTION: f64 = 0.5;
+    const SHRINK: f64 = 0.5;
+
+    let dimensions = start.len();
+    let clamp = |point: Vec<f64>| -> Vec<f64> { point.into_iter().map(|x| x.clamp(0.0, 1.0)).collect() };
+    let mut evaluations = 0;
+    let mut evaluate = |point: &[f64], evaluations: &mut usize| {
+        *evaluations += 1;
+        objective(point)
+    };
+
+    let mut simplex: Vec<(Vec<f64>, f64)> = Vec::new();
+    let start = clamp(start.to_vec());
+    simplex.push((start.clone(), evaluate(&start, &mut evaluations)));
+    if dimensions == 0 {
+        let (point, value) = simplex.swap_remove(0);
+        return Minimum { point, value, evaluations };
+    }
+    for axis in 0..dimensions {
+        let mut vertex = start.clone();
+        // Step inwards when the start lies on the upper bound.
+        vertex[axis] = if vertex[axis] + step <= 1.0 { vertex[axis] + step } else { vertex[axis] - step };
+        let vertex = clamp(vertex);
+        let value = evaluate(&vertex, &mut evaluations);
+        simplex.push((vertex, value));
+    }
+
+    while evaluations < max_evaluations {
+        simplex.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        let (best, worst) = (simplex[0].1, simplex[dimensions].1);
+        if (worst - best).abs() <= tolerance {
+            break;
+        }
+
+        let centroid: Vec<f64> = (0..dimensions)
+            .map(|axis| simplex[..dimensions].iter().map(|(point, _)| point[axis]).sum::<f64>() / dimensions as f64)
+            .collect();
+        let towards = |point: &[f64], factor: f64| -> Vec<f64> {
+            clamp(centroid.iter().zip(point.iter()).map(|(c, x)| c + factor * (x - c)).collect())
+        };
+
+        let reflected = towards(&simplex[dimensions].0, -REFLECTION);
+        let reflected_value = evaluate(&reflected, &mut evaluations);
+        if reflected_value < best {
+            let expanded = towards(&simplex[dimensions].0, -EXPANSION);
+            let expanded_value = evaluate(&expanded, &mut evaluations);
+            simplex[dimensions] = if expanded_value < reflected_value { (expanded, expanded_value) } else { (reflected, reflected_value) };
+        } else if reflected_value < simplex[dimensions - 1].1 {
+            simplex[dimensions] = (reflected, reflected_value);
+        } else {
+            let contracted = towards(&simplex[dimensions].0, CONTRACTION);
+            let contracted_value = evaluate(&contracted, &mut evaluations);
+            if contracted_value < worst {
+                simplex[dimensions] = (contracted, contracted_value);
+            } else {
+                let best_point = simplex[0].0.clone();
+                for (point, value) in simplex.iter_mut().skip(1) {
+                    *point = clamp(best_point.iter().zip(point.iter()).map(|(b, x)| b + SHRINK * (x - b)).collect());
+                    *value = evaluate(point, &mut evaluations);
+                }
+            }
+        }
+    }
+
+    simplex.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    let (point, value) = simplex.swap_remove(0);
+    Minimum { point, value, evaluations }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() < tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    #[test]
+    fn finds_the_minimum_of_a_quadratic() {
+        let quadratic = |x: &[f64]| (x[0] - 0.3).powi(2) + 2.0 * (x[1] - 0.7).powi(2) + 0.5 * (x[0] - 0.3) * (x[1] - 0.7) + 1.5;
+
+        let minimum = nelder_mead(quadratic, &[0.9, 0.1], 0.2, 500, 1e-12);
+
+        assert_close(minimum.point[0], 0.3, 1e-4);
+        assert_close(minimum.point[1], 0.7, 1e-4);
+        assert_close(minimum.value, 1.5, 1e-8);
+        assert!(minimum.evaluations < 500, "took {} evaluations", minimum.evaluations);
+    }
+
+    #[test]
+    fn stops_on_the_bound_when_the_minimum_lies_outside_the_unit_cube() {
+        let quadratic = |x: &[f64]| (x[0] - 1.5).powi(2) + (x[1] - 0.4).powi(2);
+
+        let minimum = nelder_mead(quadratic, &[0.5, 0.5], 0.2, 500, 1e-12);
+
+        assert_close(minimum.point[0], 1.0, 1e-4);
+        assert_close(minimum.point[1], 0.4, 1e-4);
+        assert_close(minimum.value, 0.25, 1e-6);
+    }
+
+    #[test]
+    fn stops_after_the_evaluation_budget() {
+        let minimum = nelder_mead(|x| x[0].powi(2) + x[1].powi(2), &[1.0, 1.0], 0.1, 10, 0.0);
+
+        // The last iteration starts below the budget and may reflect, contract and shrink the other
+        // two vertices: four evaluations at most.
+        assert!(minimum.evaluations <= 9 + 4, "took {} evaluations", minimum.evaluations);
+        assert!(minimum.value < 2.0);
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use std::{
     fs::{self, File},
     io,
@@ -24,13 +26,24 @@ pub(crate) trait Open {
     fn open(self, path: impl AsRef<Path>) -> io::Result<File>;
 }
 
+/// Advisory lock taken on a file once it is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LockMode {
+    /// Exclusive, failing at once if another process holds a lock
+    TryExclusive,
+    /// Shared with other readers, waiting for a writer to finish
+    Shared,
+    /// Exclusive, waiting for every other lock to be released
+    Exclusive,
+}
+
 pub(crate) struct OpenOptions {
     /// Instance of [`std::fs::OpenOptions`]
     inner: fs::OpenOptions,
     /// Bypasses OS cache
     bypass_cache: bool,
     sync_on_write: bool,
-    lock: bool,
+    lock: Option<LockMode>,
 }
 
 impl Default for OpenOptions {
@@ -39,7 +52,7 @@ impl Default for OpenOptions {
             inner: File::options(),
             bypass_cache: false,
             sync_on_write: false,
-            lock: false,
+            lock: None,
         }
     }
 }
@@ -56,7 +69,12 @@ impl OpenOptions {
     }
 
     pub fn lock(mut self, lock: bool) -> Self {
-        self.lock = lock;
+        self.lock = lock.then_some(LockMode::TryExclusive);
+        self
+    }
+
+    pub fn lock_mode(mut self, mode: LockMode) -> Self {
+        self.lock = Some(mode);
         self
     }
 
@@ -95,7 +113,7 @@ mod unix {
         path::Path,
     };
 
-    use super::{LocalFs, OpenOptions, Open, FsBlockSize};
+    use super::{LocalFs, LockMode, OpenOptions, Open, FsBlockSize};
 
     impl FsBlockSize for LocalFs {
         fn block_size(path: impl AsRef<Path>) -> io::Result<usize> {
@@ -118,8 +136,14 @@ mod unix {
 
             let file = self.inner.open(path)?;
 
-            if self.lock {
-                let lock = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+            if let Some(mode) = self.lock {
+                let operation = match mode {
+                    LockMode::TryExclusive => libc::LOCK_EX | libc::LOCK_NB,
+                    LockMode::Shared => libc::LOCK_SH,
+                    LockMode::Exclusive => libc::LOCK_EX,
+                };
+                // The lock goes with the descriptor and is released when the file is closed.
+                let lock = unsafe { libc::flock(file.as_raw_fd(), operation) };
                 if lock != 0 {
                     return Err(io::Error::last_os_error());
                 }
@@ -0,0 +1,62 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+use crate::dto::{Crop, GrowthStage};
+use crate::weather_service::WeatherParameters;
+
+
+/// Catalog values of one crop that a run replaces. Those left out keep the value of the seed type.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct CropParameters {
+    /// Days in a growth stage
+    pub stage_days: BTreeMap<GrowthStage, u32>,
+    /// Temperature (°C) below which the crop stops growing
+    pub base_temperature: Option<f32>,
+    /// Dry matter produced per unit of intercepted PAR (g/MJ)
+    pub radiation_use_efficiency: Option<f32>,
+    /// Soil moisture (fraction of field capacity) below which the crop suffers from water stress
+    pub water_stress_threshold: Option<f32>,
+}
+
+impl CropParameters {
+    /// Takes over every value set in `other`.
+    pub fn merge(&mut self, other: &CropParameters) {
+        self.stage_days.extend(other.stage_days.iter().map(|(stage, days)| (stage.clone(), *days)));
+        self.base_temperature = other.base_temperature.or(self.base_temperature);
+        self.radiation_use_efficiency = other.radiation_use_efficiency.or(self.radiation_use_efficiency);
+        self.water_stress_threshold = other.water_stress_threshold.or(self.water_stress_threshold);
+    }
+
+    pub fn apply(&self, crop: &mut Crop) {
+        crop.parameters.merge(self);
+    }
+}
+
+/// Model parameters that differ from the built-in ones, as tried by a sensitivity analysis or
+/// fitted by a calibration.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ModelParameters {
+    pub weather: WeatherParameters,
+    /// By crop name
+    pub crops: BTreeMap<String, CropParameters>,
+}
+
+impl ModelParameters {
+    pub fn crop(&self, crop_name: &str) -> Option<&CropParameters> {
+        self.crops.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(crop_name))
+            .map(|(_, parameters)| parameters)
+    }
+
+    pub fn crop_mut(&mut self, crop_name: &str) -> &mut CropParameters {
+        let name = self.crops.keys()
+            .find(|name| name.eq_ignore_ascii_case(crop_name))
+            .cloned()
+            .unwrap_or(crop_name.to_string());
+        self.crops.entry(name).or_default()
+    }
+}
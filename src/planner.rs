@@ -0,0 +1,444 @@
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::dto::{Crop, Farm, Plot};
+use crate::errors::AgroException;
+use crate::inventory::{SeedDemand, SeedInventory};
+use crate::seeds::SeedType;
+
+
+/// Side (m) of a cell of the ASCII map.
+const MAP_CELL: f32 = 0.5;
+
+/// What the planner optimises for when several crops share a plot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum PlanningObjective {
+    /// Rows are handed out in turns until no more fit or the seeds run out
+    #[default]
+    MaximizeArea,
+    /// Every crop gets an equal share of the plot width
+    BalanceSpecies,
+}
+
+/// Crops to be grown side by side on one plot, identified by their verbose names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlotRequest {
+    pub plot_id: u32,
+    pub crops: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanningError {
+    PlotNotFound(u32),
+    NoCropsRequested(String),
+    UnknownCrop(String),
+    /// The crop is requested more than once for the same plot
+    DuplicateCrop { crop: String, plot: String },
+    /// No usable seeds of the crop are left in the shed
+    NoSeeds(String),
+    /// The crop's spacing does not fit in the room left on the plot
+    DoesNotFit { crop: String, plot: String, reason: String },
+}
+
+impl fmt::Display for PlanningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanningError::PlotNotFound(plot_id) => write!(f, "Plot {} not found", plot_id),
+            PlanningError::NoCropsRequested(plot) => write!(f, "No crops requested for plot {}", plot),
+            PlanningError::UnknownCrop(crop) => write!(f, "Unknown crop {}", crop),
+            PlanningError::DuplicateCrop { crop, plot } => write!(f, "{} is requested more than once for plot {}", crop, plot),
+            PlanningError::NoSeeds(crop) => write!(f, "No seeds of {} in stock", crop),
+            PlanningError::DoesNotFit { crop, plot, reason } => write!(f, "{} does not fit on plot {}: {}", crop, plot, reason),
+        }
+    }
+}
+
+impl std::error::Error for PlanningError {}
+
+/// Position of a plant in metres from the corner of its plot. `x` runs along the width, `y` along the length.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A strip of the plot, across its whole length, sown in rows with a single crop.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Bed {
+    pub verbose_name: String,
+    /// Offset of the strip from the plot's edge (m)
+    pub x: f32,
+    pub width: f32,
+    pub rows: u32,
+    pub plants_per_row: u32,
+    pub plants: u32,
+    pub positions: Vec<Position>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlotLayout {
+    pub plot_id: u32,
+    pub name: String,
+    pub width: u32,
+    pub length: u32,
+    pub beds: Vec<Bed>,
+}
+
+impl PlotLayout {
+    /// Share of the plot area covered by beds (0.0 - 1.0).
+    pub fn area_used(&self) -> f32 {
+        let area = (self.width * self.length) as f32;
+        if area == 0.0 {
+            return 0.0;
+        }
+        self.beds.iter().map(|bed| bed.width * self.length as f32).sum::<f32>() / area
+    }
+
+    /// Plants of every crop on the plot.
+    pub fn counts(&self) -> BTreeMap<String, u32> {
+        let mut counts = BTreeMap::new();
+        for bed in self.beds.iter() {
+            *counts.entry(bed.verbose_name.to_string()).or_insert(0) += bed.plants;
+        }
+        counts
+    }
+
+    /// Top view of the plot. Upper case letters mark plants, lower case the rest of their bed and '.' the free ground.
+    pub fn to_ascii(&self) -> String {
+        let columns = (self.width as f32 / MAP_CELL).ceil() as usize;
+        let lines = (self.length as f32 / MAP_CELL).ceil() as usize;
+        let mut grid = vec![vec!['.'; columns]; lines];
+
+        for bed in self.beds.iter() {
+            let symbol = Self::symbol(&bed.verbose_name);
+            let first_column = (bed.x / MAP_CELL).floor() as usize;
+            let last_column = (((bed.x + bed.width) / MAP_CELL).ceil() as usize).min(columns);
+            for line in grid.iter_mut() {
+                for cell in line[first_column..last_column].iter_mut() {
+                    *cell = symbol.to_ascii_lowercase();
+                }
+            }
+            for position in bed.positions.iter() {
+                let column = ((position.x / MAP_CELL) as usize).min(columns - 1);
+                let line = ((position.y / MAP_CELL) as usize).min(lines - 1);
+                grid[line][column] = symbol;
+            }
+        }
+
+        let mut map = format!("Plot {} ({} x {} m, {:.0}% used)\n", self.name, self.width, self.length, self.area_used() * 100.0);
+        map.push_str(&format!("+{}+\n", "-".repeat(columns)));
+        for line in grid {
+            map.push_str(&format!("|{}|\n", line.into_iter().collect::<String>()));
+        }
+        map.push_str(&format!("+{}+\n", "-".repeat(columns)));
+        for (name, count) in self.counts() {
+            map.push_str(&format!("  {} = {} ({} plants)\n", Self::symbol(&name), name, count));
+        }
+        map
+    }
+
+    fn symbol(verbose_name: &str) -> char {
+        verbose_name.chars().next().unwrap_or('?').to_ascii_uppercase()
+    }
+}
+
+/// Planting layout of the farm, one entry per planned plot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlantingLayout {
+    pub objective: PlanningObjective,
+    pub sowing_date: NaiveDate,
+    pub plots: Vec<PlotLayout>,
+}
+
+impl PlantingLayout {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Takes the planned seeds out of the shed and returns them as crops on their plots.
+    pub fn draw_crops(&self, inventory: &mut SeedInventory) -> Result<Vec<Crop>, AgroException> {
+        let mut crops = Vec::new();
+        for plot in self.plots.iter() {
+            for bed in plot.beds.iter() {
+                let demand = SeedDemand {
+                    verbose_name: bed.verbose_name.to_string(),
+                    seeds: bed.plants,
+                    sowing_date: self.sowing_date,
+                };
+                let (drawn, _) = inventory.draw(&demand, Some(plot.plot_id))?;
+                crops.extend(drawn);
+            }
+        }
+        Ok(crops)
+    }
+}
+
+impl fmt::Display for PlantingLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for plot in self.plots.iter() {
+            write!(f, "{}", plot.to_ascii())?;
+        }
+        Ok(())
+    }
+}
+
+/// Lays crops out on the plots of a farm in rows, following their spacing and the seeds in the shed.
+/// Every crop gets at most the seeds a season of the rotation sows on a plot, see `SeedInventory::sowing_size`.
+pub struct PlantingPlanner<'a> {
+    farm: &'a Farm,
+    objective: PlanningObjective,
+    sowing_date: NaiveDate,
+}
+
+/// A crop being laid out on a plot.
+struct Allocation {
+    verbose_name: String,
+    row_spacing: f32,
+    plant_spacing: f32,
+    plants_per_row: u32,
+    /// Seeds to lay out: a sowing, or what is left of the stock if that is less
+    seeds: u32,
+    rows: u32,
+}
+
+impl Allocation {
+    fn rows_needed(&self) -> u32 {
+        self.seeds.div_ceil(self.plants_per_row)
+    }
+}
+
+impl<'a> PlantingPlanner<'a> {
+    pub fn new(farm: &'a Farm, sowing_date: NaiveDate) -> Self {
+        Self {
+            farm,
+            objective: PlanningObjective::default(),
+            sowing_date,
+        }
+    }
+
+    pub fn with_objective(mut self, objective: PlanningObjective) -> Self {
+        self.objective = objective;
+        self
+    }
+
+    /// Plans every requested plot. Seeds used on one plot are no longer available to the next ones.
+    pub fn plan(&self, requests: &[PlotRequest]) -> Result<PlantingLayout, PlanningError> {
+        let mut seeds_left: HashMap<String, u32> = HashMap::new();
+        let mut plots = Vec::new();
+        for request in requests {
+            let plot = self.farm.plots.iter()
+                .find(|plot| plot.id == request.plot_id)
+                .ok_or(PlanningError::PlotNotFound(request.plot_id))?;
+            plots.push(self.plan_plot(plot, &request.crops, &mut seeds_left)?);
+        }
+        Ok(PlantingLayout {
+            objective: self.objective,
+            sowing_date: self.sowing_date,
+            plots,
+        })
+    }
+
+    fn plan_plot(&self, plot: &Plot, crops: &[String], seeds_left: &mut HashMap<String, u32>) -> Result<PlotLayout, PlanningError> {
+        if crops.is_empty() {
+            return Err(PlanningError::NoCropsRequested(plot.name.to_string()));
+        }
+        let width = plot.size.width as f32;
+        let length = plot.size.length as f32;
+
+        let mut allocations: Vec<Allocation> = Vec::new();
+        for name in crops {
+            if allocations.iter().any(|allocation| allocation.verbose_name.eq_ignore_ascii_case(name)) {
+                return Err(PlanningError::DuplicateCrop { crop: name.to_string(), plot: plot.name.to_string() });
+            }
+            let seed_type = SeedType::from_str(name).map_err(|_| PlanningError::UnknownCrop(name.to_string()))?;
+            let (row_spacing, plant_spacing) = seed_type.spacing();
+            if row_spacing > width || plant_spacing > length {
+                return Err(PlanningError::DoesNotFit {
+                    crop: name.to_string(),
+                    plot: plot.name.to_string(),
+                    reason: format!("spacing of {} x {} m on a {} x {} m plot", row_spacing, plant_spacing, width, length),
+                });
+            }
+            let seeds_in_stock = *seeds_left.entry(name.to_lowercase())
+                .or_insert_with(|| self.farm.inventory.available(name, self.sowing_date));
+            if seeds_in_stock == 0 {
+                return Err(PlanningError::NoSeeds(name.to_string()));
+            }
+            allocations.push(Allocation {
+                verbose_name: seed_type.get_verbose_name(),
+                row_spacing,
+                plant_spacing,
+                plants_per_row: (length / plant_spacing).floor() as u32,
+                seeds: seeds_in_stock.min(self.farm.inventory.sowing_size(name)),
+                rows: 0,
+            });
+        }
+
+        match self.objective {
+            PlanningObjective::MaximizeArea => Self::fill_rows(&mut allocations, width),
+            PlanningObjective::BalanceSpecies => Self::share_width(&mut allocations, width),
+        }
+
+        let mut beds = Vec::new();
+        let mut x = 0.0;
+        for allocation in allocations {
+            if allocation.rows == 0 {
+                return Err(PlanningError::DoesNotFit {
+                    crop: allocation.verbose_name,
+                    plot: plot.name.to_string(),
+                    reason: format!("no room left for a row of {} m", allocation.row_spacing),
+                });
+            }
+            let bed = Self::bed(&allocation, x);
+            *seeds_left.get_mut(&allocation.verbose_name.to_lowercase()).unwrap() -= bed.plants;
+            x += bed.width;
+            beds.push(bed);
+        }
+
+        Ok(PlotLayout {
+            plot_id: plot.id,
+            name: plot.name.to_string(),
+            width: plot.size.width,
+            length: plot.size.length,
+            beds,
+        })
+    }
+
+    /// Hands out one row per crop in turns, for as long as a row still fits and seeds are left.
+    fn fill_rows(allocations: &mut [Allocation], width: f32) {
+        let mut free_width = width;
+        loop {
+            let mut added = false;
+            for allocation in allocations.iter_mut() {
+                if allocation.rows < allocation.rows_needed() && allocation.row_spacing <= free_width + f32::EPSILON * width {
+                    allocation.rows += 1;
+                    free_width -= allocation.row_spacing;
+                    added = true;
+                }
+            }
+            if !added {
+                break;
+            }
+        }
+    }
+
+    /// Gives every crop the same width, even if it cannot use all of it.
+    fn share_width(allocations: &mut [Allocation], width: f32) {
+        let share = width / allocations.len() as f32;
+        for allocation in allocations.iter_mut() {
+            allocation.rows = ((share / allocation.row_spacing).floor() as u32).min(allocation.rows_needed());
+        }
+    }
+
+    fn bed(allocation: &Allocation, x: f32) -> Bed {
+        let plants = (allocation.rows * allocation.plants_per_row).min(allocation.seeds);
+        let mut positions = Vec::new();
+        for row in 0..allocation.rows {
+            for plant in 0..allocation.plants_per_row {
+                if positions.len() as u32 == plants {
+                    break;
+                }
+                positions.push(Position {
+                    x: x + allocation.row_spacing * (row as f32 + 0.5),
+                    y: allocation.plant_spacing * (plant as f32 + 0.5),
+                });
+            }
+        }
+        Bed {
+            verbose_name: allocation.verbose_name.to_string(),
+            x,
+            width: allocation.rows as f32 * allocation.row_spacing,
+            rows: allocation.rows,
+            plants_per_row: allocation.plants_per_row,
+            plants,
+            positions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::FarmSize;
+    use crate::rotation::{RotationPlan, RotationRunner, RotationStep};
+    use crate::setup::{demo_record, setup_farm};
+
+    /// The demo farm with a 1 x 1 m strip next to its plots, small enough not to take a whole seed lot.
+    fn farm() -> Farm {
+        let mut farm = setup_farm(&demo_record()).unwrap();
+        let rotation = RotationPlan::new(vec![RotationStep::Fallow]).unwrap();
+        farm.plots.push(Plot::new(3, "Strip", FarmSize { width: 1, length: 1 }, rotation));
+        farm
+    }
+
+    fn sowing_date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, 1).unwrap()
+    }
+
+    fn request(plot_id: u32, crops: &[&str]) -> PlotRequest {
+        PlotRequest { plot_id, crops: crops.iter().map(|crop| crop.to_string()).collect() }
+    }
+
+    fn plants(layout: &PlantingLayout, plot_id: u32) -> u32 {
+        layout.plots.iter()
+            .find(|plot| plot.plot_id == plot_id)
+            .map_or(0, |plot| plot.beds.iter().map(|bed| bed.plants).sum())
+    }
+
+    #[test]
+    fn later_plots_get_the_seeds_left_by_earlier_ones() {
+        let mut farm = farm();
+        farm.inventory.entries.iter_mut()
+            .filter(|entry| entry.verbose_name() == "Sun Flower")
+            .for_each(|entry| entry.seeds = 4);
+        let layout = PlantingPlanner::new(&farm, sowing_date())
+            .plan(&[request(3, &["Sun Flower"]), request(1, &["Sun Flower"])])
+            .unwrap();
+
+        // The strip sows a bag of three, the last seed of the lot goes to the next plot.
+        assert_eq!(plants(&layout, 3), 3);
+        assert_eq!(plants(&layout, 1), 1);
+    }
+
+    #[test]
+    fn a_plot_fails_once_the_seeds_have_run_out() {
+        let mut farm = farm();
+        farm.inventory.entries.iter_mut()
+            .filter(|entry| entry.verbose_name() == "Pea")
+            .for_each(|entry| entry.seeds = 1);
+        let result = PlantingPlanner::new(&farm, sowing_date()).plan(&[request(1, &["Pea"]), request(2, &["Pea"])]);
+
+        assert_eq!(result, Err(PlanningError::NoSeeds("Pea".to_string())));
+    }
+
+    #[test]
+    fn the_plan_lays_out_the_seeds_the_rotation_sows() {
+        let farm = farm();
+        let requests: Vec<PlotRequest> = farm.plots.iter()
+            .filter_map(|plot| match plot.rotation.step_for(0) {
+                RotationStep::Crop(name) => Some(PlotRequest { plot_id: plot.id, crops: vec![name] }),
+                _ => None,
+            })
+            .collect();
+        let layout = PlantingPlanner::new(&farm, sowing_date()).plan(&requests).unwrap();
+
+        let runner = RotationRunner::new(farm, 1, 1).starting_on(sowing_date());
+        let demands = runner.seed_demands();
+        assert_eq!(demands.len(), requests.len());
+        for (request, demand) in requests.iter().zip(demands.iter()) {
+            assert_eq!(plants(&layout, request.plot_id), demand.seeds, "{}", demand.verbose_name);
+        }
+    }
+
+    #[test]
+    fn a_crop_requested_twice_for_a_plot_is_rejected() {
+        let farm = farm();
+        let result = PlantingPlanner::new(&farm, sowing_date()).plan(&[request(1, &["Pea", "pea"])]);
+
+        assert_eq!(result, Err(PlanningError::DuplicateCrop { crop: "pea".to_string(), plot: "North".to_string() }));
+    }
+}
@@ -1,249 +1,1125 @@
 #![allow(dead_code)]
 
-use chrono::Utc;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::sync::atomic::{AtomicBool, Ordering};
+use chrono::{NaiveDate, Utc};
 use rand::prelude::*;
-use crate::dto::{Crop, Farm, GrowthStage};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use crate::astronomy::{hargreaves_et0, SolarDay};
+use crate::calendar::{Hemisphere, SimulationCalendar};
+use crate::dto::{Crop, Farm, GrowthStage, Plot};
+use crate::economics::{CostCategory, CostTable, ManagementAction, PriceTable, QualityGrade, SQUARE_METRES_PER_HA};
+use crate::errors::AgroException;
+use crate::failure::{DailyEnvironment, FailureCause, FailureModel, PlotEnvironment};
+use crate::fertilizer::FertilizerPlan;
+use crate::germination::{sample_emergence, SeedbedConditions};
+use crate::greenhouse::Microclimate;
+use crate::growth::GrowthConditions;
+use crate::labour::{LabourScheduler, Resource, Task};
+use crate::parameters::ModelParameters;
+use crate::progress::{CropState, DayFrame};
+use crate::report::{EventKind, RunReport, SimulationEvent};
 use crate::seeds::SeedType;
-use std::time::Duration;
-use std::thread;
-use std::sync::Mutex;
-use std::collections::HashMap;
-use crate::weather_service::WeatherCondition;
+use crate::soil::AVAILABLE_WATER_CAPACITY;
+use crate::weather_service::{WeatherCondition, WeatherSeries};
 
 
+/// `println!` unless the service runs quiet.
+macro_rules! say {
+    ($service:ident) => {
+        if $service.verbose {
+            println!();
+        }
+    };
+    ($service:ident, $($arg:tt)*) => {
+        if $service.verbose {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Points of the simulated day at which observers are called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Start of the day, before the weather is drawn
+    PreWeather,
+    /// After the crew has carried out the day's actions
+    PostManagement,
+    /// End of the day, after the crops have grown
+    PostGrowth,
+}
+
+type Observer = Box<dyn FnMut(&PlantService) + Send>;
+
+/// When the crew carries out the routine actions, and which seed it sows.
+/// An interval of 0 turns the action off.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ManagementPolicy {
+    /// Days between irrigations
+    pub irrigation_interval: u32,
+    /// Days between weedings
+    pub weeding_interval: u32,
+    /// Days between fertilizer applications
+    pub fertilizing_interval: u32,
+    /// No fertilizer is applied from this day on
+    pub fertilizing_until: u32,
+    /// Day the seedlings are fumigated
+    pub fumigation_day: u32,
+    /// Crops sown from GMO (true) or conventional (false) seed, whatever their seed bag says
+    pub gmo_seed: BTreeMap<String, bool>,
+    /// Water (mm) to give on each day of the season. When given, the crew irrigates on these days
+    /// only instead of every `irrigation_interval` days and filling the soil up
+    pub irrigation_schedule: BTreeMap<u32, f32>,
+    /// Irrigation stops once this much water (m³) has been used in the season
+    pub water_budget_m3: Option<f32>,
+    /// Fertilizer applications by crop name. When given, the crops are fertilized as their plans
+    /// say, at growth stages, instead of every `fertilizing_interval` days
+    pub fertilizer_plans: BTreeMap<String, FertilizerPlan>,
+}
+
+impl Default for ManagementPolicy {
+    fn default() -> Self {
+        Self {
+            irrigation_interval: IRRIGATION_FREQUENCY,
+            weeding_interval: WEEDING_FARM_FREQUENCY,
+            fertilizing_interval: FERTILIZING_FREQUENCY,
+            fertilizing_until: FERTILIZING_UNTIL,
+            fumigation_day: FUMIGATION_TIME,
+            gmo_seed: BTreeMap::new(),
+            irrigation_schedule: BTreeMap::new(),
+            water_budget_m3: None,
+            fertilizer_plans: BTreeMap::new(),
+        }
+    }
+}
+
+impl ManagementPolicy {
+    fn is_due(interval: u32, day: u32) -> bool {
+        interval > 0 && day.is_multiple_of(interval)
+    }
+
+    fn is_irrigation_due(&self, day: u32) -> bool {
+        if self.irrigation_schedule.is_empty() {
+            day == 1 || Self::is_due(self.irrigation_interval, day)
+        } else {
+            self.irrigation_schedule.contains_key(&day)
+        }
+    }
+
+    pub fn fertilizer_plan(&self, crop_name: &str) -> Option<&FertilizerPlan> {
+        self.fertilizer_plans.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(crop_name))
+            .map(|(_, plan)| plan)
+    }
+
+    /// Water (mm) of the irrigation carried out on `day`: that of the last one planned by then,
+    /// as the crew may be running late, or as much as the soil holds without a schedule.
+    fn irrigation_depth(&self, day: u32) -> f32 {
+        if self.irrigation_schedule.is_empty() {
+            return AVAILABLE_WATER_CAPACITY;
+        }
+        self.irrigation_schedule.range(..=day).next_back().map_or(0.0, |(_, depth)| *depth)
+    }
+}
+
 pub struct PlantService {
     farm: Farm,
+    /// Days simulated so far
+    day: u32,
     is_all_harvested: bool,
     planting_is_initiated: bool,
-    harvest_stats: HashMap<String, HashMap<String, u32>>,
+    report: RunReport,
+    calendar: SimulationCalendar,
+    solar_day: Option<SolarDay>,
+    weather: WeatherCondition,
+    /// Weather shared with the other farms of the region. Days it does not cover are drawn for this farm
+    weather_series: Option<Arc<WeatherSeries>>,
+    /// Crops harvested and failed so far
+    stats: HarvestStats,
+    failure_model: FailureModel,
+    policy: ManagementPolicy,
+    parameters: ModelParameters,
+    /// Draws the weather, the emergence and the failures of the crops
+    rng: StdRng,
+    last_weeding_day: u32,
+    last_fumigation_day: Option<u32>,
+    /// Applications of the fertilizer plans carried out, by plot id and index in the plan
+    fertilized: BTreeSet<(u32, usize)>,
+    costs: CostTable,
+    prices: PriceTable,
+    scheduler: LabourScheduler,
+    /// Set on the days a harvest task is carried out
+    harvest_crew_in_field: bool,
+    /// Climate of the day inside the greenhouses, by plot id
+    microclimates: HashMap<u32, Microclimate>,
+    /// Climate of the day in the nursery, while it has seedlings
+    nursery_climate: Option<Microclimate>,
+    /// Checked every day; once set the run stops with `Cancelled`
+    cancellation: Option<Arc<AtomicBool>>,
+    /// Receives the state of the farm at the end of every day
+    progress: Option<Sender<DayFrame>>,
+    observers: Vec<(Phase, Observer)>,
+    /// Prints the progress of the run to stdout
+    verbose: bool,
 }
 
 static WEEDING_FARM_FREQUENCY: u32 = 7; // every 7 days
 static IRRIGATION_FREQUENCY: u32 = 3; // every 2 days
 const FERTILIZING_FREQUENCY: u32 = 14; // every 14 days
+const FERTILIZING_UNTIL: u32 = 40; // no fertilizer from day 40
 const DAYS_TO_WAIT_BEFORE_PLANTING: u32 = 7; // 7 days
 const FUMIGATION_TIME: u32 = 14; // 14 days after planting
 const PLANTING_WINDOW: u32 = 70; // 70 days - from planting to harvest
-static TOTAL_HARVESTED_SEEDS : Mutex<u32> = Mutex::new(0);
-static TOTAL_ROTTEN_SEEDS: Mutex<u32> = Mutex::new(0);
-const CLEAR: &str = "\x1B[2J\x1B[1;1H"; // clear the console
+const DAILY_TEMPERATURE_RANGE: f32 = 10.0; // °C between the daily minimum and maximum
 
 impl PlantService {
     pub fn new(farm: Farm) -> Self {
+        let calendar = SimulationCalendar::new(Utc::now().date_naive(), Hemisphere::from_location(&farm.location));
         Self {
             farm,
+            day: 0,
             planting_is_initiated: false,
             is_all_harvested: false,
-            harvest_stats: HashMap::new(),
+            report: RunReport::default(),
+            calendar,
+            solar_day: None,
+            weather: WeatherCondition::default(),
+            weather_series: None,
+            stats: HarvestStats::default(),
+            failure_model: FailureModel::default(),
+            policy: ManagementPolicy::default(),
+            parameters: ModelParameters::default(),
+            rng: StdRng::from_entropy(),
+            last_weeding_day: 0,
+            last_fumigation_day: None,
+            fertilized: BTreeSet::new(),
+            costs: CostTable::default(),
+            prices: PriceTable::default(),
+            scheduler: LabourScheduler::default(),
+            harvest_crew_in_field: false,
+            microclimates: HashMap::new(),
+            nursery_climate: None,
+            cancellation: None,
+            progress: None,
+            observers: Vec::new(),
+            verbose: true,
         }
     }
 
-    pub fn run(mut self) {
-        let mut days_count = 1;
-        println!("Running farm simulation for {:?}", self.farm);
+    /// Workers and equipment available to carry out the management actions.
+    pub fn with_resources(mut self, resources: Vec<Resource>) -> Self {
+        self.scheduler = LabourScheduler::new(resources);
+        self
+    }
 
-        loop {
-            println!();
-            println!("Day: {}", days_count);
-            println!("----------------------");
-            if days_count == 1 {self.irrigate()}; // irrigate before planting
-            if days_count == DAYS_TO_WAIT_BEFORE_PLANTING {self.planting()}; // plant the seeds 
-            if days_count % WEEDING_FARM_FREQUENCY == 0 {self.weed()}; // weed the farm
-            if days_count < 40 && days_count % FERTILIZING_FREQUENCY == 0 {
-                self.apply_fertilizer() // apply fertilizer
-            };
-            if days_count % IRRIGATION_FREQUENCY == 0 {self.irrigate()}; // irrigate after planting
-            if days_count == FUMIGATION_TIME {self.fumigate_seedlings()}; // fumigate seedlings
-            
-            // process crop activities
-            if self.planting_is_initiated {
-                self._crop_process(days_count);
-            }
-            days_count += 1;
-            
-            if days_count >= PLANTING_WINDOW { // harvesting can take place any time from now.
-                self.harvest();
-                self.end_farming_simulation(); // Terminate farming simulation.
-                if self.is_all_harvested {
-                    break;
-                }
+    /// Unit costs of the inputs and prices of the produce used for the profit and loss of the run.
+    pub fn with_economics(mut self, costs: CostTable, prices: PriceTable) -> Self {
+        self.costs = costs;
+        self.prices = prices;
+        self
+    }
+
+    /// Chooses how crop failures are decided. Defaults to the causal model.
+    pub fn with_failure_model(mut self, failure_model: FailureModel) -> Self {
+        self.failure_model = failure_model;
+        self
+    }
+
+    /// Replaces the default intervals of the routine actions.
+    pub fn with_policy(mut self, policy: ManagementPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Replaces built-in crop and weather parameters.
+    pub fn with_parameters(mut self, parameters: ModelParameters) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    /// Makes the random draws of the run repeatable: two runs with the same seed, farm and policy
+    /// come out the same.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Anchors day 1 of the simulation to `start_date`. Defaults to today.
+    pub fn starting_on(mut self, start_date: NaiveDate) -> Self {
+        self.calendar.start_date = start_date;
+        self
+    }
+
+    /// Takes the weather of the day from `weather_series` instead of drawing it for this farm alone.
+    pub fn with_weather(mut self, weather_series: Arc<WeatherSeries>) -> Self {
+        self.weather_series = Some(weather_series);
+        self
+    }
+
+    /// Lets another thread stop the run by setting `cancellation`.
+    pub fn with_cancellation(mut self, cancellation: Arc<AtomicBool>) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Sends a `DayFrame` to `progress` at the end of every simulated day.
+    pub fn with_progress(mut self, progress: Sender<DayFrame>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Runs without printing the progress of the days to stdout.
+    pub fn quiet(mut self) -> Self {
+        self.verbose = false;
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.as_ref().is_some_and(|cancellation| cancellation.load(Ordering::Relaxed))
+    }
+
+    /// Calls `observer` with the service at `phase` of every day.
+    pub fn observe(mut self, phase: Phase, observer: impl FnMut(&PlantService) + Send + 'static) -> Self {
+        self.observers.push((phase, Box::new(observer)));
+        self
+    }
+
+    pub fn farm(&self) -> &Farm {
+        &self.farm
+    }
+
+    pub fn report(&self) -> &RunReport {
+        &self.report
+    }
+
+    /// Last day simulated, 0 before the first step.
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    pub fn date(&self) -> NaiveDate {
+        self.calendar.date_for_day(self.day)
+    }
+
+    pub fn weather(&self) -> WeatherCondition {
+        self.weather
+    }
+
+    /// Every crop is either harvested or failed.
+    pub fn is_finished(&self) -> bool {
+        self.is_all_harvested
+    }
+
+    /// Runs the season until every crop is either harvested or failed.
+    /// Returns the farm (with the soil state of its plots) so it can be carried into the next season.
+    /// Fails with `InvalidSeasonCropError` if a crop would be sown outside of its sowing window,
+    /// and with `Cancelled` if the run is cancelled before it finishes.
+    pub fn run(mut self) -> Result<(Farm, RunReport), AgroException> {
+        self.run_until(|_| false)?;
+        Ok(self.finish())
+    }
+
+    /// Steps through the days until `stop` holds after a day or the season is over.
+    /// Returns the events of all the days simulated.
+    pub fn run_until(&mut self, mut stop: impl FnMut(&PlantService) -> bool) -> Result<Vec<SimulationEvent>, AgroException> {
+        let mut events = Vec::new();
+        while !self.is_finished() {
+            events.extend(self.step()?);
+            if stop(self) {
+                break;
             }
         }
+        Ok(events)
+    }
 
-        println!("Farm simulation completed!!!");
-        println!("Simumation Stats: {:?}", self.harvest_stats);
+    /// Steps through the days up to and including `day`, or until the season is over.
+    pub fn run_to_day(&mut self, day: u32) -> Result<Vec<SimulationEvent>, AgroException> {
+        if self.day >= day {
+            return Ok(Vec::new());
+        }
+        self.run_until(|service| service.day >= day)
     }
 
-    fn fumigate_seedlings(&self) {
-        println!("Fumigating seedlings");
-        thread::sleep(Duration::from_secs(2));
-        println!("Seedlings fumigated successfully!!");
-        println!();
+    /// Simulates the next day and returns what happened on it. Does nothing once the season is over.
+    pub fn step(&mut self) -> Result<Vec<SimulationEvent>, AgroException> {
+        if self.is_finished() {
+            return Ok(Vec::new());
+        }
+        if self.is_cancelled() {
+            return Err(AgroException::Cancelled);
+        }
+        if self.day == 0 {
+            self.start()?;
+        }
+        let days_count = self.day + 1;
+        let first_event = self.report.events.len();
+
+        let today = self.calendar.date_for_day(days_count);
+        say!(self);
+        say!(self, "Day: {} ({}, {})", days_count, today, self.calendar.climate_on(today).name());
+        say!(self, "----------------------");
+        self.notify(Phase::PreWeather);
+        self.solar_day = SolarDay::at(&self.farm.location, today).ok();
+        self.weather_today(today);
+        self.greenhouse_climate(today);
+        self.evapotranspiration(today);
+        if days_count == DAYS_TO_WAIT_BEFORE_PLANTING {self.queue(ManagementAction::Planting, days_count)}; // plant the seeds 
+        if ManagementPolicy::is_due(self.policy.weeding_interval, days_count) {self.queue(ManagementAction::Weeding, days_count)}; // weed the farm
+        if self.policy.fertilizer_plans.is_empty() {
+            if days_count < self.policy.fertilizing_until && ManagementPolicy::is_due(self.policy.fertilizing_interval, days_count) {
+                self.queue(ManagementAction::Fertilizing, days_count) // apply fertilizer
+            };
+        } else if !self.due_fertilizer().is_empty() {
+            self.queue(ManagementAction::Fertilizing, days_count) // apply fertilizer as planned for the growth stages
+        }
+        if self.policy.is_irrigation_due(days_count) {self.queue(ManagementAction::Irrigation, days_count)}; // irrigate before and after planting
+        if days_count == self.policy.fumigation_day {self.queue(ManagementAction::Fumigation, days_count)}; // fumigate seedlings
+        self.work_day(days_count, today);
+        self.notify(Phase::PostManagement);
+
+        // process crop activities
+        if self.planting_is_initiated {
+            self._crop_process(days_count);
+        }
+        self.day = days_count;
+        self.notify(Phase::PostGrowth);
+        if let Some(progress) = self.progress.as_ref() {
+            // Nobody listening any more is no reason to stop the run.
+            let _ = progress.send(self.day_frame(days_count, today));
+        }
+
+        if days_count + 1 >= PLANTING_WINDOW { // harvesting can take place any time from now.
+            self.end_farming_simulation(); // Terminate farming simulation.
+        }
+        Ok(self.report.events[first_event..].to_vec())
     }
 
-    pub fn prepare_farm(self) -> Self {
-        println!("Tilling the farm");
+    /// Hands back the farm and the report of the days simulated so far.
+    pub fn finish(mut self) -> (Farm, RunReport) {
+        self.report.days = self.day + 1;
+        self.report.labour = self.scheduler.report();
+        say!(self, "Farm simulation completed!!!");
+        say!(self, "Simumation Stats: {:?}", self.report.crops);
+        (self.farm, self.report)
+    }
 
-        let mut rng = thread_rng();
-        let mut delay = -1;
+    /// Checks the season can be run before the first day.
+    fn start(&mut self) -> Result<(), AgroException> {
+        say!(self, "Running farm simulation for {:?}", self.farm);
 
-        while delay != 0 {
-            let rand_labour = rng.gen_range(0..5); // Generate a random number between 1 and 5
-            println!("We are still preparing the farm...");
-            if rand_labour == 3 {
-                println!("This land feels really hard. But we are almost done.");
+        let sowing_date = self.calendar.date_for_day(DAYS_TO_WAIT_BEFORE_PLANTING);
+        // Crops under glass can be sown outside of their outdoor sowing window.
+        for crop in self.farm.crops.iter().filter(|crop| !self.is_under_glass(crop.plot_id)) {
+            self.calendar.check_sowing(crop, sowing_date)?;
+        }
+
+        // Radiation and day length are derived from the farm's coordinates.
+        if !self.farm.crops.is_empty() {
+            self.farm.location.coordinates()?;
+        }
+
+        for crop in self.farm.crops.iter_mut() {
+            let gmo = self.policy.gmo_seed.iter()
+                .find(|(crop_name, _)| crop_name.eq_ignore_ascii_case(&crop.verbose_name))
+                .map(|(_, gmo)| *gmo);
+            if let Some(gmo) = gmo {
+                crop.is_gmo = gmo;
+            }
+            if let Some(parameters) = self.parameters.crop(&crop.verbose_name) {
+                parameters.apply(crop);
             }
-            thread::sleep(Duration::from_secs(rand_labour as u64));
+        }
+        Ok(())
+    }
 
-            delay = rand_labour;
+    fn notify(&mut self, phase: Phase) {
+        // The observers are taken out for the call, so they can look at the whole service.
+        let mut observers = std::mem::take(&mut self.observers);
+        for (_, observer) in observers.iter_mut().filter(|(observer_phase, _)| *observer_phase == phase) {
+            observer(self);
         }
+        self.observers = observers;
+    }
 
-        println!("Farm preparation completed!!!");
-        println!();
+    fn day_frame(&self, day: u32, today: NaiveDate) -> DayFrame {
+        let mut stages = BTreeMap::new();
+        for stage in self.farm.crops.iter().filter_map(|crop| crop.current_stage.clone()) {
+            *stages.entry(stage).or_insert(0) += 1;
+        }
+        let crops = self.farm.crops.iter()
+            .filter_map(|crop| Some(CropState {
+                crop_name: crop.verbose_name.to_string(),
+                plot: self.farm.plots.iter().find(|plot| Some(plot.id) == crop.plot_id).map(|plot| plot.name.to_string()),
+                stage: crop.current_stage.clone()?,
+                in_nursery: crop.is_in_nursery(),
+            }))
+            .collect();
+        let soil_moisture = self.farm.plots.iter()
+            .map(|plot| (plot.name.to_string(), plot.soil.moisture))
+            .collect();
+        let events = self.report.events.iter()
+            .rev()
+            .take_while(|event| event.day == day)
+            .map(|event| event.kind.clone())
+            .collect::<Vec<_>>();
+        DayFrame {
+            day,
+            date: today,
+            weather: self.weather,
+            mean_temperature: self.mean_temperature(today),
+            stages,
+            crops,
+            soil_moisture,
+            events: events.into_iter().rev().collect(),
+            harvested: self.report.total_harvested(),
+            failed: self.report.total_failed(),
+        }
+    }
 
+    fn fumigate_seedlings(&mut self, day: u32) {
+        say!(self, "Fumigating seedlings");
+        self.last_fumigation_day = Some(day);
+        self.charge(ManagementAction::Fumigation);
+        for (_, crop_name, area_ha) in self.planted_plots() {
+            self.report.economics.record_cost(Some(&crop_name), CostCategory::Fumigation, self.costs.fumigation_per_ha * area_ha);
+        }
+        say!(self, "Seedlings fumigated successfully!!");
+        say!(self);
+    }
+
+    /// Queues the tillage of the plots to be sown. It is the first task the crew works on.
+    pub fn prepare_farm(mut self) -> Self {
+        self.queue(ManagementAction::Tillage, 1);
         self
     }
 
-    fn planting(&mut self) {
-        println!("We will be planting a total of {} seeds today", self.farm.crops.len());
+    fn till(&mut self) {
+        say!(self, "Tilling the farm");
+        for plot in self.farm.plots.iter_mut() {
+            if self.farm.crops.iter().any(|crop| crop.plot_id == Some(plot.id)) {
+                plot.soil.till();
+            }
+        }
+        self.charge(ManagementAction::Tillage);
+        say!(self, "Farm preparation completed!!!");
+        say!(self);
+    }
+
+    /// Queues an action, sized on the planted area and the number of crops, unless the same action is still waiting.
+    fn queue(&mut self, action: ManagementAction, day: u32) {
+        if self.scheduler.is_queued(action) {
+            return;
+        }
+        let area_ha = self.planted_plots().iter().fold(0.0, |total, (_, _, area_ha)| total + area_ha);
+        let crops = match action {
+            ManagementAction::Harvest => self.farm.crops.iter()
+                .filter(|crop| crop.current_stage == Some(GrowthStage::Harvest) && !crop.is_harvested())
+                .count(),
+            ManagementAction::Transplanting => self.farm.crops.iter()
+                .filter(|crop| Self::is_ready_to_transplant(crop))
+                .count(),
+            _ => self.farm.crops.len(),
+        };
+        let task = Task::new(action, day, area_ha, crops as u32);
+        say!(self, "Queued {:?} ({:.1} labour h, {:.1} machinery h)", task.action, task.labour_hours, task.machinery_hours);
+        self.scheduler.schedule(task);
+    }
+
+    /// Carries out the actions the crew finishes today.
+    fn work_day(&mut self, day: u32, today: NaiveDate) {
+        self.harvest_crew_in_field = false;
+        for action in self.scheduler.run_day(day) {
+            self.report.record_event(day, today, EventKind::Action(action));
+            match action {
+                ManagementAction::Tillage => self.till(),
+                ManagementAction::Planting => self.planting(today),
+                ManagementAction::Irrigation => self.irrigate(day),
+                ManagementAction::Weeding => self.weed(day),
+                ManagementAction::Fertilizing => self.apply_fertilizer(),
+                ManagementAction::Fumigation => self.fumigate_seedlings(day),
+                ManagementAction::Transplanting => self.transplant(day),
+                ManagementAction::Harvest => self.harvest_crew_in_field = true,
+            }
+        }
+    }
+
+    fn planting(&mut self, today: NaiveDate) {
+        say!(self, "We will be planting a total of {} seeds today", self.farm.crops.len());
         // Simulate planting
-        let mut rng = thread_rng();
-        let rand_labour = rng.gen_range(0..10); // Generate a random number between 1 and 5
-        println!("Planting started...");
-        thread::sleep(Duration::from_secs(rand_labour as u64));
+        say!(self, "Planting started...");
 
         self.planting_is_initiated = true;
+        self.sow_in_nursery();
+        self.charge(ManagementAction::Planting);
+        for (_, crop_name, area_ha) in self.planted_plots() {
+            self.report.economics.add_area(&crop_name, area_ha);
+        }
+        let outdoor_temperature = self.mean_temperature(today);
+        let nursery_temperature = self.farm.nursery.as_ref()
+            .map(|nursery| nursery.microclimate(outdoor_temperature, self.outdoor_radiation()).mean_temperature);
+        for crop in self.farm.crops.iter_mut() {
+            let soil_temperature = match (crop.is_in_nursery(), nursery_temperature) {
+                (true, Some(temperature)) => temperature,
+                _ => crop.plot_id
+                    .and_then(|plot_id| self.microclimates.get(&plot_id))
+                    .map_or(outdoor_temperature, |microclimate| microclimate.mean_temperature),
+            };
+            self.report.record_planted(&crop.verbose_name);
+            self.report.economics.record_cost(Some(&crop.verbose_name), CostCategory::Seed, self.costs.seed_price(&crop.verbose_name));
+            let seed_type = SeedType::from_str(&crop.verbose_name).unwrap();
+            let soil_moisture = self.farm.plots.iter()
+                .find(|plot| Some(plot.id) == crop.plot_id)
+                .map_or(1.0, |plot| plot.soil.moisture);
+            let seedbed = SeedbedConditions { soil_temperature, soil_moisture };
+            let emerged = sample_emergence(&seed_type, crop.seed_lot.as_ref(), today, &seedbed, &mut self.rng);
+            crop.emerged = Some(emerged);
+            if let Some(lot) = crop.seed_lot.as_ref() {
+                self.report.record_sowing(&lot.lot_number, &crop.verbose_name, emerged);
+            }
+        }
+
+        say!(self, "Successfully planted all seeds!!");
+        say!(self);
+    }
+
+    /// Sows the crops that are normally transplanted in the nursery, as far as it has free cells.
+    /// The rest is sown straight on their plots.
+    fn sow_in_nursery(&mut self) {
+        let mut free_cells = match self.farm.nursery.as_ref() {
+            Some(nursery) => nursery.capacity,
+            None => return,
+        };
+        for crop in self.farm.crops.iter_mut() {
+            let seed_type = SeedType::from_str(&crop.verbose_name).unwrap();
+            if seed_type.is_transplanted() && crop.plot_id.is_some() && free_cells > 0 {
+                crop.raise_in_nursery();
+                free_cells -= 1;
+            }
+        }
+        let sown = self.farm.crops.iter().filter(|crop| crop.is_in_nursery()).count();
+        if sown > 0 {
+            say!(self, "Sowing {} seeds in the nursery", sown);
+        }
+    }
+
+    fn is_ready_to_transplant(crop: &Crop) -> bool {
+        crop.is_in_nursery() && crop.current_stage == Some(GrowthStage::Seedling)
+    }
 
-        println!("Successfully planted all seeds!!");
-        println!();
+    /// Plants the seedlings out on their plots. Plots that were empty until now start counting towards the cropped area.
+    fn transplant(&mut self, day: u32) {
+        say!(self, "Transplanting seedlings");
+        let planted_before: Vec<u32> = self.planted_plots().iter().map(|(plot_id, _, _)| *plot_id).collect();
+        for crop in self.farm.crops.iter_mut().filter(|crop| Self::is_ready_to_transplant(crop)) {
+            crop.transplant();
+            let plot = self.farm.plots.iter()
+                .find(|plot| Some(plot.id) == crop.plot_id)
+                .map_or(String::new(), |plot| plot.name.to_string());
+            self.report.record_transplant(day, &crop.verbose_name, &plot, day.saturating_sub(DAYS_TO_WAIT_BEFORE_PLANTING));
+        }
+        self.charge(ManagementAction::Transplanting);
+        for (plot_id, crop_name, area_ha) in self.planted_plots() {
+            if !planted_before.contains(&plot_id) {
+                self.report.economics.add_area(&crop_name, area_ha);
+            }
+        }
+        say!(self, "Transplanting completed");
+        say!(self);
     }
 
-    fn irrigate(&self) {
+    /// Waters the plots, back to field capacity or with the depth the irrigation schedule gives for
+    /// the day. On wet days only the greenhouses need watering. Once the season's water budget is
+    /// used up the plots get what is left of it, then nothing.
+    fn irrigate(&mut self, day: u32) {
         // Simulate Irrigation
-        let weather_condition = WeatherCondition::generate_random_weather_condition();
-        if !(weather_condition == WeatherCondition::Rainy || weather_condition == WeatherCondition::Stormy) {
-            println!("Irrigation started");
-            thread::sleep(Duration::from_secs(2));
-            println!("Irrigation completed");
-            println!();
+        if !self.weather.is_wet() || !self.microclimates.is_empty() {
+            say!(self, "Irrigation started");
+            let is_wet = self.weather.is_wet();
+            let depth = self.policy.irrigation_depth(day);
+            for plot in self.farm.plots.iter_mut().filter(|plot| !is_wet || plot.greenhouse.is_some()) {
+                let area = (plot.size.width * plot.size.length) as f32;
+                let allowance = self.policy.water_budget_m3
+                    .map_or(depth, |budget| (budget - self.report.water_m3).max(0.0) * 1000.0 / area);
+                let moisture = plot.soil.moisture;
+                plot.soil.add_water(depth.min(allowance));
+                // mm of water over the plot area, in m³
+                let water = (plot.soil.moisture - moisture) * AVAILABLE_WATER_CAPACITY * area / 1000.0;
+                let crop_name = self.farm.crops.iter()
+                    .find(|crop| crop.plot_id == Some(plot.id))
+                    .map(|crop| crop.verbose_name.as_str());
+                self.report.economics.record_cost(crop_name, CostCategory::Water, water * self.costs.water_per_m3);
+                self.report.water_m3 += water;
+            }
+            self.charge(ManagementAction::Irrigation);
+            say!(self, "Irrigation completed");
+            say!(self);
         } else {
-            println!("No need to irrigate. Today's weather is {:?}", weather_condition);
+            say!(self, "No need to irrigate. Today's weather is {:?}", self.weather);
+        }
+    }
+
+    /// Draws the weather of the day. Rain refills the soil of every plot in the open.
+    fn weather_today(&mut self, today: NaiveDate) {
+        let climate = self.calendar.climate_on(today);
+        self.weather = match self.weather_series.as_ref().and_then(|series| series.on(today)) {
+            Some(weather) => weather,
+            None => WeatherCondition::generate_with(&climate, self.parameters.weather.wet_weather_factor, &mut self.rng),
+        };
+        if self.weather.is_wet() {
+            for plot in self.farm.plots.iter_mut().filter(|plot| plot.greenhouse.is_none()) {
+                plot.soil.add_water(self.parameters.weather.rainfall_mm);
+            }
         }
     }
 
-    fn apply_fertilizer(&self) {
+    fn mean_temperature(&self, today: NaiveDate) -> f32 {
+        self.calendar.climate_on(today).mean_temperature() + self.weather.temperature_offset() + self.parameters.weather.temperature_shift
+    }
+
+    fn outdoor_radiation(&self) -> f32 {
+        self.solar_day.map_or(0.0, |solar_day| solar_day.clear_sky_radiation) * self.weather.radiation_factor()
+    }
+
+    fn is_under_glass(&self, plot_id: Option<u32>) -> bool {
+        self.farm.plots.iter().any(|plot| Some(plot.id) == plot_id && plot.greenhouse.is_some())
+    }
+
+    /// Works out the climate inside every greenhouse with crops, and in the nursery while it has seedlings,
+    /// from today's weather, and pays for the heating.
+    fn greenhouse_climate(&mut self, today: NaiveDate) {
+        let outdoor_temperature = self.mean_temperature(today);
+        let outdoor_radiation = self.outdoor_radiation();
+        self.microclimates.clear();
+        for plot in self.farm.plots.iter() {
+            let crop_name = self.farm.crops.iter()
+                .find(|crop| crop.plot_id == Some(plot.id) && !crop.is_inactive())
+                .map(|crop| crop.verbose_name.as_str());
+            let greenhouse = match (plot.greenhouse.as_ref(), crop_name) {
+                (Some(greenhouse), Some(_)) => greenhouse,
+                _ => continue,
+            };
+            let microclimate = greenhouse.microclimate(outdoor_temperature, outdoor_radiation, plot.area());
+            self.report.economics.record_cost(crop_name, CostCategory::Energy, microclimate.heating_kwh * self.costs.energy_per_kwh);
+            self.report.greenhouses.entry(plot.name.to_string()).or_default().record(&microclimate);
+            self.microclimates.insert(plot.id, microclimate);
+        }
+
+        self.nursery_climate = None;
+        let nursery_crop = self.farm.crops.iter().find(|crop| crop.is_in_nursery() && !crop.is_inactive());
+        if let (Some(nursery), Some(crop)) = (self.farm.nursery.as_ref(), nursery_crop) {
+            let microclimate = nursery.microclimate(outdoor_temperature, outdoor_radiation);
+            self.report.economics.record_cost(Some(&crop.verbose_name), CostCategory::Energy, microclimate.heating_kwh * self.costs.energy_per_kwh);
+            self.report.greenhouses.entry(nursery.name.to_string()).or_default().record(&microclimate);
+            self.nursery_climate = Some(microclimate);
+        }
+    }
+
+    /// Draws down the soil water of the planted plots by the Hargreaves reference evapotranspiration.
+    fn evapotranspiration(&mut self, today: NaiveDate) {
+        let solar_day = match self.solar_day {
+            Some(solar_day) => solar_day,
+            None => return,
+        };
+        let mean_temperature = self.mean_temperature(today);
+        let et0 = hargreaves_et0(
+            mean_temperature - DAILY_TEMPERATURE_RANGE / 2.0,
+            mean_temperature + DAILY_TEMPERATURE_RANGE / 2.0,
+            solar_day.extraterrestrial_radiation,
+        );
+        for plot in self.farm.plots.iter_mut() {
+            if self.farm.crops.iter().any(|crop| crop.plot_id == Some(plot.id)) {
+                // Under glass the crop loses water to the inside climate and to the light let through.
+                let plot_et0 = match (plot.greenhouse.as_ref(), self.microclimates.get(&plot.id)) {
+                    (Some(greenhouse), Some(microclimate)) => hargreaves_et0(
+                        microclimate.min_temperature,
+                        microclimate.max_temperature,
+                        solar_day.extraterrestrial_radiation * greenhouse.light_transmission,
+                    ),
+                    _ => et0,
+                };
+                plot.soil.evaporate(plot_et0);
+            }
+        }
+        self.report.et0_mm += et0;
+    }
+
+    fn apply_fertilizer(&mut self) {
         // Simulate Fertilizer application
-        println!("Fertilizer application started");
-        thread::sleep(Duration::from_secs(2));
-        println!("Fertilizer application completed");
-        println!();
+        say!(self, "Fertilizer application started");
+        self.charge(ManagementAction::Fertilizing);
+        if self.policy.fertilizer_plans.is_empty() {
+            for (_, crop_name, area_ha) in self.planted_plots() {
+                let fertilizer = self.costs.fertilizer_rate * area_ha * self.costs.fertilizer_per_kg;
+                self.report.economics.record_cost(Some(&crop_name), CostCategory::Fertilizer, fertilizer);
+            }
+        }
+        let areas: HashMap<u32, f32> = self.planted_plots().into_iter().map(|(plot_id, _, area_ha)| (plot_id, area_ha)).collect();
+        for (plot_id, crop_name, idx) in self.due_fertilizer() {
+            let Some(application) = self.policy.fertilizer_plan(&crop_name).map(|plan| plan.applications[idx].clone()) else {
+                continue;
+            };
+            if let Some(plot) = self.farm.plots.iter_mut().find(|plot| plot.id == plot_id) {
+                application.product.apply(application.rate, &mut plot.soil);
+            }
+            let cost = application.rate * areas.get(&plot_id).copied().unwrap_or(0.0) * self.costs.product_price(application.product);
+            self.report.economics.record_cost(Some(&crop_name), CostCategory::Fertilizer, cost);
+            self.fertilized.insert((plot_id, idx));
+            say!(self, "{}: {} at {:.0} kg/ha", crop_name, application.product, application.rate);
+        }
+        say!(self, "Fertilizer application completed");
+        say!(self);
     }
 
-    fn harvest(&mut self) {
-        // Simulate Harvest
-        let mut rng = thread_rng();
-        let rand_harvest_duration = rng.gen_range(1..5); // Generate a random number between 1 and 5
-        thread::sleep(Duration::from_secs(rand_harvest_duration));
-        println!();
+    /// Applications of the fertilizer plans the crops in the field have reached the stage of,
+    /// by plot id, crop name and index in the plan.
+    fn due_fertilizer(&self) -> Vec<(u32, String, usize)> {
+        if !self.planting_is_initiated {
+            return Vec::new();
+        }
+        self.farm.crops.iter()
+            .filter(|crop| !crop.is_in_nursery())
+            .filter_map(|crop| Some((crop.plot_id?, crop, self.policy.fertilizer_plan(&crop.verbose_name)?)))
+            .flat_map(|(plot_id, crop, plan)| {
+                let stage = crop.current_stage.clone().unwrap_or_default();
+                plan.due(&stage)
+                    .map(move |(idx, _)| (plot_id, crop.verbose_name.to_string(), idx))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|(plot_id, _, idx)| !self.fertilized.contains(&(*plot_id, *idx)))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
     }
 
     fn end_farming_simulation(&mut self) {
         // Simulate Termination
-        let num_harvested = TOTAL_HARVESTED_SEEDS.lock().unwrap();
-        let num_rotten = TOTAL_ROTTEN_SEEDS.lock().unwrap();
-        if self.farm.crops.len() == (*num_rotten + *num_harvested) as usize {
-            let mut _stats = Some(HarvestStats {num_harvested: *num_harvested, num_rotten: *num_rotten});
-            // self.harvest_stats.insert("rotten".to_string(), *num_rotten);
-            // self.harvest_stats.insert("harvested".to_string(), *num_harvested );
+        if self.farm.crops.len() == (self.stats.num_rotten + self.stats.num_harvested) as usize {
             self.farm.is_ready_for_harvest = Some(true);
             self.is_all_harvested = true;
         }
     }
 
     fn _crop_process(&mut self, current_days: u32) {
+        let day_length = self.solar_day.map(|solar_day| solar_day.day_length);
+        let outdoor_radiation = self.outdoor_radiation();
+        let today = self.calendar.date_for_day(current_days);
+        let outdoor_temperature = self.mean_temperature(today);
+        let outdoors = DailyEnvironment {
+            weather: self.weather,
+            min_temperature: outdoor_temperature - DAILY_TEMPERATURE_RANGE / 2.0,
+            max_temperature: outdoor_temperature + DAILY_TEMPERATURE_RANGE / 2.0,
+            days_since_weeding: current_days - self.last_weeding_day,
+            days_since_fumigation: self.last_fumigation_day.map(|day| current_days - day),
+            sheltered: false,
+        };
+        let mut awaiting_harvest = false;
         for crop in self.farm.crops.iter_mut() {
             if crop.is_harvestable {
                 crop.grow(1);
+                let plot = self.farm.plots.iter().find(|plot| Some(plot.id) == crop.plot_id);
+                let microclimate = match crop.is_in_nursery() {
+                    true => self.nursery_climate.as_ref(),
+                    false => plot.and_then(|plot| self.microclimates.get(&plot.id)),
+                };
+                let (radiation, mean_temperature, environment) = match microclimate {
+                    Some(microclimate) => (
+                        microclimate.radiation,
+                        microclimate.mean_temperature,
+                        DailyEnvironment {
+                            min_temperature: microclimate.min_temperature,
+                            max_temperature: microclimate.max_temperature,
+                            sheltered: true,
+                            ..outdoors
+                        },
+                    ),
+                    None => (outdoor_radiation, outdoor_temperature, outdoors),
+                };
+                let conditions = PlantService::growth_conditions(crop, plot, radiation, mean_temperature);
+                crop.grow_biomass(&conditions);
+                let plot_environment = plot.map_or(PlotEnvironment::default(), |plot| PlotEnvironment {
+                    soil_moisture: plot.soil.moisture,
+                    compaction: plot.soil.compaction,
+                    rotation_factor: plot.rotation_factor(&crop.species),
+                });
+                if crop.fails_to_emerge() {
+                    crop.fail(FailureCause::GerminationFailure);
+                } else if let Some(cause) = self.failure_model.assess(crop, &environment, &plot_environment, &mut self.rng) {
+                    crop.fail(cause);
+                }
+                let stage_before = crop.current_stage.clone();
                 match crop.current_stage {
                     Some(GrowthStage::Seed) => {
                         Crop::sow(crop);
-                        PlantService::_check_for_update(crop);
+                        PlantService::_check_for_update(crop, day_length, self.verbose);
                     },
                     Some(GrowthStage::Germination) => {
-                        PlantService::_check_for_update(crop);
+                        PlantService::_check_for_update(crop, day_length, self.verbose);
                     },
                     Some(GrowthStage::Seedling) => {
-                        PlantService::_check_for_update(crop);
+                        PlantService::_check_for_update(crop, day_length, self.verbose);
                     }
                     Some(GrowthStage::Vegetative) => {
-                        PlantService::_check_for_update(crop);
+                        PlantService::_check_for_update(crop, day_length, self.verbose);
                     }
                     Some(GrowthStage::Flowering) => {
-                        PlantService::_check_for_update(crop);
+                        PlantService::_check_for_update(crop, day_length, self.verbose);
                     },
                     Some(GrowthStage::Fruiting) => {
-                        PlantService::_check_for_update(crop);
+                        PlantService::_check_for_update(crop, day_length, self.verbose);
                     },
                     Some(GrowthStage::Maturity) => {
-                        PlantService::_check_for_update(crop);
+                        PlantService::_check_for_update(crop, day_length, self.verbose);
                     },
                     Some(GrowthStage::Harvest) => {
-                        let mut num = TOTAL_HARVESTED_SEEDS.lock().unwrap();
-                        if !crop.is_harvested() {
-                            *num += 1;
-                            crop.harvest_date = Some(Utc::now().to_string());
-                            let inner_map = self.harvest_stats
-                                                                        .entry(crop.verbose_name.to_string())
-                                                                        .or_insert_with(HashMap::new);
-                            *inner_map.entry("havested".to_string())
-                                .or_insert(0) += 1;
-                            println!(
-                                "Crop: {:?} -> Harvest completed after {} days!!!",
+                        if !crop.is_harvested() && !self.harvest_crew_in_field {
+                            awaiting_harvest = true;
+                        } else if !crop.is_harvested() {
+                            self.stats.num_harvested += 1;
+                            crop.harvest_date = Some(today.to_string());
+                            // Produce left in the field once it is ready rots, splits or gets eaten.
+                            let seed_type = SeedType::from_str(&crop.verbose_name).unwrap();
+                            let days_waiting = crop.days_in_stage.unwrap_or(1).saturating_sub(1);
+                            let loss = (seed_type.field_loss_rate() * days_waiting as f32).min(1.0);
+                            let potential_yield = crop.harvest_yield(plot.map_or(1.0, |plot| plot.rotation_factor(&crop.species)));
+                            let yield_kg = potential_yield * (1.0 - loss);
+                            let biomass = crop.growth.as_ref().map_or(0.0, |growth| growth.biomass());
+                            crop.yield_kg = Some(yield_kg);
+                            self.report.record_harvest(&crop.verbose_name, yield_kg, biomass / 1000.0);
+                            self.report.record_harvest_loss(&crop.verbose_name, potential_yield - yield_kg);
+                            let grade = QualityGrade::from_yield(&seed_type, yield_kg / crop.split_size.unwrap_or(1.0));
+                            let price = self.prices.price(&crop.verbose_name, grade);
+                            self.report.record_event(current_days, today, EventKind::Harvested {
+                                crop_name: crop.verbose_name.to_string(),
+                                yield_kg,
+                            });
+                            // The produce is sold out of storage, less what spoils there.
+                            let sold_kg = match self.farm.storage.store(&crop.verbose_name, yield_kg, today, price) {
+                                Ok(lot) => {
+                                    self.report.record_storage(&crop.verbose_name, yield_kg, lot.lost_kg());
+                                    lot.sold_kg()
+                                },
+                                Err(err) => {
+                                    say!(self, "{}, sold at harvest", err);
+                                    yield_kg
+                                },
+                            };
+                            self.report.economics.record_sale(&crop.verbose_name, grade, sold_kg, price);
+                            self.report.economics.record_cost(Some(&crop.verbose_name), CostCategory::Labour, self.costs.harvest_labour(yield_kg));
+                            say!(self, 
+                                "Crop: {:?} -> Harvest completed after {} days!!! ({:.3} kg, {:.1} g dry matter)",
                                 crop.verbose_name,
-                                current_days
+                                current_days,
+                                yield_kg,
+                                biomass
                             );
                         }
                     },
-                    Some(GrowthStage::Failed) => {
-                        if !crop.has_issues() {
-                            let mut num = TOTAL_ROTTEN_SEEDS.lock().unwrap();
-                            *num += 1;
-                            crop.date_rot_detected = Some(Utc::now().to_string());
-                            println!("Crop: {:?} -> Failed after {} days!!!", crop.verbose_name, current_days);
-                            let inner_map = self.harvest_stats
-                                                                        .entry(crop.verbose_name.to_string())
-                                                                        .or_insert_with(HashMap::new);
-                            *inner_map.entry("failed".to_string())
-                                .or_insert(0) += 1;
-                        }
+                    Some(GrowthStage::Failed) if !crop.has_issues() => {
+                        self.stats.num_rotten += 1;
+                        crop.date_rot_detected = Some(today.to_string());
+                        let cause = *crop.failure_cause.get_or_insert(FailureCause::Unknown);
+                        say!(self, "Crop: {:?} -> Failed after {} days ({})!!!", crop.verbose_name, current_days, cause);
+                        self.report.record_failure(&crop.verbose_name, cause);
+                        self.report.record_event(current_days, today, EventKind::Failed {
+                            crop_name: crop.verbose_name.to_string(),
+                            cause,
+                        });
                     }
                     _ => ()
                 }
+                if let (true, Some(stage)) = (crop.current_stage != stage_before, crop.current_stage.clone()) {
+                    self.report.record_event(current_days, today, EventKind::StageChanged {
+                        crop_name: crop.verbose_name.to_string(),
+                        stage,
+                    });
+                }
             };
         }
+        if self.farm.crops.iter().any(Self::is_ready_to_transplant) {
+            self.queue(ManagementAction::Transplanting, current_days + 1);
+        }
+        if awaiting_harvest {
+            // The crew can start on the harvest tomorrow at the earliest.
+            self.queue(ManagementAction::Harvest, current_days + 1);
+        }
     }
 
-    fn _check_for_update(crop: &mut Crop) -> () {
-        let seed_type = crop.verbose_name.as_str();
+    /// Weather of the day combined with the water and nitrogen available on the crop's plot.
+    /// Crops without a plot are assumed to be well watered and fertilised.
+    fn growth_conditions(crop: &Crop, plot: Option<&Plot>, radiation: f32, mean_temperature: f32) -> GrowthConditions {
+        let (soil_moisture, nitrogen_factor) = match plot {
+            Some(plot) => {
+                let seed_type = SeedType::from_str(&crop.verbose_name).unwrap();
+                (plot.soil.moisture, plot.soil.nitrogen_factor(seed_type.nitrogen_demand()))
+            },
+            None => (1.0, 1.0),
+        };
+        GrowthConditions {
+            radiation,
+            mean_temperature,
+            soil_moisture,
+            nitrogen_factor,
+        }
+    }
+
+    /// Id, crop and area (ha) of every plot that has crops on it.
+    fn planted_plots(&self) -> Vec<(u32, String, f32)> {
+        self.farm.plots.iter()
+            .filter_map(|plot| {
+                let crop = self.farm.crops.iter().find(|crop| crop.plot_id == Some(plot.id))?;
+                let area_ha = (plot.size.width * plot.size.length) as f32 / SQUARE_METRES_PER_HA;
+                Some((plot.id, crop.verbose_name.to_string(), area_ha))
+            })
+            .collect()
+    }
+
+    /// Books the labour and machinery of an action carried out on every planted plot.
+    fn charge(&mut self, action: ManagementAction) {
+        for (_, crop_name, area_ha) in self.planted_plots() {
+            for (category, amount) in self.costs.action_costs(action, area_ha) {
+                self.report.economics.record_cost(Some(&crop_name), category, amount);
+            }
+        }
+    }
+
+    fn _check_for_update(crop: &mut Crop, day_length: Option<f32>, verbose: bool) {
         let stage = crop.current_stage.as_ref().unwrap();
-        let stage_time = stage.get_days(SeedType::from_str(seed_type).unwrap());
-        if (crop.days_in_stage.unwrap() == stage_time) && !crop.is_inactive() {
-            print!("{:?} --> {:?} stage | ", crop.verbose_name, stage);
+        let stage_time = crop.stage_days(stage);
+        if *stage == GrowthStage::Vegetative && !crop.is_flowering_induced(day_length) {
+            return; // wait for an inductive day length before flowering
+        }
+        if (crop.days_in_stage.unwrap() >= stage_time) && !crop.is_inactive() {
+            let previous_stage = stage.clone();
             crop.advance_to_next_stage();
+            if verbose {
+                println!("{:?}: {:?} -> {:?}", crop.verbose_name, previous_stage, crop.current_stage.as_ref().unwrap());
+            }
             crop.days_in_stage = Some(0);
         }
     }
 
-    fn weed(&self) {
+    fn weed(&mut self, day: u32) {
         // Simulate Weeding
-        println!("Weeding started");
-        thread::sleep(Duration::from_secs(2));
-        println!("Weeding completed");
-        println!();
+        say!(self, "Weeding started");
+        self.last_weeding_day = day;
+        self.charge(ManagementAction::Weeding);
+        say!(self, "Weeding completed");
+        say!(self);
     }
 }
 
+#[derive(Debug, Default, Clone, Copy)]
 struct HarvestStats {
     num_harvested: u32,
     num_rotten: u32
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeds::SeedBag;
+    use crate::setup::{crops_from_bag, demo_record, setup_farm};
+
+    /// Plot of the demo farm with a greenhouse.
+    const GREENHOUSE_PLOT: u32 = 2;
+
+    /// The demo farm with a single crop of `crop_name` on `plot_id`, in January.
+    fn service(crop_name: &str, plot_id: u32) -> PlantService {
+        let mut farm = setup_farm(&demo_record()).unwrap();
+        let bag = SeedBag::new(1, crop_name, crop_name.to_string()).unwrap();
+        farm.crops = crops_from_bag(&bag, 1, Some(plot_id));
+        PlantService::new(farm)
+            .quiet()
+            .with_seed(1)
+            .starting_on(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap())
+    }
+
+    fn energy_cost(service: &PlantService, crop_name: &str) -> f32 {
+        service.report.economics.crops.get(crop_name)
+            .and_then(|account| account.costs.get(&CostCategory::Energy))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    #[test]
+    fn a_greenhouse_is_heated_while_its_crop_grows() {
+        let mut service = service("Tomato", GREENHOUSE_PLOT);
+
+        service.greenhouse_climate(service.date());
+
+        assert!(energy_cost(&service, "Tomato") > 0.0);
+        assert!(service.microclimates.contains_key(&GREENHOUSE_PLOT));
+    }
+
+    #[test]
+    fn a_greenhouse_is_not_heated_for_a_failed_or_harvested_crop() {
+        for stage in [GrowthStage::Failed, GrowthStage::Harvest] {
+            let mut service = service("Tomato", GREENHOUSE_PLOT);
+            service.farm.crops[0].current_stage = Some(stage);
+
+            service.greenhouse_climate(service.date());
+
+            assert_eq!(energy_cost(&service, "Tomato"), 0.0);
+            assert!(service.microclimates.is_empty());
+        }
+    }
+
+    fn dry_out(service: &mut PlantService) {
+        for plot in service.farm.plots.iter_mut() {
+            plot.soil.moisture = 0.0;
+        }
+    }
+
+    #[test]
+    fn only_scheduled_days_are_irrigated() {
+        let policy = ManagementPolicy {
+            irrigation_schedule: BTreeMap::from([(3, 10.0), (7, 5.0)]),
+            ..ManagementPolicy::default()
+        };
+
+        let due: Vec<u32> = (1..=10).filter(|day| policy.is_irrigation_due(*day)).collect();
+        assert_eq!(due, vec![3, 7]);
+        // A crew running late gives the depth of the last irrigation planned.
+        assert_eq!([2, 3, 5, 7, 9].map(|day| policy.irrigation_depth(day)), [0.0, 10.0, 10.0, 5.0, 5.0]);
+    }
+
+    #[test]
+    fn a_scheduled_irrigation_gives_its_depth() {
+        let policy = ManagementPolicy {
+            irrigation_schedule: BTreeMap::from([(1, 10.0)]),
+            ..ManagementPolicy::default()
+        };
+        let mut service = service("Tomato", GREENHOUSE_PLOT).with_policy(policy);
+        dry_out(&mut service);
+
+        service.irrigate(1);
+
+        for plot in service.farm.plots.iter() {
+            assert!((plot.soil.moisture - 10.0 / AVAILABLE_WATER_CAPACITY).abs() < 1e-6);
+        }
+        // 10 mm over the two 50 m² plots.
+        assert!((service.report.water_m3 - 1.0).abs() < 1e-4, "used {} m³", service.report.water_m3);
+    }
+
+    #[test]
+    fn irrigation_stops_once_the_water_budget_is_used_up() {
+        let policy = ManagementPolicy { water_budget_m3: Some(1.5), ..ManagementPolicy::default() };
+        let mut service = service("Tomato", GREENHOUSE_PLOT).with_policy(policy);
+
+        let mut used = Vec::new();
+        for day in 1..=5 {
+            dry_out(&mut service);
+            service.irrigate(day);
+            used.push(service.report.water_m3);
+        }
+
+        assert!((used[4] - 1.5).abs() < 1e-4, "used {:?} m³", used);
+        assert!(used.windows(2).all(|pair| pair[1] >= pair[0]));
+        // The last irrigations find the budget spent and give nothing.
+        assert_eq!(used[3], used[4]);
+    }
+}
@@ -0,0 +1,81 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::dto::{Farm, GrowthStage};
+use crate::errors::AgroException;
+use crate::plant_service::PlantService;
+use crate::report::{EventKind, RunReport};
+use crate::weather_service::WeatherCondition;
+
+
+/// Growth stage of one crop, and the plot it grows on.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CropState {
+    pub crop_name: String,
+    /// Name of the plot, if the crop has one
+    pub plot: Option<String>,
+    pub stage: GrowthStage,
+    pub in_nursery: bool,
+}
+
+/// State of the farm at the end of a simulated day, sent to whoever follows the run.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DayFrame {
+    pub day: u32,
+    pub date: NaiveDate,
+    pub weather: WeatherCondition,
+    /// Outdoor mean temperature (°C)
+    pub mean_temperature: f32,
+    /// Number of crops in every growth stage
+    pub stages: BTreeMap<GrowthStage, u32>,
+    pub crops: Vec<CropState>,
+    /// Soil moisture (fraction of field capacity) by plot name
+    pub soil_moisture: BTreeMap<String, f32>,
+    /// Actions carried out and what happened to the crops during the day
+    pub events: Vec<EventKind>,
+    /// Crops harvested and failed since the start of the run
+    pub harvested: u32,
+    pub failed: u32,
+}
+
+/// A run carried out on its own thread, handing out a frame for every day as soon as it is simulated.
+///
+/// ```ignore
+/// let mut stream = SimulationStream::spawn(PlantService::new(farm).prepare_farm());
+/// for frame in stream.by_ref() {
+///     println!("{}: {} harvested", frame.date, frame.harvested);
+/// }
+/// let (farm, report) = stream.finish()?;
+/// ```
+pub struct SimulationStream {
+    frames: Receiver<DayFrame>,
+    handle: JoinHandle<Result<(Farm, RunReport), AgroException>>,
+}
+
+impl SimulationStream {
+    pub fn spawn(service: PlantService) -> Self {
+        let (sender, frames) = mpsc::channel();
+        let handle = thread::spawn(move || service.with_progress(sender).run());
+        Self { frames, handle }
+    }
+
+    /// Waits for the end of the run. Frames that were not read are dropped.
+    pub fn finish(self) -> Result<(Farm, RunReport), AgroException> {
+        drop(self.frames);
+        self.handle.join().unwrap_or_else(|_| Err(AgroException::ServerError("the simulation panicked".to_string())))
+    }
+}
+
+impl Iterator for SimulationStream {
+    type Item = DayFrame;
+
+    /// The next day of the run, or `None` once the run is over.
+    fn next(&mut self) -> Option<DayFrame> {
+        self.frames.recv().ok()
+    }
+}
@@ -0,0 +1,172 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use rand::prelude::*;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::calendar::Hemisphere;
+use crate::economics::ProfitAndLoss;
+use crate::report::{CropSummary, RunReport};
+use crate::rotation::{RotationReport, RotationRunner};
+use crate::weather_service::WeatherSeries;
+
+
+/// Outcome of the rotation of one farm of the region.
+#[derive(Serialize, Debug)]
+pub struct FarmOutcome {
+    pub farm_id: u32,
+    /// Missing if the rotation could not be carried out
+    pub report: Option<RotationReport>,
+    pub error: Option<String>,
+}
+
+impl FarmOutcome {
+    fn seasons(&self) -> impl Iterator<Item = &RunReport> {
+        self.report.iter().flat_map(|report| report.seasons.iter())
+    }
+
+    pub fn harvested(&self) -> u32 {
+        self.seasons().map(|season| season.total_harvested()).sum()
+    }
+
+    pub fn failed(&self) -> u32 {
+        self.seasons().map(|season| season.total_failed()).sum()
+    }
+
+    pub fn yield_kg(&self) -> f32 {
+        self.seasons().fold(0.0, |total, season| total + season.total_yield())
+    }
+
+    pub fn profit(&self) -> f32 {
+        self.seasons().fold(0.0, |total, season| total + season.economics.profit())
+    }
+}
+
+/// Results of all the farms of a region, with the totals over every farm and season.
+#[derive(Serialize, Debug, Default)]
+pub struct RegionalReport {
+    pub farms: Vec<FarmOutcome>,
+    /// Every crop of every farm and season
+    pub crops: BTreeMap<String, CropSummary>,
+    pub economics: ProfitAndLoss,
+    /// Whether all the farms were run under the same weather
+    pub shared_weather: bool,
+}
+
+impl RegionalReport {
+    fn add(&mut self, outcome: FarmOutcome) {
+        for season in outcome.seasons() {
+            for (crop_name, summary) in season.crops.iter() {
+                self.crops.entry(crop_name.to_string()).or_default().merge(summary);
+            }
+            self.economics.merge(&season.economics);
+        }
+        self.farms.push(outcome);
+    }
+
+    pub fn total_yield(&self) -> f32 {
+        self.crops.values().fold(0.0, |total, summary| total + summary.yield_kg)
+    }
+}
+
+impl fmt::Display for RegionalReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Region: {} farms{}", self.farms.len(), if self.shared_weather { ", shared weather" } else { "" })?;
+        for outcome in self.farms.iter() {
+            match outcome.error.as_ref() {
+                Some(err) => writeln!(f, "  Farm {:>3}: {}", outcome.farm_id, err)?,
+                None => writeln!(
+                    f,
+                    "  Farm {:>3}: harvested: {:>3} failed: {:>3} yield: {:>8.3} kg profit: {:>9.2}",
+                    outcome.farm_id,
+                    outcome.harvested(),
+                    outcome.failed(),
+                    outcome.yield_kg(),
+                    outcome.profit(),
+                )?,
+            }
+        }
+        writeln!(f, "Crops over all farms:")?;
+        for (crop_name, summary) in self.crops.iter() {
+            writeln!(
+                f,
+                "  {:<10} planted: {:>4} harvested: {:>4} failed: {:>4} yield: {:>8.3} kg",
+                crop_name,
+                summary.planted,
+                summary.harvested,
+                summary.failed,
+                summary.yield_kg,
+            )?;
+        }
+        writeln!(f, "Regional profit and loss:")?;
+        write!(f, "{}", self.economics)
+    }
+}
+
+/// Runs the rotations of many farms at the same time, one per rayon worker,
+/// and adds up their results.
+///
+/// With `with_shared_weather` every farm sees the same day-to-day weather, drawn once for the region.
+pub struct RegionRunner {
+    runners: Vec<RotationRunner>,
+    weather_series: Option<Arc<WeatherSeries>>,
+}
+
+impl RegionRunner {
+    pub fn new(runners: Vec<RotationRunner>) -> Self {
+        Self {
+            runners,
+            weather_series: None,
+        }
+    }
+
+    /// Runs every farm under `weather_series`.
+    pub fn with_weather(mut self, weather_series: WeatherSeries) -> Self {
+        self.weather_series = Some(Arc::new(weather_series));
+        self
+    }
+
+    /// Draws one weather series spanning the rotations of all the farms, for the hemisphere of the first one.
+    pub fn with_shared_weather(self) -> Self {
+        let first_date = self.runners.iter().map(|runner| runner.start_date()).min();
+        let last_date = self.runners.iter().map(|runner| runner.end_date()).max();
+        match (first_date, last_date, self.runners.first()) {
+            (Some(first_date), Some(last_date), Some(runner)) => {
+                let hemisphere = Hemisphere::from_location(&runner.farm().location);
+                let weather_series = WeatherSeries::generate(first_date, last_date, hemisphere, &mut thread_rng());
+                self.with_weather(weather_series)
+            },
+            _ => self,
+        }
+    }
+
+    /// A farm that fails does not stop the others; its error is kept in its `FarmOutcome`.
+    pub fn run(self) -> RegionalReport {
+        let weather_series = self.weather_series;
+        let outcomes: Vec<FarmOutcome> = self.runners.into_par_iter()
+            .map(|runner| {
+                let farm_id = runner.farm().id;
+                let runner = match weather_series.as_ref() {
+                    Some(weather_series) => runner.with_weather(weather_series.clone()),
+                    None => runner,
+                };
+                match runner.run() {
+                    Ok(report) => FarmOutcome { farm_id, report: Some(report), error: None },
+                    Err(err) => FarmOutcome { farm_id, report: None, error: Some(err.to_string()) },
+                }
+            })
+            .collect();
+
+        let mut report = RegionalReport {
+            shared_weather: weather_series.is_some(),
+            ..RegionalReport::default()
+        };
+        for outcome in outcomes {
+            report.add(outcome);
+        }
+        report
+    }
+}
@@ -0,0 +1,370 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::access::{Credentials, Operation, Role, SecurityCode};
+use crate::dto::{FarmSize, Location, UserInfo};
+use crate::errors::AgroException;
+use crate::file_ops::lock_file;
+use crate::os::LockMode;
+
+
+/// Details of a farm to be registered. The security code is given in plain text and only its hash is kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewFarm {
+    pub owner: UserInfo,
+    pub location: Location,
+    pub size: FarmSize,
+    pub security_code: String,
+}
+
+/// A registered farm, as kept in the registry file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FarmRecord {
+    pub id: u32,
+    pub owner: UserInfo,
+    pub location: Location,
+    pub size: FarmSize,
+    pub security_code: SecurityCode,
+    /// Role of every user with access to the farm, by lower case email. The owner is always a member
+    pub members: BTreeMap<String, Role>,
+    pub is_active: bool,
+    pub is_trackable: Option<bool>,
+}
+
+impl FarmRecord {
+    /// Checks the security code and the role of the user for `operation`.
+    /// A wrong code or a user who is not a member is `Unauthorized`, a role that does not allow the operation is `PermissionDenied`.
+    pub fn authorize(&self, credentials: &Credentials, operation: Operation) -> Result<Role, AgroException> {
+        if !self.security_code.verify(&credentials.security_code) {
+            return Err(AgroException::Unauthorized(format!("wrong security code for farm {}", self.id)));
+        }
+        let role = *self.members.get(&credentials.email.to_lowercase())
+            .ok_or(AgroException::Unauthorized(format!("{} is not a member of farm {}", credentials.email, self.id)))?;
+        if !role.allows(operation) {
+            return Err(AgroException::PermissionDenied);
+        }
+        Ok(role)
+    }
+
+    fn check_code(code: &str) -> Result<(), AgroException> {
+        if code.trim().is_empty() {
+            return Err(AgroException::InvalidFarmError("the security code is empty".to_string()));
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), AgroException> {
+        let invalid = |reason: &str| Err(AgroException::InvalidFarmError(format!("farm {}: {}", self.id, reason)));
+        if self.owner.first_name.trim().is_empty() || self.owner.last_name.trim().is_empty() {
+            return invalid("the owner needs a first and last name");
+        }
+        if !self.owner.email.contains('@') {
+            return invalid(&format!("{} is not an email address", self.owner.email));
+        }
+        if self.size.width == 0 || self.size.length == 0 {
+            return invalid("the farm has no area");
+        }
+        if self.location.latitude.is_some_and(|latitude| !(-90.0..=90.0).contains(&latitude))
+            || self.location.longitude.is_some_and(|longitude| !(-180.0..=180.0).contains(&longitude))
+        {
+            return invalid("the coordinates are out of range");
+        }
+        Ok(())
+    }
+}
+
+/// Changes to a registered farm. Fields left to `None` are kept as they are.
+/// A new owner takes over the owner role from the previous one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FarmUpdate {
+    pub owner: Option<UserInfo>,
+    pub location: Option<Location>,
+    pub size: Option<FarmSize>,
+    pub security_code: Option<String>,
+    pub is_trackable: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct RegistryData {
+    next_id: u32,
+    farms: BTreeMap<u32, FarmRecord>,
+}
+
+/// Farms registered on this machine, kept in a JSON file.
+/// Reads share a lock on `<file>.lock` and updates hold it exclusively, waiting for each other,
+/// so two processes cannot update the registry at the same time.
+#[derive(Debug, Clone)]
+pub struct FarmRegistry {
+    path: PathBuf,
+}
+
+impl FarmRegistry {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self { path: path.as_ref().to_path_buf() }
+    }
+
+    /// Registers a farm, with its owner as the only member.
+    pub fn create(&self, farm: NewFarm) -> Result<FarmRecord, AgroException> {
+        FarmRecord::check_code(&farm.security_code)?;
+        self.update_data(|data| {
+            data.next_id += 1;
+            let members = BTreeMap::from([(farm.owner.email.to_lowercase(), Role::Owner)]);
+            let record = FarmRecord {
+                id: data.next_id,
+                owner: farm.owner,
+                location: farm.location,
+                size: farm.size,
+                security_code: SecurityCode::new(&farm.security_code),
+                members,
+                is_active: true,
+                is_trackable: None,
+            };
+            record.validate()?;
+            data.farms.insert(record.id, record.clone());
+            Ok(record)
+        })
+    }
+
+    pub fn get(&self, id: u32) -> Result<FarmRecord, AgroException> {
+        self.read_data()?.farms.remove(&id).ok_or(AgroException::FarmNotFound)
+    }
+
+    /// Every registered farm, deactivated ones included, by id.
+    pub fn list(&self) -> Result<Vec<FarmRecord>, AgroException> {
+        Ok(self.read_data()?.farms.into_values().collect())
+    }
+
+    pub fn list_active(&self) -> Result<Vec<FarmRecord>, AgroException> {
+        Ok(self.list()?.into_iter().filter(|record| record.is_active).collect())
+    }
+
+    /// Returns the farm if the user may carry out `operation` on it.
+    pub fn authorize(&self, id: u32, credentials: &Credentials, operation: Operation) -> Result<FarmRecord, AgroException> {
+        let record = self.get(id)?;
+        record.authorize(credentials, operation)?;
+        Ok(record)
+    }
+
+    pub fn update(&self, id: u32, credentials: &Credentials, update: FarmUpdate) -> Result<FarmRecord, AgroException> {
+        if let Some(security_code) = update.security_code.as_ref() {
+            FarmRecord::check_code(security_code)?;
+        }
+        self.update_data(|data| {
+            let record = data.farms.get_mut(&id).ok_or(AgroException::FarmNotFound)?;
+            record.authorize(credentials, Operation::ManageFarm)?;
+            if !record.is_active {
+                return Err(AgroException::InvalidFarmError(format!("farm {} is deactivated", id)));
+            }
+            let mut updated = record.clone();
+            if let Some(owner) = update.owner {
+                updated.members.remove(&updated.owner.email.to_lowercase());
+                updated.members.insert(owner.email.to_lowercase(), Role::Owner);
+                updated.owner = owner;
+            }
+            if let Some(location) = update.location {
+                updated.location = location;
+            }
+            if let Some(size) = update.size {
+                updated.size = size;
+            }
+            if let Some(security_code) = update.security_code {
+                updated.security_code = SecurityCode::new(&security_code);
+            }
+            if update.is_trackable.is_some() {
+                updated.is_trackable = update.is_trackable;
+            }
+            updated.validate()?;
+            *record = updated.clone();
+            Ok(updated)
+        })
+    }
+
+    /// Deactivated farms stay in the registry but can no longer be updated or simulated.
+    pub fn deactivate(&self, id: u32, credentials: &Credentials) -> Result<FarmRecord, AgroException> {
+        self.update_data(|data| {
+            let record = data.farms.get_mut(&id).ok_or(AgroException::FarmNotFound)?;
+            record.authorize(credentials, Operation::ManageFarm)?;
+            if !record.is_active {
+                return Err(AgroException::InvalidFarmError(format!("farm {} is already deactivated", id)));
+            }
+            record.is_active = false;
+            Ok(record.clone())
+        })
+    }
+
+    /// Gives `email` access to the farm with `role`, or changes the role of an existing member.
+    pub fn add_member(&self, id: u32, credentials: &Credentials, email: &str, role: Role) -> Result<FarmRecord, AgroException> {
+        self.update_data(|data| {
+            let record = data.farms.get_mut(&id).ok_or(AgroException::FarmNotFound)?;
+            record.authorize(credentials, Operation::ManageFarm)?;
+            if !email.contains('@') {
+                return Err(AgroException::InvalidFarmError(format!("{} is not an email address", email)));
+            }
+            if email.eq_ignore_ascii_case(&record.owner.email) {
+                return Err(AgroException::InvalidFarmError(format!("{} owns farm {}", email, id)));
+            }
+            record.members.insert(email.to_lowercase(), role);
+            Ok(record.clone())
+        })
+    }
+
+    pub fn remove_member(&self, id: u32, credentials: &Credentials, email: &str) -> Result<FarmRecord, AgroException> {
+        self.update_data(|data| {
+            let record = data.farms.get_mut(&id).ok_or(AgroException::FarmNotFound)?;
+            record.authorize(credentials, Operation::ManageFarm)?;
+            if email.eq_ignore_ascii_case(&record.owner.email) {
+                return Err(AgroException::InvalidFarmError(format!("the owner of farm {} cannot be removed", id)));
+            }
+            record.members.remove(&email.to_lowercase())
+                .ok_or(AgroException::Unauthorized(format!("{} is not a member of farm {}", email, id)))?;
+            Ok(record.clone())
+        })
+    }
+
+    fn read_data(&self) -> Result<RegistryData, AgroException> {
+        let _lock = lock_file(self.lock_path(), LockMode::Shared).map_err(|err| self.unavailable(err))?;
+        self.load()
+    }
+
+    /// Applies `change` to the registry and writes it back, without letting go of the lock in between.
+    /// Nothing is written if `change` fails. The new registry is written to a temporary file that then
+    /// replaces the old one, so a crash halfway leaves the old registry whole.
+    fn update_data<T>(&self, change: impl FnOnce(&mut RegistryData) -> Result<T, AgroException>) -> Result<T, AgroException> {
+        let _lock = lock_file(self.lock_path(), LockMode::Exclusive).map_err(|err| self.unavailable(err))?;
+        let mut data = self.load()?;
+        let result = change(&mut data)?;
+
+        let json = serde_json::to_string_pretty(&data).map_err(|err| AgroException::RegistryUnavailable(err.to_string()))?;
+        let temporary = self.sibling_path("tmp");
+        let written = File::create(&temporary)
+            .and_then(|mut temporary| {
+                temporary.write_all(json.as_bytes())?;
+                temporary.sync_all()
+            })
+            .and_then(|_| fs::rename(&temporary, &self.path));
+        if let Err(err) = written {
+            let _ = fs::remove_file(&temporary);
+            return Err(self.unavailable(err));
+        }
+        Ok(result)
+    }
+
+    /// The lock is taken on a file of its own, as the registry file is replaced on every update.
+    fn lock_path(&self) -> PathBuf {
+        self.sibling_path("lock")
+    }
+
+    /// `<registry>.<extension>`, next to the registry so a rename stays on the same file system.
+    fn sibling_path(&self, extension: &str) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".");
+        path.push(extension);
+        PathBuf::from(path)
+    }
+
+    /// Reads the registry, empty if it has not been written yet. The caller holds the lock.
+    fn load(&self) -> Result<RegistryData, AgroException> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(self.unavailable(err)),
+        };
+        Self::parse(&content)
+    }
+
+    fn parse(content: &str) -> Result<RegistryData, AgroException> {
+        if content.trim().is_empty() {
+            return Ok(RegistryData::default());
+        }
+        serde_json::from_str(content).map_err(|err| AgroException::RegistryUnavailable(format!("corrupt registry: {}", err)))
+    }
+
+    fn unavailable(&self, err: std::io::Error) -> AgroException {
+        AgroException::RegistryUnavailable(format!("{}: {}", self.path.display(), err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use super::*;
+    use crate::setup::{demo_credentials, demo_farm};
+
+    /// A registry of its own in the temporary directory, removed with its lock file when dropped.
+    struct TemporaryRegistry(FarmRegistry);
+
+    impl TemporaryRegistry {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("agro_mini_{}_{}.json", name, std::process::id()));
+            let registry = TemporaryRegistry(FarmRegistry::new(path));
+            registry.remove_files();
+            registry
+        }
+
+        fn remove_files(&self) {
+            for path in [self.0.path.clone(), self.0.lock_path(), self.0.sibling_path("tmp")] {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    impl Drop for TemporaryRegistry {
+        fn drop(&mut self) {
+            self.remove_files();
+        }
+    }
+
+    #[test]
+    fn concurrent_updates_all_survive() {
+        let registry = TemporaryRegistry::new("concurrent");
+        let id = registry.0.create(demo_farm()).unwrap().id;
+        let writers = 8;
+        let barrier = Arc::new(Barrier::new(writers));
+
+        let handles: Vec<_> = (0..writers)
+            .map(|writer| {
+                let (registry, barrier) = (registry.0.clone(), barrier.clone());
+                thread::spawn(move || {
+                    barrier.wait();
+                    let email = format!("member{}@example.com", writer);
+                    registry.add_member(id, &demo_credentials(), &email, Role::Viewer).unwrap();
+                    registry.create(demo_farm()).unwrap();
+                    registry.list().unwrap();
+                })
+            })
+            .collect();
+        handles.into_iter().for_each(|handle| handle.join().unwrap());
+
+        let record = registry.0.get(id).unwrap();
+        for writer in 0..writers {
+            assert_eq!(record.members.get(&format!("member{}@example.com", writer)), Some(&Role::Viewer));
+        }
+        let ids: Vec<u32> = registry.0.list().unwrap().iter().map(|record| record.id).collect();
+        assert_eq!(ids, (1..=writers as u32 + 1).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn a_failed_update_leaves_the_registry_unchanged() {
+        let registry = TemporaryRegistry::new("failed_update");
+        let record = registry.0.create(demo_farm()).unwrap();
+
+        let err = registry.0.add_member(record.id, &demo_credentials(), "not an email", Role::Viewer).unwrap_err();
+
+        assert!(matches!(err, AgroException::InvalidFarmError(_)));
+        assert_eq!(registry.0.get(record.id).unwrap(), record);
+    }
+
+    #[test]
+    fn an_empty_registry_has_no_farms() {
+        let registry = TemporaryRegistry::new("empty");
+
+        assert!(registry.0.list().unwrap().is_empty());
+        assert!(matches!(registry.0.get(1), Err(AgroException::FarmNotFound)));
+    }
+}
@@ -0,0 +1,184 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::dto::GrowthStage;
+use crate::economics::{ManagementAction, ProfitAndLoss};
+use crate::failure::FailureCause;
+use crate::greenhouse::GreenhouseSummary;
+use crate::labour::ScheduleReport;
+
+
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct CropSummary {
+    pub planted: u32,
+    pub harvested: u32,
+    pub failed: u32,
+    pub yield_kg: f32,
+    /// Total dry matter of the harvested plants
+    pub biomass_kg: f32,
+    /// Yield lost while the ripe crop waited for the harvest crew
+    pub lost_kg: f32,
+    /// Harvest put into storage and the part of it that spoiled before it was sold
+    pub stored_kg: f32,
+    pub storage_loss_kg: f32,
+    pub failure_causes: BTreeMap<FailureCause, u32>,
+}
+
+impl CropSummary {
+    /// Adds the counts and quantities of `other`.
+    pub fn merge(&mut self, other: &CropSummary) {
+        self.planted += other.planted;
+        self.harvested += other.harvested;
+        self.failed += other.failed;
+        self.yield_kg += other.yield_kg;
+        self.biomass_kg += other.biomass_kg;
+        self.lost_kg += other.lost_kg;
+        self.stored_kg += other.stored_kg;
+        self.storage_loss_kg += other.storage_loss_kg;
+        for (cause, count) in other.failure_causes.iter() {
+            *self.failure_causes.entry(*cause).or_insert(0) += count;
+        }
+    }
+}
+
+/// A seedling planted out from the nursery.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TransplantEvent {
+    pub day: u32,
+    pub crop_name: String,
+    pub plot: String,
+    pub days_in_nursery: u32,
+}
+
+/// Emergence of the seeds sown from one seed bag, identified by its lot number.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct EmergenceSummary {
+    pub crop_name: String,
+    pub sown: u32,
+    pub emerged: u32,
+}
+
+impl EmergenceSummary {
+    pub fn percentage(&self) -> f32 {
+        if self.sown == 0 {
+            return 0.0;
+        }
+        self.emerged as f32 / self.sown as f32 * 100.0
+    }
+}
+
+/// Something that happened to the farm or one of its crops during a run.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum EventKind {
+    /// A management action the crew finished
+    Action(ManagementAction),
+    StageChanged { crop_name: String, stage: GrowthStage },
+    Harvested { crop_name: String, yield_kg: f32 },
+    Failed { crop_name: String, cause: FailureCause },
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EventKind::Action(action) => write!(f, "{:?} completed", action),
+            EventKind::StageChanged { crop_name, stage } => write!(f, "{} reached {:?}", crop_name, stage),
+            EventKind::Harvested { crop_name, yield_kg } => write!(f, "{} harvested ({:.3} kg)", crop_name, yield_kg),
+            EventKind::Failed { crop_name, cause } => write!(f, "{} failed ({})", crop_name, cause),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SimulationEvent {
+    pub day: u32,
+    pub date: NaiveDate,
+    pub kind: EventKind,
+}
+
+/// Summary of a single `PlantService` run, grouped by crop.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct RunReport {
+    pub days: u32,
+    /// Reference evapotranspiration over the run (mm)
+    pub et0_mm: f32,
+    /// Irrigation water applied (m³)
+    pub water_m3: f32,
+    pub crops: BTreeMap<String, CropSummary>,
+    pub emergence: BTreeMap<String, EmergenceSummary>,
+    pub economics: ProfitAndLoss,
+    pub labour: ScheduleReport,
+    /// Energy use and climate control of the greenhouses, by plot name
+    pub greenhouses: BTreeMap<String, GreenhouseSummary>,
+    pub transplants: Vec<TransplantEvent>,
+    /// Everything that happened during the run, in order
+    pub events: Vec<SimulationEvent>,
+}
+
+impl RunReport {
+    pub fn record_planted(&mut self, crop_name: &str) {
+        self.crops.entry(crop_name.to_string()).or_default().planted += 1;
+    }
+
+    pub fn record_sowing(&mut self, lot_number: &str, crop_name: &str, emerged: bool) {
+        let summary = self.emergence.entry(lot_number.to_string()).or_insert_with(|| EmergenceSummary {
+            crop_name: crop_name.to_string(),
+            ..Default::default()
+        });
+        summary.sown += 1;
+        if emerged {
+            summary.emerged += 1;
+        }
+    }
+
+    pub fn record_harvest(&mut self, crop_name: &str, yield_kg: f32, biomass_kg: f32) {
+        let summary = self.crops.entry(crop_name.to_string()).or_default();
+        summary.harvested += 1;
+        summary.yield_kg += yield_kg;
+        summary.biomass_kg += biomass_kg;
+    }
+
+    pub fn record_harvest_loss(&mut self, crop_name: &str, lost_kg: f32) {
+        self.crops.entry(crop_name.to_string()).or_default().lost_kg += lost_kg;
+    }
+
+    pub fn record_storage(&mut self, crop_name: &str, stored_kg: f32, lost_kg: f32) {
+        let summary = self.crops.entry(crop_name.to_string()).or_default();
+        summary.stored_kg += stored_kg;
+        summary.storage_loss_kg += lost_kg;
+    }
+
+    pub fn record_transplant(&mut self, day: u32, crop_name: &str, plot: &str, days_in_nursery: u32) {
+        self.transplants.push(TransplantEvent {
+            day,
+            crop_name: crop_name.to_string(),
+            plot: plot.to_string(),
+            days_in_nursery,
+        });
+    }
+
+    pub fn record_event(&mut self, day: u32, date: NaiveDate, kind: EventKind) {
+        self.events.push(SimulationEvent { day, date, kind });
+    }
+
+    pub fn record_failure(&mut self, crop_name: &str, cause: FailureCause) {
+        let summary = self.crops.entry(crop_name.to_string()).or_default();
+        summary.failed += 1;
+        *summary.failure_causes.entry(cause).or_insert(0) += 1;
+    }
+
+    pub fn total_harvested(&self) -> u32 {
+        self.crops.values().map(|summary| summary.harvested).sum()
+    }
+
+    pub fn total_failed(&self) -> u32 {
+        self.crops.values().map(|summary| summary.failed).sum()
+    }
+
+    pub fn total_yield(&self) -> f32 {
+        self.crops.values().fold(0.0, |total, summary| total + summary.yield_kg)
+    }
+}
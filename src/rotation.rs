@@ -0,0 +1,480 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::sync::atomic::AtomicBool;
+use chrono::{Months, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::dto::{Crop, Farm};
+use crate::economics::{CostTable, PriceTable};
+use crate::errors::AgroException;
+use crate::failure::{FailureCause, FailureModel};
+use crate::inventory::SeedDemand;
+use crate::parameters::ModelParameters;
+use crate::plant_service::{ManagementPolicy, PlantService};
+use crate::progress::DayFrame;
+use crate::report::RunReport;
+use crate::seeds::SeedType;
+use crate::soil::SoilState;
+use crate::weather_service::WeatherSeries;
+
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum CoverCrop {
+    Clover,
+    Vetch,
+    Rye,
+}
+
+impl CoverCrop {
+    /// Nitrogen (kg/ha) released when the cover crop is incorporated.
+    pub fn nitrogen_credit(&self) -> f32 {
+        match self {
+            CoverCrop::Clover => 50.0,
+            CoverCrop::Vetch => 70.0,
+            CoverCrop::Rye => 10.0,
+        }
+    }
+
+    /// Organic matter (percentage points) added to the soil.
+    pub fn organic_matter_credit(&self) -> f32 {
+        match self {
+            CoverCrop::Clover => 0.1,
+            CoverCrop::Vetch => 0.1,
+            CoverCrop::Rye => 0.3,
+        }
+    }
+}
+
+/// What a plot is used for during one season.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum RotationStep {
+    /// A cash crop, identified by its verbose name e.g. "Pea"
+    Crop(String),
+    CoverCrop(CoverCrop),
+    Fallow,
+}
+
+impl fmt::Display for RotationStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RotationStep::Crop(name) => write!(f, "{}", name),
+            RotationStep::CoverCrop(cover_crop) => write!(f, "{:?} (cover crop)", cover_crop),
+            RotationStep::Fallow => write!(f, "Fallow"),
+        }
+    }
+}
+
+/// Ordered list of steps that repeats for as many seasons as the simulation runs.
+/// E.g. Pea -> Broccoli -> Fallow -> Pea -> ...
+#[derive(Debug, Clone, Default)]
+pub struct RotationPlan {
+    steps: Vec<RotationStep>,
+}
+
+impl RotationPlan {
+    pub fn new(steps: Vec<RotationStep>) -> Result<Self, String> {
+        for step in steps.iter() {
+            if let RotationStep::Crop(name) = step {
+                SeedType::from_str(name)?;
+            }
+        }
+        Ok(Self { steps })
+    }
+
+    pub fn step_for(&self, season: u32) -> RotationStep {
+        if self.steps.is_empty() {
+            return RotationStep::Fallow;
+        }
+        self.steps[season as usize % self.steps.len()].clone()
+    }
+
+    pub fn steps(&self) -> &[RotationStep] {
+        &self.steps
+    }
+}
+
+/// Outcome of one season on one plot.
+#[derive(Serialize, Debug, Clone)]
+pub struct SeasonRecord {
+    pub season: u32,
+    pub year: u32,
+    pub start_date: NaiveDate,
+    pub step: RotationStep,
+    /// Botanical family of the cash crop, if any
+    pub species: Option<String>,
+    pub planted: u32,
+    pub harvested: u32,
+    pub failed: u32,
+    pub yield_kg: f32,
+    pub failure_causes: BTreeMap<FailureCause, u32>,
+    /// Soil state at the end of the season
+    pub soil: SoilState,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct RotationReport {
+    pub seasons: Vec<RunReport>,
+    pub plots: BTreeMap<String, Vec<SeasonRecord>>,
+    /// Lots running low in the shed at the end of the rotation
+    pub low_stock: Vec<String>,
+}
+
+impl fmt::Display for RotationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (plot, records) in self.plots.iter() {
+            writeln!(f, "Plot: {}", plot)?;
+            for record in records {
+                writeln!(
+                    f,
+                    "  Year {} Season {} ({}): {:<22} planted: {:>3} harvested: {:>3} failed: {:>3} yield: {:>7.3} kg | soil N: {:.1} kg/ha, OM: {:.2}%",
+                    record.year + 1,
+                    record.season + 1,
+                    record.start_date,
+                    record.step.to_string(),
+                    record.planted,
+                    record.harvested,
+                    record.failed,
+                    record.yield_kg,
+                    record.soil.nitrogen,
+                    record.soil.organic_matter,
+                )?;
+                for (cause, count) in record.failure_causes.iter() {
+                    writeln!(f, "      failed from {}: {}", cause, count)?;
+                }
+            }
+        }
+        writeln!(f, "Seed emergence:")?;
+        for (season, run_report) in self.seasons.iter().enumerate() {
+            for (lot_number, emergence) in run_report.emergence.iter() {
+                writeln!(
+                    f,
+                    "  Season {} lot {} ({}): {}/{} emerged ({:.1}%)",
+                    season + 1,
+                    lot_number,
+                    emergence.crop_name,
+                    emergence.emerged,
+                    emergence.sown,
+                    emergence.percentage(),
+                )?;
+            }
+        }
+        for (season, run_report) in self.seasons.iter().enumerate() {
+            writeln!(f, "Season {} profit and loss:", season + 1)?;
+            write!(f, "{}", run_report.economics)?;
+            writeln!(f, "Season {} storage:", season + 1)?;
+            for (crop_name, summary) in run_report.crops.iter().filter(|(_, summary)| summary.stored_kg > 0.0) {
+                writeln!(
+                    f,
+                    "  {:<10} stored: {:>7.3} kg spoiled: {:>7.3} kg ({:.1}%)",
+                    crop_name,
+                    summary.stored_kg,
+                    summary.storage_loss_kg,
+                    summary.storage_loss_kg / summary.stored_kg * 100.0,
+                )?;
+            }
+            for transplant in run_report.transplants.iter() {
+                writeln!(
+                    f,
+                    "Season {} transplanted {} to {} on day {} after {} days in the nursery",
+                    season + 1,
+                    transplant.crop_name,
+                    transplant.plot,
+                    transplant.day,
+                    transplant.days_in_nursery,
+                )?;
+            }
+            for (plot, greenhouse) in run_report.greenhouses.iter() {
+                writeln!(f, "Season {} greenhouse {}: {}", season + 1, plot, greenhouse)?;
+            }
+            writeln!(f, "Season {} labour:", season + 1)?;
+            write!(f, "{}", run_report.labour)?;
+        }
+        for warning in self.low_stock.iter() {
+            writeln!(f, "Low stock: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a farm through several seasons, following the rotation plan of each plot
+/// and carrying the soil state over between seasons.
+pub struct RotationRunner {
+    farm: Farm,
+    seasons: u32,
+    seasons_per_year: u32,
+    start_date: NaiveDate,
+    failure_model: FailureModel,
+    policy: ManagementPolicy,
+    parameters: ModelParameters,
+    /// Seed of the first season, if the run is to be repeatable
+    seed: Option<u64>,
+    costs: CostTable,
+    prices: PriceTable,
+    cancellation: Option<Arc<AtomicBool>>,
+    progress: Option<Sender<DayFrame>>,
+    weather_series: Option<Arc<WeatherSeries>>,
+    /// Prints the seasons and the days of the run to stdout
+    verbose: bool,
+}
+
+impl RotationRunner {
+    pub fn new(farm: Farm, seasons: u32, seasons_per_year: u32) -> Self {
+        Self {
+            farm,
+            seasons,
+            seasons_per_year: seasons_per_year.max(1),
+            start_date: Utc::now().date_naive(),
+            failure_model: FailureModel::default(),
+            policy: ManagementPolicy::default(),
+            parameters: ModelParameters::default(),
+            seed: None,
+            costs: CostTable::default(),
+            prices: PriceTable::default(),
+            cancellation: None,
+            progress: None,
+            weather_series: None,
+            verbose: true,
+        }
+    }
+
+    pub fn with_failure_model(mut self, failure_model: FailureModel) -> Self {
+        self.failure_model = failure_model;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: ManagementPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_parameters(mut self, parameters: ModelParameters) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    /// Seeds the random draws of every season, season `n` with `seed + n`.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_economics(mut self, costs: CostTable, prices: PriceTable) -> Self {
+        self.costs = costs;
+        self.prices = prices;
+        self
+    }
+
+    /// Stops the rotation with `Cancelled` as soon as `cancellation` is set, in the middle of a season if needed.
+    pub fn with_cancellation(mut self, cancellation: Arc<AtomicBool>) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Sends a `DayFrame` to `progress` at the end of every day of every season.
+    pub fn with_progress(mut self, progress: Sender<DayFrame>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Every season takes the weather of its days from `weather_series`, as far as it covers them.
+    pub fn with_weather(mut self, weather_series: Arc<WeatherSeries>) -> Self {
+        self.weather_series = Some(weather_series);
+        self
+    }
+
+    /// Runs every season without printing its progress to stdout.
+    pub fn quiet(mut self) -> Self {
+        self.verbose = false;
+        self
+    }
+
+    /// Date on which the first season starts. The following seasons are spread evenly over the year.
+    pub fn starting_on(mut self, start_date: NaiveDate) -> Self {
+        self.start_date = start_date;
+        self
+    }
+
+    fn season_start(&self, season: u32) -> NaiveDate {
+        let months = season * 12 / self.seasons_per_year;
+        self.start_date + Months::new(months)
+    }
+
+    pub fn farm(&self) -> &Farm {
+        &self.farm
+    }
+
+    pub fn start_date(&self) -> NaiveDate {
+        self.start_date
+    }
+
+    /// Day after the last season of the rotation ends, at the latest.
+    pub fn end_date(&self) -> NaiveDate {
+        self.season_start(self.seasons)
+    }
+
+    /// Seeds every plot needs from the shed over the whole rotation: one bag per cash crop and season.
+    pub fn seed_demands(&self) -> Vec<SeedDemand> {
+        let mut demands = Vec::new();
+        for season in 0..self.seasons {
+            for plot in self.farm.plots.iter() {
+                if let RotationStep::Crop(name) = plot.rotation.step_for(season) {
+                    demands.push(SeedDemand {
+                        seeds: self.farm.inventory.sowing_size(&name),
+                        verbose_name: name,
+                        sowing_date: self.season_start(season),
+                    });
+                }
+            }
+        }
+        demands
+    }
+
+    /// Fails with `InsufficientStock` listing every sowing the seeds in the shed cannot cover.
+    pub fn check_seed_stock(&self) -> Result<(), AgroException> {
+        let shortfalls = self.farm.inventory.shortfalls(&self.seed_demands());
+        if shortfalls.is_empty() {
+            return Ok(());
+        }
+        let shortfalls: Vec<String> = shortfalls.iter().map(|shortfall| shortfall.to_string()).collect();
+        Err(AgroException::InsufficientStock(shortfalls.join("; ")))
+    }
+
+    pub fn run(self) -> Result<RotationReport, AgroException> {
+        self.check_seed_stock()?;
+        let mut report = RotationReport::default();
+        let season_starts: Vec<NaiveDate> = (0..self.seasons).map(|season| self.season_start(season)).collect();
+        let mut farm = self.farm;
+
+        for (season, start_date) in (0..self.seasons).zip(season_starts) {
+            let year = season / self.seasons_per_year;
+            if self.verbose {
+                println!("==================================================");
+                println!("Year {} - Season {} (starting {})", year + 1, season % self.seasons_per_year + 1, start_date);
+                println!("==================================================");
+            }
+
+            let steps = Self::start_season(&mut farm, season, start_date, self.verbose)?;
+            let mut service = PlantService::new(farm)
+                .starting_on(start_date)
+                .with_failure_model(self.failure_model)
+                .with_policy(self.policy.clone())
+                .with_parameters(self.parameters.clone())
+                .with_economics(self.costs.clone(), self.prices.clone());
+            if let Some(cancellation) = self.cancellation.as_ref() {
+                service = service.with_cancellation(cancellation.clone());
+            }
+            if let Some(progress) = self.progress.as_ref() {
+                service = service.with_progress(progress.clone());
+            }
+            if let Some(seed) = self.seed {
+                service = service.with_seed(seed.wrapping_add(season as u64));
+            }
+            if let Some(weather_series) = self.weather_series.as_ref() {
+                service = service.with_weather(weather_series.clone());
+            }
+            if !self.verbose {
+                service = service.quiet();
+            }
+            let (returned_farm, run_report) = service.prepare_farm().run()?;
+            farm = returned_farm;
+
+            for (plot_idx, step) in steps.into_iter().enumerate() {
+                let record = Self::close_season(&mut farm, plot_idx, season, year, start_date, step);
+                farm.plots[plot_idx].history.push(record.clone());
+                report.plots
+                    .entry(farm.plots[plot_idx].name.to_string())
+                    .or_default()
+                    .push(record);
+            }
+            report.seasons.push(run_report);
+        }
+        report.low_stock = farm.inventory.low_stock_warnings();
+
+        Ok(report)
+    }
+
+    /// Applies the inter-season activities and takes the seeds of this season's cash crops out of the shed.
+    fn start_season(farm: &mut Farm, season: u32, start_date: NaiveDate, verbose: bool) -> Result<Vec<RotationStep>, AgroException> {
+        let mut crops: Vec<Crop> = Vec::new();
+        let mut steps = Vec::new();
+        for plot in farm.plots.iter_mut() {
+            let step = plot.rotation.step_for(season);
+            match &step {
+                RotationStep::Crop(name) => {
+                    if verbose {
+                        println!("Plot {}: planting {}", plot.name, name);
+                    }
+                    let demand = SeedDemand {
+                        verbose_name: name.to_string(),
+                        seeds: farm.inventory.sowing_size(name),
+                        sowing_date: start_date,
+                    };
+                    let (drawn, warnings) = farm.inventory.draw(&demand, Some(plot.id))?;
+                    if verbose {
+                        for warning in warnings {
+                            println!("Low stock: {}", warning);
+                        }
+                    }
+                    crops.extend(drawn);
+                },
+                RotationStep::CoverCrop(cover_crop) => {
+                    if verbose {
+                        println!("Plot {}: sowing {:?} as cover crop", plot.name, cover_crop);
+                    }
+                    plot.soil.cover_crop(cover_crop);
+                },
+                RotationStep::Fallow => {
+                    if verbose {
+                        println!("Plot {}: left fallow", plot.name);
+                    }
+                    plot.soil.fallow();
+                },
+            }
+            steps.push(step);
+        }
+        farm.crops = crops;
+        Ok(steps)
+    }
+
+    /// Removes the nutrients taken up by the crop, returns the residues and records the season.
+    fn close_season(farm: &mut Farm, plot_idx: usize, season: u32, year: u32, start_date: NaiveDate, step: RotationStep) -> SeasonRecord {
+        let plot = &mut farm.plots[plot_idx];
+        let plot_crops: Vec<&Crop> = farm.crops.iter()
+            .filter(|crop| crop.field_plot() == Some(plot.id))
+            .collect();
+
+        let planted = plot_crops.len() as u32;
+        let harvested = plot_crops.iter().filter(|crop| crop.is_harvested()).count() as u32;
+        let failed = plot_crops.iter().filter(|crop| crop.has_issues()).count() as u32;
+        let yield_kg = plot_crops.iter().filter_map(|crop| crop.yield_kg).fold(0.0, |total, kg| total + kg);
+        let mut failure_causes = BTreeMap::new();
+        for cause in plot_crops.iter().filter_map(|crop| crop.failure_cause) {
+            *failure_causes.entry(cause).or_insert(0) += 1;
+        }
+
+        if let RotationStep::Crop(name) = &step {
+            let seed_type = SeedType::from_str(name).unwrap();
+            let harvested_share = if planted > 0 { harvested as f32 / planted as f32 } else { 0.0 };
+            plot.soil.uptake(seed_type.nitrogen_demand() * harvested_share);
+            plot.soil.add_residue(seed_type.residual_nitrogen() * harvested_share, 0.05);
+        }
+
+        SeasonRecord {
+            season,
+            year,
+            start_date,
+            species: plot_crops.first().map(|crop| crop.species.to_string()),
+            step,
+            planted,
+            harvested,
+            failed,
+            yield_kg,
+            failure_causes,
+            soil: plot.soil.clone(),
+        }
+    }
+}
@@ -1,9 +1,16 @@
 #![allow(dead_code)]
 
+use std::collections::HashMap;
+use std::fmt;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::astronomy::PhotoperiodResponse;
+use crate::calendar::SowingWindow;
 use crate::crop_parser::extract_content;
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SeedType {
     Sunflower(String, String),
     Pea(String, String),
@@ -24,6 +31,13 @@ impl SeedType {
         }
     }
 
+    /// Every crop the simulator can grow, under its verbose name.
+    pub fn catalog() -> Vec<SeedType> {
+        ["Sun flower", "Pea", "Carrot", "Tomato", "Broccoli"].iter()
+            .map(|name| SeedType::from_str(name).unwrap())
+            .collect()
+    }
+
     pub fn get_botanica_name(&self) -> String {
         match self {
             SeedType::Sunflower(_, botanic_name) => botanic_name.to_string(),
@@ -34,6 +48,18 @@ impl SeedType {
         }
     }
 
+    /// Same seed type under the botanical name given on the bag.
+    pub fn with_botanica_name(self, botanica_name: &str) -> SeedType {
+        let botanica_name = botanica_name.to_string();
+        match self {
+            SeedType::Sunflower(verbose_name, _) => SeedType::Sunflower(verbose_name, botanica_name),
+            SeedType::Pea(verbose_name, _) => SeedType::Pea(verbose_name, botanica_name),
+            SeedType::Carrot(verbose_name, _) => SeedType::Carrot(verbose_name, botanica_name),
+            SeedType::Tomato(verbose_name, _) => SeedType::Tomato(verbose_name, botanica_name),
+            SeedType::Broccoli(verbose_name, _) => SeedType::Broccoli(verbose_name, botanica_name),
+        }
+    }
+
     pub fn get_verbose_name(&self) -> String {
         match self {
             SeedType::Sunflower(verbose_name, _) => verbose_name.to_string(),
@@ -43,15 +69,216 @@ impl SeedType {
             SeedType::Broccoli(verbose_name, _) => verbose_name.to_string(),
         }
     }
+
+    /// Legumes fix their own nitrogen and leave some of it behind for the next crop.
+    pub fn is_legume(&self) -> bool {
+        matches!(self, SeedType::Pea(..))
+    }
+
+    /// Row spacing and in-row spacing between plants, in metres.
+    pub fn spacing(&self) -> (f32, f32) {
+        match self {
+            SeedType::Sunflower(..) => (0.45, 0.3),
+            SeedType::Pea(..) => (0.5, 0.05),
+            SeedType::Carrot(..) => (0.3, 0.05),
+            SeedType::Tomato(..) => (1.0, 0.5),
+            SeedType::Broccoli(..) => (0.6, 0.45),
+        }
+    }
+
+    /// Ground area (m²) available to a single plant.
+    pub fn plant_area(&self) -> f32 {
+        let (row_spacing, plant_spacing) = self.spacing();
+        row_spacing * plant_spacing
+    }
+
+    /// Seasonal nitrogen demand of the crop in kg/ha.
+    pub fn nitrogen_demand(&self) -> f32 {
+        match self {
+            SeedType::Sunflower(..) => 80.0,
+            SeedType::Pea(..) => 20.0,
+            SeedType::Carrot(..) => 60.0,
+            SeedType::Tomato(..) => 120.0,
+            SeedType::Broccoli(..) => 150.0,
+        }
+    }
+
+    /// Outdoor sowing months in the northern hemisphere.
+    pub fn sowing_window(&self) -> SowingWindow {
+        match self {
+            SeedType::Sunflower(..) => SowingWindow::new(4, 6),
+            SeedType::Pea(..) => SowingWindow::new(2, 6),
+            SeedType::Carrot(..) => SowingWindow::new(3, 7),
+            SeedType::Tomato(..) => SowingWindow::new(3, 5),
+            SeedType::Broccoli(..) => SowingWindow::new(3, 9),
+        }
+    }
+
+    /// Day length (hours) that triggers flowering, for the crops that are sensitive to it.
+    pub fn photoperiod_response(&self) -> Option<PhotoperiodResponse> {
+        match self {
+            SeedType::Sunflower(..) => Some(PhotoperiodResponse::ShortDay(14.5)),
+            SeedType::Pea(..) => Some(PhotoperiodResponse::LongDay(12.0)),
+            _ => None,
+        }
+    }
+
+    /// Lowest air temperature (°C) the young plant survives.
+    pub fn frost_tolerance(&self) -> f32 {
+        match self {
+            SeedType::Sunflower(..) => -2.0,
+            SeedType::Pea(..) => -6.0,
+            SeedType::Carrot(..) => -5.0,
+            SeedType::Tomato(..) => 0.0,
+            SeedType::Broccoli(..) => -6.0,
+        }
+    }
+
+    /// Soil temperature (°C) below which the seeds hardly germinate.
+    pub fn germination_temperature(&self) -> f32 {
+        match self {
+            SeedType::Sunflower(..) => 8.0,
+            SeedType::Pea(..) => 4.0,
+            SeedType::Carrot(..) => 7.0,
+            SeedType::Tomato(..) => 10.0,
+            SeedType::Broccoli(..) => 5.0,
+        }
+    }
+
+    /// Years after which a seed lot kept at room conditions has lost half of its viability.
+    pub fn viability_half_life(&self) -> f32 {
+        match self {
+            SeedType::Sunflower(..) => 2.0,
+            SeedType::Pea(..) => 3.0,
+            SeedType::Carrot(..) => 2.5,
+            SeedType::Tomato(..) => 4.0,
+            SeedType::Broccoli(..) => 3.0,
+        }
+    }
+
+    /// Fresh weight (kg) harvested from a single well grown plant.
+    pub fn reference_yield(&self) -> f32 {
+        match self {
+            SeedType::Sunflower(..) => 0.1,
+            SeedType::Pea(..) => 0.05,
+            SeedType::Carrot(..) => 0.1,
+            SeedType::Tomato(..) => 3.0,
+            SeedType::Broccoli(..) => 0.4,
+        }
+    }
+
+    /// Crops that are normally raised as seedlings in a nursery and planted out.
+    pub fn is_transplanted(&self) -> bool {
+        matches!(self, SeedType::Tomato(..) | SeedType::Broccoli(..))
+    }
+
+    /// Share of the daily growth lost right after planting out, while the roots re-establish.
+    /// Tap rooted and direct sown crops take it worst.
+    pub fn transplant_shock(&self) -> f32 {
+        match self {
+            SeedType::Sunflower(..) => 0.6,
+            SeedType::Pea(..) => 0.6,
+            SeedType::Carrot(..) => 0.9,
+            SeedType::Tomato(..) => 0.3,
+            SeedType::Broccoli(..) => 0.4,
+        }
+    }
+
+    /// Share of the yield lost for every day the ripe crop is left unharvested.
+    pub fn field_loss_rate(&self) -> f32 {
+        match self {
+            SeedType::Sunflower(..) => 0.02,
+            SeedType::Pea(..) => 0.03,
+            SeedType::Carrot(..) => 0.01,
+            SeedType::Tomato(..) => 0.05,
+            SeedType::Broccoli(..) => 0.04,
+        }
+    }
+
+    /// Nitrogen (kg/ha) returned to the soil by the residues after harvest.
+    pub fn residual_nitrogen(&self) -> f32 {
+        match self {
+            SeedType::Pea(..) => 40.0,
+            SeedType::Broccoli(..) => 15.0,
+            _ => 5.0,
+        }
+    }
+}
+
+/// How a seed lot has been kept since it was packed.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageCondition {
+    /// Sealed and refrigerated
+    ColdDry,
+    #[default]
+    Ambient,
+    Humid,
+}
+
+impl StorageCondition {
+    pub fn from_str(storage: &str) -> Result<StorageCondition, String> {
+        match storage.to_lowercase().as_str() {
+            "cold_dry" => Ok(StorageCondition::ColdDry),
+            "ambient" => Ok(StorageCondition::Ambient),
+            "humid" => Ok(StorageCondition::Humid),
+            _ => Err("Invalid storage condition".to_string()),
+        }
+    }
+
+    /// Multiplier of the viability half-life of the seeds.
+    pub fn longevity_factor(&self) -> f32 {
+        match self {
+            StorageCondition::ColdDry => 3.0,
+            StorageCondition::Ambient => 1.0,
+            StorageCondition::Humid => 0.4,
+        }
+    }
+}
+
+impl fmt::Display for StorageCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageCondition::ColdDry => write!(f, "cold_dry"),
+            StorageCondition::Ambient => write!(f, "ambient"),
+            StorageCondition::Humid => write!(f, "humid"),
+        }
+    }
+}
+
+/// Quality attributes of the seeds packed in a bag.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SeedLot {
+    pub lot_number: String,
+    /// Share of the seeds that germinated in the lab test at packing (0.0 - 1.0)
+    pub germination_rate: f32,
+    /// How well the seeds cope with a cold or dry seedbed (0.0 - 1.0)
+    pub vigor: f32,
+    pub packed_on: NaiveDate,
+    pub storage: StorageCondition,
 }
 
-#[derive(Debug)]
+impl SeedLot {
+    /// Age of the seeds in years on `date`.
+    pub fn age_on(&self, date: NaiveDate) -> f32 {
+        (date - self.packed_on).num_days().max(0) as f32 / 365.25
+    }
+
+    /// Share of the seeds still able to germinate on `date`. Viability halves every
+    /// half-life of the seed type, which is stretched or shortened by the storage.
+    pub fn viability_on(&self, seed_type: &SeedType, date: NaiveDate) -> f32 {
+        let half_life = seed_type.viability_half_life() * self.storage.longevity_factor();
+        self.germination_rate * 0.5_f32.powf(self.age_on(date) / half_life)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct SeedBag {
     pub quantity: u32,
     pub seed_type: SeedType,
     pub species: String,
     pub is_gmo: Option<bool>,
     pub description: Option<String>,
+    pub lot: Option<SeedLot>,
 }
 
 impl SeedBag {
@@ -61,33 +288,43 @@ impl SeedBag {
             Ok(seed) => Some(Self {
                 quantity,
                 seed_type: seed,
-                species: species,
+                species,
                 description: None,
                 is_gmo: None,
+                lot: None,
             }),
             Err(_) => panic!("Invalid seed type"),
         }
     }
 
+    /// Reads a bag from a row of a seeds CSV file.
+    pub fn from_record(record: &HashMap<String, String>) -> Result<SeedBag, String> {
+        let field = |name: &str| record.get(name).ok_or(format!("Missing column {}", name));
+        let seed_type = SeedType::from_str(field("verbose_name")?)?.with_botanica_name(field("botanica_name")?);
+        let lot = SeedLot {
+            lot_number: field("lot_number")?.to_string(),
+            germination_rate: field("germination_rate")?.parse::<f32>().map_err(|err| err.to_string())?,
+            vigor: field("vigor")?.parse::<f32>().map_err(|err| err.to_string())?,
+            packed_on: NaiveDate::parse_from_str(field("packed_on")?, "%Y-%m-%d").map_err(|err| err.to_string())?,
+            storage: StorageCondition::from_str(field("storage")?)?,
+        };
+        Ok(Self {
+            quantity: field("quantity_per_bag")?.parse::<u32>().map_err(|err| err.to_string())?,
+            seed_type,
+            species: field("species")?.to_string(),
+            is_gmo: Some(field("is_gmo")?.parse::<bool>().map_err(|err| err.to_string())?),
+            description: Some(field("description")?.to_string()),
+            lot: Some(lot),
+        })
+    }
+
     pub fn tear_bags() -> Vec<SeedBag> {
         let bag_maps = extract_content("test_data/seeds.csv");
         let mut bags: Vec<SeedBag> = Vec::new();
 
         for bag in bag_maps {
-            let seed_type_str = bag.get("verbose_name").unwrap().to_string();
-            let quantity_per_bag = bag.get("quantity_per_bag").unwrap().parse::<u32>().unwrap();
-            let seed_bag = SeedBag::new(quantity_per_bag, &seed_type_str.as_str(), bag.get("species").unwrap().to_string());
-            match seed_bag {
-                Some(mut bg) => {
-                    bg.is_gmo = Some(bag.get("is_gmo").unwrap().parse::<bool>().unwrap());
-                    bg.description = Some(bag.get("description").unwrap().to_string());
-                    bags.push(bg);
-                },
-                None => ()
-            }
+            bags.push(SeedBag::from_record(&bag).unwrap_or_else(|err| panic!("Error: {}", err)));
         }
         bags
     }
-
-
-}
\ No newline at end of file
+}
@@ -0,0 +1,587 @@
+#![allow(dead_code)]
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use chrono::NaiveDate;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::dto::{Farm, GrowthStage};
+use crate::errors::AgroException;
+use crate::experiment::{sown_crops, Metric, REPLICATE_SEED_STRIDE};
+use crate::growth::GrowthParameters;
+use crate::parameters::ModelParameters;
+use crate::plant_service::ManagementPolicy;
+use crate::rotation::RotationRunner;
+use crate::seeds::SeedType;
+use crate::statistics::{mean, variance};
+
+
+/// A model input the analysis varies.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Parameter {
+    /// Days a crop spends in a growth stage
+    StageDays { crop: String, stage: GrowthStage },
+    /// Temperature (°C) below which a crop stops growing
+    BaseTemperature { crop: String },
+    RadiationUseEfficiency { crop: String },
+    /// Soil moisture below which a crop suffers from water stress
+    WaterStressThreshold { crop: String },
+    IrrigationInterval,
+    WeedingInterval,
+    FertilizingInterval,
+    /// Water a rainy or stormy day brings (mm)
+    Rainfall,
+    /// Shift of the seasonal mean temperature (°C)
+    TemperatureShift,
+    /// How many times as likely rainy and stormy days are
+    WetWeather,
+}
+
+impl Parameter {
+    /// Value of the parameter when it is not varied.
+    pub fn nominal(&self) -> f64 {
+        let policy = ManagementPolicy::default();
+        let weather = ModelParameters::default().weather;
+        match self {
+            Parameter::StageDays { crop, stage } => SeedType::from_str(crop).map_or(0.0, |seed_type| stage.get_days(seed_type) as f64),
+            Parameter::BaseTemperature { crop } => self.growth_parameters(crop).map_or(0.0, |growth| growth.base_temperature as f64),
+            Parameter::RadiationUseEfficiency { crop } => self.growth_parameters(crop).map_or(0.0, |growth| growth.radiation_use_efficiency as f64),
+            Parameter::WaterStressThreshold { crop } => self.growth_parameters(crop).map_or(0.0, |growth| growth.water_stress_threshold as f64),
+            Parameter::IrrigationInterval => policy.irrigation_interval as f64,
+            Parameter::WeedingInterval => policy.weeding_interval as f64,
+            Parameter::FertilizingInterval => policy.fertilizing_interval as f64,
+            Parameter::Rainfall => weather.rainfall_mm as f64,
+            Parameter::TemperatureShift => weather.temperature_shift as f64,
+            Parameter::WetWeather => weather.wet_weather_factor as f64,
+        }
+    }
+
+    fn growth_parameters(&self, crop: &str) -> Option<GrowthParameters> {
+        SeedType::from_str(crop).ok().map(|seed_type| GrowthParameters::for_seed_type(&seed_type))
+    }
+
+    /// The crop the parameter belongs to, if it is a catalog value of a crop.
+    pub fn crop(&self) -> Option<&str> {
+        match self {
+            Parameter::StageDays { crop, .. }
+            | Parameter::BaseTemperature { crop }
+            | Parameter::RadiationUseEfficiency { crop }
+            | Parameter::WaterStressThreshold { crop } => Some(crop),
+            _ => None,
+        }
+    }
+
+    /// Whether the parameter only takes whole values, so a run rounds what it is given.
+    pub fn is_discrete(&self) -> bool {
+        matches!(
+            self,
+            Parameter::StageDays { .. } | Parameter::IrrigationInterval | Parameter::WeedingInterval | Parameter::FertilizingInterval
+        )
+    }
+
+    pub fn apply(&self, value: f64, policy: &mut ManagementPolicy, parameters: &mut ModelParameters) {
+        let days = value.round().max(0.0) as u32;
+        match self {
+            Parameter::StageDays { crop, stage } => {
+                parameters.crop_mut(crop).stage_days.insert(stage.clone(), days.max(1));
+            },
+            Parameter::BaseTemperature { crop } => parameters.crop_mut(crop).base_temperature = Some(value as f32),
+            Parameter::RadiationUseEfficiency { crop } => parameters.crop_mut(crop).radiation_use_efficiency = Some(value as f32),
+            Parameter::WaterStressThreshold { crop } => parameters.crop_mut(crop).water_stress_threshold = Some(value as f32),
+            Parameter::IrrigationInterval => policy.irrigation_interval = days,
+            Parameter::WeedingInterval => policy.weeding_interval = days,
+            Parameter::FertilizingInterval => policy.fertilizing_interval = days,
+            Parameter::Rainfall => parameters.weather.rainfall_mm = value as f32,
+            Parameter::TemperatureShift => parameters.weather.temperature_shift = value as f32,
+            Parameter::WetWeather => parameters.weather.wet_weather_factor = value as f32,
+        }
+    }
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parameter::StageDays { crop, stage } => write!(f, "{} {:?} days", crop, stage),
+            Parameter::BaseTemperature { crop } => write!(f, "{} base temperature", crop),
+            Parameter::RadiationUseEfficiency { crop } => write!(f, "{} RUE", crop),
+            Parameter::WaterStressThreshold { crop } => write!(f, "{} water stress", crop),
+            Parameter::IrrigationInterval => write!(f, "Irrigation interval"),
+            Parameter::WeedingInterval => write!(f, "Weeding interval"),
+            Parameter::FertilizingInterval => write!(f, "Fertilizing interval"),
+            Parameter::Rainfall => write!(f, "Rainfall (mm)"),
+            Parameter::TemperatureShift => write!(f, "Temperature shift"),
+            Parameter::WetWeather => write!(f, "Wet weather factor"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParameterRange {
+    pub parameter: Parameter,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ParameterRange {
+    pub fn validate(&self) -> Result<(), AgroException> {
+        if self.min.is_nan() || self.max.is_nan() || self.min >= self.max {
+            return Err(AgroException::InvalidExperiment(format!("{}: the minimum must be below the maximum", self.parameter)));
+        }
+        if let Some(crop) = self.parameter.crop() {
+            SeedType::from_str(crop).map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", crop, err)))?;
+        }
+        Ok(())
+    }
+
+    /// Maps `unit` in [0, 1] onto the range.
+    pub fn scale(&self, unit: f64) -> f64 {
+        self.min + unit * (self.max - self.min)
+    }
+}
+
+/// How the points of the base matrices of the Saltelli design are drawn.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Sampling {
+    /// Stratified: every parameter has one point in each of `samples` equal slices of its range
+    #[default]
+    LatinHypercube,
+    /// Independent uniform draws, as in Saltelli's original scheme
+    Saltelli,
+}
+
+/// Which parameters the outputs of a rotation hinge on.
+///
+/// One-at-a-time elasticities are taken around the nominal values. Sobol first-order and total
+/// indices come from the Saltelli design: two base matrices `A` and `B` of `samples` points and,
+/// for every parameter, `A` with that column taken from `B`, i.e. `samples * (parameters + 2)` runs.
+/// Each row of the design gets its own seed, shared by the row of `A`, of `B` and of every mixed
+/// matrix, so the indices average over the weather while the rows stay comparable. The one-at-a-time
+/// runs all get the seed of the first row. Confidence intervals of the indices are bootstrapped over
+/// the rows.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SensitivityStudy {
+    pub name: String,
+    pub start_date: NaiveDate,
+    #[serde(default = "SensitivityStudy::default_seasons")]
+    pub seasons: u32,
+    #[serde(default = "SensitivityStudy::default_seasons_per_year")]
+    pub seasons_per_year: u32,
+    #[serde(default = "SensitivityStudy::default_samples")]
+    pub samples: u32,
+    #[serde(default)]
+    pub sampling: Sampling,
+    #[serde(default)]
+    pub seed: u64,
+    /// Step of the one-at-a-time differences, as a share of the range
+    #[serde(default = "SensitivityStudy::default_oat_step")]
+    pub oat_step: f64,
+    /// Resamples of the rows behind the confidence intervals of the Sobol indices
+    #[serde(default = "SensitivityStudy::default_bootstrap")]
+    pub bootstrap: u32,
+    pub parameters: Vec<ParameterRange>,
+    #[serde(default = "SensitivityStudy::default_outputs")]
+    pub outputs: Vec<Metric>,
+}
+
+impl SensitivityStudy {
+    fn default_seasons() -> u32 {
+        1
+    }
+
+    fn default_seasons_per_year() -> u32 {
+        2
+    }
+
+    fn default_samples() -> u32 {
+        512
+    }
+
+    fn default_oat_step() -> f64 {
+        0.1
+    }
+
+    fn default_bootstrap() -> u32 {
+        200
+    }
+
+    fn default_outputs() -> Vec<Metric> {
+        Metric::ALL.to_vec()
+    }
+
+    /// Reads a study from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AgroException> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", path.display(), err)))?;
+        let study: SensitivityStudy = serde_json::from_str(&content)
+            .map_err(|err| AgroException::InvalidExperiment(format!("{}: {}", path.display(), err)))?;
+        study.validate()?;
+        Ok(study)
+    }
+
+    fn validate(&self) -> Result<(), AgroException> {
+        if self.parameters.is_empty() || self.outputs.is_empty() {
+            return Err(AgroException::InvalidExperiment("no parameters or no outputs to analyse".to_string()));
+        }
+        if self.samples < 2 {
+            return Err(AgroException::InvalidExperiment("at least 2 samples are needed".to_string()));
+        }
+        self.parameters.iter().try_for_each(ParameterRange::validate)
+    }
+
+    /// Fails if a parameter belongs to a crop the farm does not sow in any season of the run, as
+    /// varying it would change nothing.
+    fn check_crops(&self, farm: &Farm) -> Result<(), AgroException> {
+        let sown = sown_crops(farm, self.seasons);
+        let unsown = self.parameters.iter()
+            .find(|range| range.parameter.crop().is_some_and(|crop| !sown.iter().any(|sown| sown.eq_ignore_ascii_case(crop))));
+        match unsown {
+            Some(range) => Err(AgroException::InvalidExperiment(format!(
+                "{} belongs to a crop the farm does not sow in the {} season(s) of the run",
+                range.parameter,
+                self.seasons
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs the analysis on fresh farms from `farm`, the runs in parallel.
+    pub fn run(&self, farm: impl Fn() -> Result<Farm, AgroException> + Sync) -> Result<SensitivityReport, AgroException> {
+        self.validate()?;
+        self.check_crops(&farm()?)?;
+        let mut points = Vec::new();
+        let nominal = self.nominal_point();
+        let oat = self.oat_points(&nominal);
+        points.push((nominal.clone(), self.seed));
+        points.extend(oat.iter().flat_map(|(low, high)| [(low.clone(), self.seed), (high.clone(), self.seed)]));
+
+        let (a, b) = self.base_matrices();
+        let samples = a.len();
+        let row_seeds: Vec<u64> = (0..samples).map(|row| self.seed.wrapping_add(row as u64 * REPLICATE_SEED_STRIDE)).collect();
+        points.extend(a.iter().cloned().zip(row_seeds.iter().copied()));
+        points.extend(b.iter().cloned().zip(row_seeds.iter().copied()));
+        for idx in 0..self.parameters.len() {
+            points.extend(a.iter().zip(b.iter()).zip(row_seeds.iter()).map(|((a_row, b_row), seed)| {
+                let mut row = a_row.clone();
+                row[idx] = b_row[idx];
+                (row, *seed)
+            }));
+        }
+
+        let outputs: Vec<Vec<f64>> = points.par_iter()
+            .map(|(point, seed)| self.evaluate(point, *seed, &farm))
+            .collect::<Result<_, _>>()?;
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let resamples: Vec<Vec<usize>> = (0..self.bootstrap)
+            .map(|_| (0..samples).map(|_| rng.gen_range(0..samples)).collect())
+            .collect();
+        let mut rows = Vec::new();
+        for (output, metric) in self.outputs.iter().enumerate() {
+            let y: Vec<f64> = outputs.iter().map(|values| values[output]).collect();
+            let y_nominal = y[0];
+            let y_a = &y[1 + 2 * oat.len()..][..samples];
+            let y_b = &y[1 + 2 * oat.len() + samples..][..samples];
+            for (idx, range) in self.parameters.iter().enumerate() {
+                let (low, high) = &oat[idx];
+                let (y_low, y_high) = (y[1 + 2 * idx], y[2 + 2 * idx]);
+                let x_step = high[idx] - low[idx];
+                let elasticity = match (x_step > 0.0, y_nominal != 0.0, nominal[idx] != 0.0) {
+                    (true, true, true) => Some((y_high - y_low) / x_step * nominal[idx] / y_nominal),
+                    _ => None,
+                };
+                let y_ab = &y[1 + 2 * oat.len() + (2 + idx) * samples..][..samples];
+                let indices = sobol_indices(y_a, y_b, y_ab);
+                let (first_order_interval, total_interval) = match indices {
+                    Some(_) => bootstrap_intervals(y_a, y_b, y_ab, &resamples),
+                    None => (None, None),
+                };
+                rows.push(SensitivityRow {
+                    parameter: range.parameter.clone(),
+                    metric: *metric,
+                    nominal: nominal[idx],
+                    elasticity,
+                    first_order: indices.map(|(first_order, _)| first_order),
+                    first_order_interval,
+                    total: indices.map(|(_, total)| total),
+                    total_interval,
+                });
+            }
+        }
+
+        Ok(SensitivityReport {
+            name: self.name.to_string(),
+            sampling: self.sampling,
+            samples: self.samples,
+            runs: points.len(),
+            rows,
+        })
+    }
+
+    /// Built-in value of every parameter, brought within its range.
+    fn nominal_point(&self) -> Vec<f64> {
+        self.parameters.iter().map(|range| range.parameter.nominal().clamp(range.min, range.max)).collect()
+    }
+
+    /// The nominal point with one parameter moved down and up by the OAT step, for every parameter.
+    fn oat_points(&self, nominal: &[f64]) -> Vec<(Vec<f64>, Vec<f64>)> {
+        self.parameters.iter().enumerate()
+            .map(|(idx, range)| {
+                let mut step = self.oat_step * (range.max - range.min) / 2.0;
+                if range.parameter.is_discrete() {
+                    step = step.max(1.0);
+                }
+                let mut low = nominal.to_vec();
+                let mut high = nominal.to_vec();
+                low[idx] = (nominal[idx] - step).max(range.min);
+                high[idx] = (nominal[idx] + step).min(range.max);
+                (low, high)
+            })
+            .collect()
+    }
+
+    /// The `A` and `B` matrices of the Saltelli design, scaled onto the parameter ranges.
+    fn base_matrices(&self) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let samples = self.samples as usize;
+        let draw = |rng: &mut StdRng| -> Vec<Vec<f64>> {
+            let columns: Vec<Vec<f64>> = self.parameters.iter()
+                .map(|range| {
+                    let units: Vec<f64> = match self.sampling {
+                        Sampling::LatinHypercube => {
+                            let mut strata: Vec<usize> = (0..samples).collect();
+                            strata.shuffle(rng);
+                            strata.into_iter().map(|stratum| (stratum as f64 + rng.gen::<f64>()) / samples as f64).collect()
+                        },
+                        Sampling::Saltelli => (0..samples).map(|_| rng.gen::<f64>()).collect(),
+                    };
+                    units.into_iter().map(|unit| range.scale(unit)).collect()
+                })
+                .collect();
+            (0..samples).map(|row| columns.iter().map(|column| column[row]).collect()).collect()
+        };
+        let a = draw(&mut rng);
+        let b = draw(&mut rng);
+        (a, b)
+    }
+
+    /// Outputs of one run of the rotation with the parameters at `point`.
+    fn evaluate(&self, point: &[f64], seed: u64, farm: &(impl Fn() -> Result<Farm, AgroException> + Sync)) -> Result<Vec<f64>, AgroException> {
+        let mut policy = ManagementPolicy::default();
+        let mut parameters = ModelParameters::default();
+        for (range, value) in self.parameters.iter().zip(point.iter()) {
+            range.parameter.apply(*value, &mut policy, &mut parameters);
+        }
+        let report = RotationRunner::new(farm()?, self.seasons, self.seasons_per_year)
+            .starting_on(self.start_date)
+            .quiet()
+            .with_policy(policy)
+            .with_parameters(parameters)
+            .with_seed(seed)
+            .run()?;
+        Ok(self.outputs.iter().map(|metric| metric.of(&report)).collect())
+    }
+}
+
+/// Low and high end of a confidence interval.
+type Interval = (f64, f64);
+
+/// Sobol first-order and total indices of one parameter from the outputs of the rows of `A`, `B` and
+/// `A` with the parameter's column from `B`, by the estimators of Saltelli (2010) and Jansen (1999).
+/// None when the output does not vary.
+fn sobol_indices(y_a: &[f64], y_b: &[f64], y_ab: &[f64]) -> Option<(f64, f64)> {
+    let total_variance = variance(&[y_a, y_b].concat());
+    if total_variance <= 0.0 {
+        return None;
+    }
+    let first: Vec<f64> = y_b.iter().zip(y_ab.iter()).zip(y_a.iter()).map(|((b, ab), a)| b * (ab - a)).collect();
+    let total: Vec<f64> = y_a.iter().zip(y_ab.iter()).map(|(a, ab)| (a - ab).powi(2) / 2.0).collect();
+    Some((mean(&first) / total_variance, mean(&total) / total_variance))
+}
+
+/// 95% percentile intervals of the first-order and total indices over resamples of the rows.
+fn bootstrap_intervals(y_a: &[f64], y_b: &[f64], y_ab: &[f64], resamples: &[Vec<usize>]) -> (Option<Interval>, Option<Interval>) {
+    let pick = |values: &[f64], rows: &[usize]| -> Vec<f64> { rows.iter().map(|row| values[*row]).collect() };
+    let (mut first, mut total): (Vec<f64>, Vec<f64>) = resamples.iter()
+        .filter_map(|rows| sobol_indices(&pick(y_a, rows), &pick(y_b, rows), &pick(y_ab, rows)))
+        .unzip();
+    (percentile_interval(&mut first), percentile_interval(&mut total))
+}
+
+fn percentile_interval(values: &mut [f64]) -> Option<Interval> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let at = |share: f64| values[((values.len() - 1) as f64 * share).round() as usize];
+    Some((at(0.025), at(0.975)))
+}
+
+/// Sensitivity of one output to one parameter. Indices that cannot be worked out, e.g. because
+/// the output never moved, are left out.
+#[derive(Serialize, Debug, Clone)]
+pub struct SensitivityRow {
+    pub parameter: Parameter,
+    pub metric: Metric,
+    pub nominal: f64,
+    /// Relative change of the output over the relative change of the parameter, at the nominal point
+    pub elasticity: Option<f64>,
+    /// Sobol first-order index: share of the output variance due to the parameter alone
+    pub first_order: Option<f64>,
+    /// 95% bootstrap interval of the first-order index
+    pub first_order_interval: Option<Interval>,
+    /// Sobol total index: share of the variance due to the parameter and its interactions
+    pub total: Option<f64>,
+    pub total_interval: Option<Interval>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SensitivityReport {
+    pub name: String,
+    pub sampling: Sampling,
+    pub samples: u32,
+    pub runs: usize,
+    pub rows: Vec<SensitivityRow>,
+}
+
+impl fmt::Display for SensitivityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let index = |value: Option<f64>| value.map_or("-".to_string(), |value| format!("{:.3}", value));
+        let interval = |value: Option<Interval>| value.map_or("-".to_string(), |(low, high)| format!("[{:.2}, {:.2}]", low, high));
+        writeln!(f, "Sensitivity: {} ({} samples, {:?}, {} runs)", self.name, self.samples, self.sampling, self.runs)?;
+        let mut metrics: Vec<Metric> = self.rows.iter().map(|row| row.metric).collect();
+        metrics.dedup();
+        for metric in metrics {
+            writeln!(f, "{}", metric)?;
+            writeln!(
+                f,
+                "  {:<28} {:>9} {:>11} {:>12} {:>14} {:>8} {:>14}",
+                "Parameter", "Nominal", "Elasticity", "First order", "95% CI", "Total", "95% CI",
+            )?;
+            for row in self.rows.iter().filter(|row| row.metric == metric) {
+                writeln!(
+                    f,
+                    "  {:<28} {:>9.3} {:>11} {:>12} {:>14} {:>8} {:>14}",
+                    row.parameter.to_string(),
+                    row.nominal,
+                    index(row.elasticity),
+                    index(row.first_order),
+                    interval(row.first_order_interval),
+                    index(row.total),
+                    interval(row.total_interval),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::setup::{demo_record, setup_farm};
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() < tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    /// A study of three parameters on [0, 1], only used for its design.
+    fn study(samples: u32) -> SensitivityStudy {
+        let unit = |parameter| ParameterRange { parameter, min: 0.0, max: 1.0 };
+        serde_json::from_value(serde_json::json!({
+            "name": "Unit cube",
+            "start_date": "2025-04-15",
+            "samples": samples,
+            "seed": 3,
+            "parameters": [unit(Parameter::Rainfall), unit(Parameter::TemperatureShift), unit(Parameter::WetWeather)],
+        }))
+        .unwrap()
+    }
+
+    /// Outputs of `model` on `A`, on `B` and on `A` with the column of every parameter from `B`.
+    fn design_outputs(study: &SensitivityStudy, model: impl Fn(&[f64]) -> f64) -> (Vec<f64>, Vec<f64>, Vec<Vec<f64>>) {
+        let (a, b) = study.base_matrices();
+        let mixed = (0..study.parameters.len())
+            .map(|idx| {
+                a.iter().zip(b.iter())
+                    .map(|(a_row, b_row)| {
+                        let mut row = a_row.clone();
+                        row[idx] = b_row[idx];
+                        model(&row)
+                    })
+                    .collect()
+            })
+            .collect();
+        (a.iter().map(|row| model(row)).collect(), b.iter().map(|row| model(row)).collect(), mixed)
+    }
+
+    #[test]
+    fn indices_of_an_additive_function() {
+        // Y = X1 + 2 X2 with uniform inputs: the variances 1/12 and 4/12 give indices of 1/5 and 4/5,
+        // total and first order alike, and X3 does nothing.
+        let study = study(4096);
+        let (y_a, y_b, y_ab) = design_outputs(&study, |x| x[0] + 2.0 * x[1]);
+
+        for (y_ab, expected) in y_ab.iter().zip([0.2, 0.8, 0.0]) {
+            let (first_order, total) = sobol_indices(&y_a, &y_b, y_ab).unwrap();
+            assert_close(first_order, expected, 0.03);
+            assert_close(total, expected, 0.03);
+        }
+    }
+
+    #[test]
+    fn indices_of_an_interaction() {
+        // Y = X1 X2 has variance 7/144, of which X1 alone explains 1/48 (3/7) and X1 with its
+        // interaction 1/36 (4/7).
+        let study = study(4096);
+        let (y_a, y_b, y_ab) = design_outputs(&study, |x| x[0] * x[1]);
+
+        let (first_order, total) = sobol_indices(&y_a, &y_b, &y_ab[0]).unwrap();
+        assert_close(first_order, 3.0 / 7.0, 0.03);
+        assert_close(total, 4.0 / 7.0, 0.03);
+    }
+
+    #[test]
+    fn bootstrap_intervals_cover_the_analytic_indices() {
+        let study = study(1024);
+        let (y_a, y_b, y_ab) = design_outputs(&study, |x| x[0] + 2.0 * x[1]);
+        let mut rng = StdRng::seed_from_u64(11);
+        let resamples: Vec<Vec<usize>> = (0..200).map(|_| (0..y_a.len()).map(|_| rng.gen_range(0..y_a.len())).collect()).collect();
+
+        for (y_ab, expected) in y_ab.iter().zip([0.2, 0.8]) {
+            let (first_order, total) = bootstrap_intervals(&y_a, &y_b, y_ab, &resamples);
+            let (low, high) = first_order.unwrap();
+            assert!(low < expected && expected < high, "first order {} outside [{}, {}]", expected, low, high);
+            let (low, high) = total.unwrap();
+            assert!(low < expected && expected < high, "total {} outside [{}, {}]", expected, low, high);
+        }
+    }
+
+    #[test]
+    fn parameters_of_crops_the_run_does_not_sow_are_rejected() {
+        let farm = setup_farm(&demo_record()).unwrap();
+        let mut study = study(2);
+        study.parameters = vec![ParameterRange {
+            parameter: Parameter::StageDays { crop: "Carrot".to_string(), stage: GrowthStage::Seedling },
+            min: 10.0,
+            max: 24.0,
+        }];
+
+        // The carrot follows the tomato and a clover cover crop on the south plot.
+        study.seasons = 2;
+        assert!(matches!(study.check_crops(&farm), Err(AgroException::InvalidExperiment(_))));
+        study.seasons = 3;
+        assert!(study.check_crops(&farm).is_ok());
+    }
+
+    #[test]
+    fn constant_output_has_no_indices() {
+        assert_eq!(sobol_indices(&[1.0, 1.0], &[1.0, 1.0], &[1.0, 1.0]), None);
+    }
+}
@@ -0,0 +1,707 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::any::Any;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::access::{Credentials, Operation};
+use crate::errors::AgroException;
+use crate::fertilizer::{FertilizerRequest, Recommendation};
+use crate::progress::DayFrame;
+use crate::registry::FarmRegistry;
+use crate::report::SimulationEvent;
+use crate::rotation::{RotationReport, RotationRunner};
+use crate::seeds::SeedType;
+use crate::setup::setup_farm;
+
+
+/// How often a stream checks for new days while the run is going.
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// Streams open at the same time. Every stream has a thread of its own, so further ones are refused.
+const MAX_STREAMS: usize = 16;
+
+/// Headers carrying the credentials of the user.
+const EMAIL_HEADER: &str = "X-Email";
+const SECURITY_CODE_HEADER: &str = "X-Security-Code";
+
+/// A simulation submitted over the API. The credentials in the headers are checked on submission
+/// and only the email is kept.
+#[derive(Deserialize, Debug)]
+pub struct ScenarioRequest {
+    pub farm_id: u32,
+    pub start_date: NaiveDate,
+    #[serde(default = "ScenarioRequest::default_seasons")]
+    pub seasons: u32,
+    #[serde(default = "ScenarioRequest::default_seasons_per_year")]
+    pub seasons_per_year: u32,
+}
+
+impl ScenarioRequest {
+    fn default_seasons() -> u32 {
+        1
+    }
+
+    fn default_seasons_per_year() -> u32 {
+        2
+    }
+}
+
+/// A registered farm to be simulated over a number of seasons.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub id: u32,
+    pub farm_id: u32,
+    pub submitted_by: String,
+    pub start_date: NaiveDate,
+    pub seasons: u32,
+    pub seasons_per_year: u32,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// A run of a scenario, as shown by the status endpoint.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub id: u32,
+    pub scenario_id: u32,
+    pub status: RunStatus,
+    pub error: Option<String>,
+}
+
+struct Run {
+    summary: RunSummary,
+    cancellation: Arc<AtomicBool>,
+    report: Option<RotationReport>,
+    /// Every day simulated so far, kept for the streams that join late
+    frames: Vec<DayFrame>,
+}
+
+/// Entry of the crop catalog.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CropInfo {
+    pub name: String,
+    pub is_legume: bool,
+    pub is_transplanted: bool,
+    /// Outdoor sowing months in the northern hemisphere
+    pub sowing_months: (u32, u32),
+    /// Nitrogen demand over the season (kg/ha)
+    pub nitrogen_demand: f32,
+    /// Fresh weight (kg) harvested from a well grown plant
+    pub reference_yield: f32,
+}
+
+impl CropInfo {
+    fn from_seed_type(seed_type: &SeedType) -> Self {
+        let window = seed_type.sowing_window();
+        Self {
+            name: seed_type.get_verbose_name(),
+            is_legume: seed_type.is_legume(),
+            is_transplanted: seed_type.is_transplanted(),
+            sowing_months: (window.first_month, window.last_month),
+            nitrogen_demand: seed_type.nitrogen_demand(),
+            reference_yield: seed_type.reference_yield(),
+        }
+    }
+}
+
+/// An event of the run, with the season (from 1) it happened in.
+#[derive(Serialize, Debug)]
+struct SeasonEvent<'a> {
+    season: usize,
+    #[serde(flatten)]
+    event: &'a SimulationEvent,
+}
+
+#[derive(Default)]
+struct ServerState {
+    next_scenario_id: u32,
+    next_run_id: u32,
+    scenarios: BTreeMap<u32, Scenario>,
+    runs: BTreeMap<u32, Run>,
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Fixed number of threads working through the queued runs in order.
+struct WorkerPool {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl WorkerPool {
+    fn new(size: usize) -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..size.max(1) {
+            let receiver = receiver.clone();
+            thread::spawn(move || loop {
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    // A job that panics must not take the thread with it.
+                    Ok(job) => {
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    },
+                    Err(_) => break, // the server has shut down
+                }
+            });
+        }
+        Self { jobs }
+    }
+
+    fn execute(&self, job: Job) {
+        self.jobs.send(job).expect("the worker pool has stopped");
+    }
+}
+
+/// Holds one of the stream slots of the server until dropped.
+struct StreamSlot(Arc<AtomicUsize>);
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic.downcast_ref::<&str>().copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("no message")
+}
+
+/// Error answered to a request: an HTTP status code and a message.
+#[derive(Debug)]
+struct ApiError(u16, String);
+
+impl From<AgroException> for ApiError {
+    fn from(err: AgroException) -> Self {
+        let status = match err {
+            AgroException::FarmNotFound => 404,
+            AgroException::Unauthorized(_) => 401,
+            AgroException::PermissionDenied => 403,
+            AgroException::RegistryUnavailable(_) | AgroException::ServerError(_) => 503,
+            _ => 422,
+        };
+        ApiError(status, err.to_string())
+    }
+}
+
+type ApiResult = Result<(u16, String), ApiError>;
+
+fn json<T: Serialize + ?Sized>(status: u16, value: &T) -> ApiResult {
+    serde_json::to_string(value)
+        .map(|body| (status, body))
+        .map_err(|err| ApiError(500, err.to_string()))
+}
+
+fn not_found(what: &str, id: u32) -> ApiError {
+    ApiError(404, format!("{} {} not found", what, id))
+}
+
+/// JSON API over HTTP to run the simulator on the farms of the registry.
+///
+/// - `GET /crops` lists the crop catalog
+/// - `POST /scenarios` submits a `ScenarioRequest`, `GET /scenarios[/{id}]` shows them
+/// - `POST /scenarios/{id}/runs` queues a run of the scenario
+/// - `GET /runs[/{id}]` polls the status, `POST /runs/{id}/cancel` stops a run
+/// - `GET /runs/{id}/report` and `GET /runs/{id}/events` fetch the results of a completed run
+/// - `GET /runs/{id}/stream[?days_per_second=N]` follows a run day by day as Server-Sent Events,
+///   from its first day, paced to `N` days a second if given
+///
+/// Every route but the catalog and the fertilizer recommendations needs the `X-Email` and
+/// `X-Security-Code` headers of a member of the farm. Submitting a scenario and starting and
+/// cancelling its runs need a user who may run simulations on the farm; the other routes one who
+/// may view reports, and the lists only show the scenarios and runs of such farms.
+///
+/// Runs are carried out by a pool of `workers` threads. At most `MAX_STREAMS` streams are open at a time.
+pub struct ApiServer {
+    registry: FarmRegistry,
+    pool: WorkerPool,
+    state: Arc<Mutex<ServerState>>,
+    open_streams: Arc<AtomicUsize>,
+}
+
+impl ApiServer {
+    pub fn new(registry: FarmRegistry, workers: usize) -> Self {
+        Self {
+            registry,
+            pool: WorkerPool::new(workers),
+            state: Arc::new(Mutex::new(ServerState::default())),
+            open_streams: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Answers requests on `address`, e.g. "127.0.0.1:8080", until the process is stopped.
+    pub fn serve(&self, address: &str) -> Result<(), AgroException> {
+        let server = Server::http(address).map_err(|err| AgroException::ServerError(format!("{}: {}", address, err)))?;
+        println!("Listening on http://{}", address);
+        for request in server.incoming_requests() {
+            self.respond(request);
+        }
+        Ok(())
+    }
+
+    fn respond(&self, mut request: Request) {
+        if let Some(run_id) = Self::stream_route(request.method(), request.url()) {
+            return self.stream(request, run_id);
+        }
+        let credentials = Self::credentials(&request);
+        let mut body = String::new();
+        let result = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => self.handle(request.method(), request.url(), &body, credentials.as_ref()),
+            Err(err) => Err(ApiError(400, err.to_string())),
+        };
+        let (status, body) = result.unwrap_or_else(|ApiError(status, message)| {
+            (status, serde_json::json!({ "error": message }).to_string())
+        });
+        let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let response = Response::from_string(body).with_status_code(status).with_header(content_type);
+        if let Err(err) = request.respond(response) {
+            eprintln!("Error: {}", err);
+        }
+    }
+
+    fn stream_route(method: &Method, url: &str) -> Option<u32> {
+        let path = url.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+        match (method, segments.as_slice()) {
+            (Method::Get, ["runs", run_id, "stream"]) => run_id.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Answers with an event stream: a `day` event with the `DayFrame` of every day,
+    /// then a `status` event with the `RunSummary` once the run is over.
+    fn stream(&self, request: Request, run_id: u32) {
+        let interval = self.authorize_run(run_id, Self::credentials(&request).as_ref(), Operation::ViewReport)
+            .and_then(|_| Self::stream_interval(request.url()))
+            .and_then(|interval| self.open_stream().map(|slot| (interval, slot)));
+        let (interval, slot) = match interval {
+            Ok(interval) => interval,
+            Err(ApiError(status, message)) => {
+                let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+                let body = serde_json::json!({ "error": message }).to_string();
+                let _ = request.respond(Response::from_string(body).with_status_code(status).with_header(content_type));
+                return;
+            },
+        };
+        let state = self.state.clone();
+        // The stream lasts as long as the run, so it gets a thread of its own.
+        thread::spawn(move || {
+            let _slot = slot;
+            let mut writer = request.into_writer();
+            if let Err(err) = Self::write_stream(&state, run_id, interval, &mut writer) {
+                eprintln!("Stream of run {} closed: {}", run_id, err);
+            }
+        });
+    }
+
+    /// Takes one of the `MAX_STREAMS` slots, or fails with 503 if they are all taken.
+    fn open_stream(&self) -> Result<StreamSlot, ApiError> {
+        self.open_streams
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| (open < MAX_STREAMS).then_some(open + 1))
+            .map(|_| StreamSlot(self.open_streams.clone()))
+            .map_err(|_| ApiError(503, format!("{} streams are open already", MAX_STREAMS)))
+    }
+
+    /// Time between two days of the stream, if `days_per_second` is given. Fails with 400 on a pace
+    /// too slow to be represented.
+    fn stream_interval(url: &str) -> Result<Option<Duration>, ApiError> {
+        let days_per_second = url.split_once('?')
+            .and_then(|(_, query)| query.split('&').find_map(|pair| pair.strip_prefix("days_per_second=")))
+            .and_then(|days_per_second| days_per_second.parse::<f32>().ok())
+            .filter(|days_per_second| *days_per_second > 0.0);
+        match days_per_second {
+            Some(days_per_second) => Duration::try_from_secs_f32(1.0 / days_per_second)
+                .map(Some)
+                .map_err(|err| ApiError(400, format!("days_per_second={}: {}", days_per_second, err))),
+            None => Ok(None),
+        }
+    }
+
+    fn write_stream(state: &Mutex<ServerState>, run_id: u32, interval: Option<Duration>, writer: &mut dyn Write) -> std::io::Result<()> {
+        // The events are sent as chunks, so that the client sees where the stream ends.
+        write!(writer, "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nTransfer-Encoding: chunked\r\n\r\n")?;
+        writer.flush()?;
+        let mut sent = 0;
+        loop {
+            let (event, data) = {
+                let state = state.lock().unwrap();
+                let run = &state.runs[&run_id];
+                match run.frames.get(sent) {
+                    Some(frame) => ("day", serde_json::to_string(frame)?),
+                    None if matches!(run.summary.status, RunStatus::Queued | RunStatus::Running) => {
+                        drop(state);
+                        thread::sleep(STREAM_POLL_INTERVAL);
+                        continue;
+                    },
+                    None => ("status", serde_json::to_string(&run.summary)?),
+                }
+            };
+            let chunk = format!("event: {}\ndata: {}\n\n", event, data);
+            write!(writer, "{:x}\r\n{}\r\n", chunk.len(), chunk)?;
+            if event == "status" {
+                write!(writer, "0\r\n\r\n")?;
+                return writer.flush();
+            }
+            writer.flush()?;
+            sent += 1;
+            if let Some(interval) = interval {
+                thread::sleep(interval);
+            }
+        }
+    }
+
+    /// Credentials given in the `X-Email` and `X-Security-Code` headers, if both are there.
+    fn credentials(request: &Request) -> Option<Credentials> {
+        let header = |name: &'static str| request.headers().iter()
+            .find(|header| header.field.equiv(name))
+            .map(|header| header.value.as_str().to_string());
+        Some(Credentials::new(&header(EMAIL_HEADER)?, &header(SECURITY_CODE_HEADER)?))
+    }
+
+    /// The credentials, or 401 if the headers are missing.
+    fn require(credentials: Option<&Credentials>) -> Result<&Credentials, ApiError> {
+        Ok(credentials.ok_or(AgroException::Unauthorized(format!("missing {} or {} header", EMAIL_HEADER, SECURITY_CODE_HEADER)))?)
+    }
+
+    /// Fails unless the user may carry out `operation` on the farm of the scenario.
+    fn authorize_scenario(&self, scenario_id: u32, credentials: Option<&Credentials>, operation: Operation) -> Result<(), ApiError> {
+        let farm_id = self.state.lock().unwrap().scenarios.get(&scenario_id)
+            .map(|scenario| scenario.farm_id)
+            .ok_or(not_found("scenario", scenario_id))?;
+        self.registry.authorize(farm_id, Self::require(credentials)?, operation)?;
+        Ok(())
+    }
+
+    /// Ids of the farms on which the user may view reports, out of `farm_ids`.
+    fn viewable_farms(&self, farm_ids: impl Iterator<Item = u32>, credentials: &Credentials) -> Vec<u32> {
+        let mut farm_ids: Vec<u32> = farm_ids.collect();
+        farm_ids.sort_unstable();
+        farm_ids.dedup();
+        farm_ids.retain(|farm_id| self.registry.authorize(*farm_id, credentials, Operation::ViewReport).is_ok());
+        farm_ids
+    }
+
+    /// Fails unless the user may carry out `operation` on the farm the run simulates.
+    fn authorize_run(&self, run_id: u32, credentials: Option<&Credentials>, operation: Operation) -> Result<(), ApiError> {
+        let scenario_id = self.state.lock().unwrap().runs.get(&run_id)
+            .map(|run| run.summary.scenario_id)
+            .ok_or(not_found("run", run_id))?;
+        self.authorize_scenario(scenario_id, credentials, operation)
+    }
+
+    fn handle(&self, method: &Method, url: &str, body: &str, credentials: Option<&Credentials>) -> ApiResult {
+        let path = url.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+        let id = |segment: &str| segment.parse::<u32>().map_err(|_| ApiError(404, format!("no resource at {}", path)));
+        match (method, segments.as_slice()) {
+            (Method::Get, ["crops"]) => {
+                let catalog: Vec<CropInfo> = SeedType::catalog().iter().map(CropInfo::from_seed_type).collect();
+                json(200, &catalog)
+            },
+            (Method::Post, ["fertilizer", "recommendations"]) => {
+                let request: FertilizerRequest = serde_json::from_str(body).map_err(|err| ApiError(400, err.to_string()))?;
+                json(200, &Recommendation::new(&request)?)
+            },
+            (Method::Post, ["scenarios"]) => self.submit_scenario(body, Self::require(credentials)?),
+            (Method::Get, ["scenarios"]) => {
+                let credentials = Self::require(credentials)?;
+                let scenarios: Vec<Scenario> = self.state.lock().unwrap().scenarios.values().cloned().collect();
+                let farm_ids = self.viewable_farms(scenarios.iter().map(|scenario| scenario.farm_id), credentials);
+                json(200, &scenarios.iter().filter(|scenario| farm_ids.contains(&scenario.farm_id)).collect::<Vec<_>>())
+            },
+            (Method::Get, ["scenarios", scenario_id]) => {
+                let scenario_id = id(scenario_id)?;
+                self.authorize_scenario(scenario_id, credentials, Operation::ViewReport)?;
+                let state = self.state.lock().unwrap();
+                let scenario = state.scenarios.get(&scenario_id).ok_or(not_found("scenario", scenario_id))?;
+                json(200, scenario)
+            },
+            (Method::Post, ["scenarios", scenario_id, "runs"]) => {
+                let scenario_id = id(scenario_id)?;
+                self.authorize_scenario(scenario_id, credentials, Operation::RunSimulation)?;
+                self.start_run(scenario_id)
+            },
+            (Method::Get, ["runs"]) => {
+                let credentials = Self::require(credentials)?;
+                let runs: Vec<(u32, RunSummary)> = {
+                    let state = self.state.lock().unwrap();
+                    state.runs.values()
+                        .filter_map(|run| state.scenarios.get(&run.summary.scenario_id).map(|scenario| (scenario.farm_id, run.summary.clone())))
+                        .collect()
+                };
+                let farm_ids = self.viewable_farms(runs.iter().map(|(farm_id, _)| *farm_id), credentials);
+                json(200, &runs.iter().filter(|(farm_id, _)| farm_ids.contains(farm_id)).map(|(_, summary)| summary).collect::<Vec<_>>())
+            },
+            (Method::Get, ["runs", run_id]) => {
+                let run_id = id(run_id)?;
+                self.authorize_run(run_id, credentials, Operation::ViewReport)?;
+                let state = self.state.lock().unwrap();
+                let run = state.runs.get(&run_id).ok_or(not_found("run", run_id))?;
+                json(200, &run.summary)
+            },
+            (Method::Post, ["runs", run_id, "cancel"]) => {
+                let run_id = id(run_id)?;
+                self.authorize_run(run_id, credentials, Operation::RunSimulation)?;
+                self.cancel_run(run_id)
+            },
+            (Method::Get, ["runs", run_id, "report"]) => {
+                let run_id = id(run_id)?;
+                self.authorize_run(run_id, credentials, Operation::ViewReport)?;
+                let state = self.state.lock().unwrap();
+                json(200, Self::report(&state, run_id)?)
+            },
+            (Method::Get, ["runs", run_id, "events"]) => {
+                let run_id = id(run_id)?;
+                self.authorize_run(run_id, credentials, Operation::ViewReport)?;
+                let state = self.state.lock().unwrap();
+                let events: Vec<SeasonEvent> = Self::report(&state, run_id)?.seasons.iter()
+                    .enumerate()
+                    .flat_map(|(season, report)| report.events.iter().map(move |event| SeasonEvent { season: season + 1, event }))
+                    .collect();
+                json(200, &events)
+            },
+            _ => Err(ApiError(404, format!("no resource at {} {}", method, path))),
+        }
+    }
+
+    /// Registers a scenario if the user may run simulations on the farm.
+    fn submit_scenario(&self, body: &str, credentials: &Credentials) -> ApiResult {
+        let request: ScenarioRequest = serde_json::from_str(body).map_err(|err| ApiError(400, err.to_string()))?;
+        if request.seasons == 0 || request.seasons_per_year == 0 {
+            return Err(ApiError(422, "a scenario needs at least one season".to_string()));
+        }
+        self.registry.authorize(request.farm_id, credentials, Operation::RunSimulation)?;
+
+        let mut state = self.state.lock().unwrap();
+        state.next_scenario_id += 1;
+        let scenario = Scenario {
+            id: state.next_scenario_id,
+            farm_id: request.farm_id,
+            submitted_by: credentials.email.to_string(),
+            start_date: request.start_date,
+            seasons: request.seasons,
+            seasons_per_year: request.seasons_per_year,
+        };
+        state.scenarios.insert(scenario.id, scenario.clone());
+        json(201, &scenario)
+    }
+
+    /// Queues a run of the scenario on the worker pool.
+    fn start_run(&self, scenario_id: u32) -> ApiResult {
+        let scenario = self.state.lock().unwrap().scenarios.get(&scenario_id).cloned().ok_or(not_found("scenario", scenario_id))?;
+        let farm = setup_farm(&self.registry.get(scenario.farm_id)?)?;
+        let cancellation = Arc::new(AtomicBool::new(false));
+        let (progress, frames) = mpsc::channel();
+        let runner = RotationRunner::new(farm, scenario.seasons, scenario.seasons_per_year)
+            .starting_on(scenario.start_date)
+            .quiet()
+            .with_cancellation(cancellation.clone())
+            .with_progress(progress);
+
+        let mut state = self.state.lock().unwrap();
+        state.next_run_id += 1;
+        let summary = RunSummary {
+            id: state.next_run_id,
+            scenario_id,
+            status: RunStatus::Queued,
+            error: None,
+        };
+        state.runs.insert(summary.id, Run { summary: summary.clone(), cancellation, report: None, frames: Vec::new() });
+        drop(state);
+
+        let state = self.state.clone();
+        let run_id = summary.id;
+        self.pool.execute(Box::new(move || Self::execute(&state, run_id, runner, frames)));
+        json(202, &summary)
+    }
+
+    /// Carries out the run and records how it ended. A panic of the simulation fails the run.
+    fn execute(state: &Arc<Mutex<ServerState>>, run_id: u32, runner: RotationRunner, frames: mpsc::Receiver<DayFrame>) {
+        {
+            let mut state = state.lock().unwrap();
+            let Some(run) = state.runs.get_mut(&run_id) else { return };
+            if run.summary.status == RunStatus::Cancelled {
+                return;
+            }
+            run.summary.status = RunStatus::Running;
+        }
+        let collector = {
+            let state = state.clone();
+            thread::spawn(move || {
+                for frame in frames {
+                    if let Some(run) = state.lock().unwrap().runs.get_mut(&run_id) {
+                        run.frames.push(frame);
+                    }
+                }
+            })
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| runner.run()));
+        // The runner has gone with its end of the channel, so the collector stops after the last day.
+        let _ = collector.join();
+        let mut state = state.lock().unwrap();
+        let Some(run) = state.runs.get_mut(&run_id) else { return };
+        match result {
+            Ok(Ok(report)) => {
+                run.summary.status = RunStatus::Completed;
+                run.report = Some(report);
+            },
+            Ok(Err(AgroException::Cancelled)) => run.summary.status = RunStatus::Cancelled,
+            Ok(Err(err)) => {
+                run.summary.status = RunStatus::Failed;
+                run.summary.error = Some(err.to_string());
+            },
+            Err(panic) => {
+                run.summary.status = RunStatus::Failed;
+                run.summary.error = Some(format!("the simulation panicked: {}", panic_message(&*panic)));
+            },
+        }
+    }
+
+    /// Cancels a queued run straight away. A running one stops at the start of its next day.
+    fn cancel_run(&self, run_id: u32) -> ApiResult {
+        let mut state = self.state.lock().unwrap();
+        let run = state.runs.get_mut(&run_id).ok_or(not_found("run", run_id))?;
+        match run.summary.status {
+            RunStatus::Queued => run.summary.status = RunStatus::Cancelled,
+            RunStatus::Running => run.cancellation.store(true, Ordering::Relaxed),
+            status => return Err(ApiError(409, format!("run {} is already {:?}", run_id, status).to_lowercase())),
+        }
+        json(202, &run.summary)
+    }
+
+    fn report(state: &ServerState, run_id: u32) -> Result<&RotationReport, ApiError> {
+        let run = state.runs.get(&run_id).ok_or(not_found("run", run_id))?;
+        run.report.as_ref().ok_or(ApiError(409, format!("run {} has not completed", run_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use super::*;
+    use crate::setup::{demo_credentials, demo_farm};
+
+    /// A server on a registry of its own with the demo farm (1) and a farm of another owner (2).
+    struct TestServer {
+        server: ApiServer,
+        path: PathBuf,
+    }
+
+    impl TestServer {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("agro_mini_server_{}_{}.json", name, std::process::id()));
+            let registry = FarmRegistry::new(&path);
+            registry.create(demo_farm()).unwrap();
+            let mut other = demo_farm();
+            other.owner.email = "other@example.com".to_string();
+            other.security_code = "Other-code".to_string();
+            registry.create(other).unwrap();
+            Self { server: ApiServer::new(registry, 1), path }
+        }
+
+        fn get(&self, url: &str, credentials: Option<&Credentials>) -> Result<serde_json::Value, u16> {
+            self.request(Method::Get, url, "", credentials)
+        }
+
+        fn request(&self, method: Method, url: &str, body: &str, credentials: Option<&Credentials>) -> Result<serde_json::Value, u16> {
+            self.server.handle(&method, url, body, credentials)
+                .map(|(_, body)| serde_json::from_str(&body).unwrap())
+                .map_err(|ApiError(status, _)| status)
+        }
+    }
+
+    impl Drop for TestServer {
+        fn drop(&mut self) {
+            let mut lock = self.path.clone().into_os_string();
+            lock.push(".lock");
+            let _ = fs::remove_file(&self.path);
+            let _ = fs::remove_file(lock);
+        }
+    }
+
+    fn other_credentials() -> Credentials {
+        Credentials::new("other@example.com", "Other-code")
+    }
+
+    fn scenario(farm_id: u32) -> String {
+        format!(r#"{{"farm_id": {}, "start_date": "2025-04-15"}}"#, farm_id)
+    }
+
+    #[test]
+    fn scenarios_are_submitted_with_the_credentials_of_the_headers() {
+        let server = TestServer::new("submit");
+
+        assert_eq!(server.request(Method::Post, "/scenarios", &scenario(1), None), Err(401));
+        assert_eq!(server.request(Method::Post, "/scenarios", &scenario(1), Some(&other_credentials())), Err(401));
+        let scenario = server.request(Method::Post, "/scenarios", &scenario(1), Some(&demo_credentials())).unwrap();
+
+        assert_eq!(scenario["submitted_by"], demo_credentials().email);
+    }
+
+    #[test]
+    fn users_only_see_the_scenarios_and_runs_of_their_farms() {
+        let server = TestServer::new("lists");
+        server.request(Method::Post, "/scenarios", &scenario(1), Some(&demo_credentials())).unwrap();
+        server.request(Method::Post, "/scenarios", &scenario(2), Some(&other_credentials())).unwrap();
+        server.request(Method::Post, "/scenarios/1/runs", "", Some(&demo_credentials())).unwrap();
+
+        assert_eq!(server.get("/scenarios", None), Err(401));
+        let scenarios = server.get("/scenarios", Some(&demo_credentials())).unwrap();
+        assert_eq!(scenarios.as_array().unwrap().len(), 1);
+        assert_eq!(scenarios[0]["farm_id"], 1);
+        assert_eq!(server.get("/scenarios/2", Some(&demo_credentials())), Err(401));
+        assert_eq!(server.get("/scenarios/2", Some(&other_credentials())).unwrap()["farm_id"], 2);
+
+        assert_eq!(server.get("/runs", None), Err(401));
+        assert_eq!(server.get("/runs", Some(&demo_credentials())).unwrap().as_array().unwrap().len(), 1);
+        assert!(server.get("/runs", Some(&other_credentials())).unwrap().as_array().unwrap().is_empty());
+        assert_eq!(server.get("/runs/1", Some(&other_credentials())), Err(401));
+        assert_eq!(server.get("/runs/1", Some(&demo_credentials())).unwrap()["scenario_id"], 1);
+    }
+
+    #[test]
+    fn a_panicking_job_does_not_take_its_worker_down() {
+        let pool = WorkerPool::new(1);
+        let (done, finished) = mpsc::channel();
+
+        pool.execute(Box::new(|| panic!("the simulation went wrong")));
+        pool.execute(Box::new(move || done.send(()).unwrap()));
+
+        assert!(finished.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn panic_messages_are_reported() {
+        let panic = panic::catch_unwind(|| panic!("day {} went wrong", 3)).unwrap_err();
+        assert_eq!(panic_message(&*panic), "day 3 went wrong");
+        let panic = panic::catch_unwind(|| panic!("no day")).unwrap_err();
+        assert_eq!(panic_message(&*panic), "no day");
+    }
+
+    #[test]
+    fn streams_beyond_the_cap_are_refused() {
+        let server = TestServer::new("streams");
+        let slots: Vec<StreamSlot> = (0..MAX_STREAMS).map(|_| server.server.open_stream().unwrap()).collect();
+
+        assert!(matches!(server.server.open_stream(), Err(ApiError(503, _))));
+        drop(slots);
+        assert!(server.server.open_stream().is_ok());
+    }
+}
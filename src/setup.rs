@@ -1,7 +1,18 @@
-use crate::dto::{Address, Crop, Farm, FarmSize, Location, UserInfo};
+use crate::access::Credentials;
+use crate::calendar::Hemisphere;
+use crate::dto::{Address, Crop, Farm, FarmSize, Location, Plot, UserInfo};
+use crate::errors::AgroException;
+use crate::greenhouse::Greenhouse;
+use crate::inventory::SeedInventory;
+use crate::nursery::Nursery;
+use crate::registry::{FarmRecord, NewFarm};
+use crate::rotation::{CoverCrop, RotationPlan, RotationStep};
 use crate::seeds::SeedBag;
+use crate::storage::{PostHarvestStore, StorageFacility};
 
 
+const DEMO_SECURITY_CODE: &str = "Zsx12-00-RSA";
+
 fn get_location() -> Location {
     let address = Address {
         house_number: 1,
@@ -12,10 +23,10 @@ fn get_location() -> Location {
     };
 
     Location {
-        address: address,
+        address,
         is_virtual: false,
-        longitude: None,
-        latitude: None,
+        longitude: Some(-0.0716),
+        latitude: Some(51.5171),
     }
 }
 
@@ -30,22 +41,89 @@ fn get_user_info() -> UserInfo {
     }
 }
 
-pub fn setup_farm() -> Result<Farm, String> {
+/// Split the farm into two plots, each with its own rotation.
+fn get_plots() -> Result<Vec<Plot>, String> {
+    let north = RotationPlan::new(vec![
+        RotationStep::Crop("Pea".to_string()),
+        RotationStep::Crop("Broccoli".to_string()),
+        RotationStep::Fallow,
+    ])?;
+    let south = RotationPlan::new(vec![
+        RotationStep::Crop("Tomato".to_string()),
+        RotationStep::CoverCrop(CoverCrop::Clover),
+        RotationStep::Crop("Carrot".to_string()),
+    ])?;
+
+    Ok(vec![
+        Plot::new(1, "North", FarmSize { width: 10, length: 5 }, north),
+        Plot::new(2, "South", FarmSize { width: 10, length: 5 }, south).with_greenhouse(Greenhouse::default()),
+    ])
+}
+
+/// The demo farm, to be registered when the registry is still empty.
+pub fn demo_farm() -> NewFarm {
+    NewFarm {
+        owner: get_user_info(),
+        location: get_location(),
+        size: FarmSize {
+            width: 10,
+            length: 10,
+        },
+        security_code: DEMO_SECURITY_CODE.to_string(),
+    }
+}
+
+/// The demo farm as the registry keeps it once registered first, with its owner as only member.
+#[cfg(test)]
+pub fn demo_record() -> FarmRecord {
+    let farm = demo_farm();
+    FarmRecord {
+        id: 1,
+        members: std::collections::BTreeMap::from([(farm.owner.email.to_lowercase(), crate::access::Role::Owner)]),
+        owner: farm.owner,
+        location: farm.location,
+        size: farm.size,
+        security_code: crate::access::SecurityCode::new(&farm.security_code),
+        is_active: true,
+        is_trackable: None,
+    }
+}
+
+/// The demo farm's owner, with the farm's security code.
+pub fn demo_credentials() -> Credentials {
+    Credentials::new(&get_user_info().email, DEMO_SECURITY_CODE)
+}
+
+/// Builds a registered farm with the demo plots, seed shed and stores.
+/// Fails with `InvalidFarmError` if the farm has been deactivated.
+pub fn setup_farm(record: &FarmRecord) -> Result<Farm, AgroException> {
+    if !record.is_active {
+        return Err(AgroException::InvalidFarmError(format!("farm {} is deactivated", record.id)));
+    }
     let crops = load_crops_from_bags();
-    let location = get_location();
-    let size = FarmSize {
-        width: 10,
-        length: 10,
-    };
-    let owner = get_user_info();
+    let plots = get_plots().map_err(AgroException::InvalidFarmError)?;
+    let inventory = SeedInventory::import_csv("test_data/seeds.csv")?;
+    let location = record.location.clone();
+    let storage = PostHarvestStore::new(
+        vec![
+            StorageFacility::cooled("Cold room", 50.0, 4.0, 0.9),
+            StorageFacility::ambient("Barn", 200.0),
+        ],
+        Hemisphere::from_location(&location),
+    );
     Ok(Farm {
+        id: record.id,
         crops,
-        location: location,
-        size: size,
-        owner: owner,
-        security_code: "Zsx12-00-RSA".to_string(),
-        is_active: true,
-        is_trackable: None,
+        plots,
+        inventory,
+        storage,
+        nursery: Some(Nursery::new("Propagation house", 200)),
+        location,
+        size: record.size.clone(),
+        owner: record.owner.clone(),
+        security_code: record.security_code.clone(),
+        is_active: record.is_active,
+        is_trackable: record.is_trackable,
         is_plant_ready: None,
         is_ready_for_harvest: Some(false),
     })
@@ -59,11 +137,16 @@ fn load_crops_from_bags() -> Vec<Crop> {
     let seed_bags = SeedBag::tear_bags();
     let mut crops: Vec<Crop> = Vec::new();
     for bag in seed_bags {
-        let mut crop = Crop::new(bag.seed_type.get_botanica_name(), bag.seed_type.get_verbose_name(), bag.species, bag.description);
-        crop.is_gmo = bag.is_gmo.unwrap();
-        for _ in 0..bag.quantity {
-            crops.push(crop.clone());
-        }
+        crops.extend(crops_from_bag(&bag, bag.quantity, None));
     }
     crops
+}
+
+/// Turns `quantity` seeds of a bag into crops to be sown on `plot_id`
+pub fn crops_from_bag(bag: &SeedBag, quantity: u32, plot_id: Option<u32>) -> Vec<Crop> {
+    let mut crop = Crop::new(bag.seed_type.get_botanica_name(), bag.seed_type.get_verbose_name(), bag.species.to_string(), bag.description.clone());
+    crop.is_gmo = bag.is_gmo.unwrap_or(false);
+    crop.plot_id = plot_id;
+    crop.seed_lot = bag.lot.clone();
+    vec![crop; quantity as usize]
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+#![allow(dead_code)]
+
+use serde::Serialize;
+
+use crate::rotation::CoverCrop;
+
+
+/// Water (mm) held in the root zone between wilting point and field capacity.
+pub const AVAILABLE_WATER_CAPACITY: f32 = 100.0;
+
+/// Soil condition of a plot. It is carried over from one season to the next so
+/// that the effect of a crop (or of leaving the land fallow) shows up later.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SoilState {
+    /// Plant available nitrogen in kg/ha
+    pub nitrogen: f32,
+    /// Organic matter content in percent
+    pub organic_matter: f32,
+    pub ph: f32,
+    /// Fraction of field capacity (0.0 - 1.0)
+    pub moisture: f32,
+    /// 0.0 is a loose seedbed, 1.0 is fully compacted
+    pub compaction: f32,
+}
+
+impl Default for SoilState {
+    fn default() -> Self {
+        Self {
+            nitrogen: 60.0,
+            organic_matter: 3.0,
+            ph: 6.5,
+            moisture: 0.6,
+            compaction: 0.3,
+        }
+    }
+}
+
+impl SoilState {
+    /// Tillage loosens the soil and speeds up mineralisation of organic matter.
+    pub fn till(&mut self) {
+        let mineralised = self.organic_matter * 0.03;
+        self.organic_matter -= mineralised;
+        self.nitrogen += mineralised * 150.0;
+        self.compaction = 0.1;
+    }
+
+    /// A fallow season lets the soil rest. Some nitrogen is mineralised and some is leached.
+    pub fn fallow(&mut self) {
+        self.nitrogen = self.nitrogen * 0.9 + self.organic_matter * 6.0;
+        self.moisture = (self.moisture + 0.2).min(1.0);
+        self.compaction = (self.compaction - 0.1).max(0.0);
+    }
+
+    /// A cover crop protects the soil over the season and is incorporated at the end of it.
+    pub fn cover_crop(&mut self, cover_crop: &CoverCrop) {
+        self.nitrogen += cover_crop.nitrogen_credit();
+        self.organic_matter += cover_crop.organic_matter_credit();
+        self.compaction = (self.compaction - 0.15).max(0.0);
+    }
+
+    /// Water lost to evapotranspiration (mm).
+    pub fn evaporate(&mut self, water: f32) {
+        self.moisture = (self.moisture - water / AVAILABLE_WATER_CAPACITY).max(0.0);
+    }
+
+    /// Rain or irrigation (mm). Anything above field capacity drains away.
+    pub fn add_water(&mut self, water: f32) {
+        self.moisture = (self.moisture + water / AVAILABLE_WATER_CAPACITY).min(1.0);
+    }
+
+    /// Removes the nitrogen taken up by a crop.
+    pub fn uptake(&mut self, nitrogen: f32) {
+        self.nitrogen = (self.nitrogen - nitrogen).max(0.0);
+        self.compaction = (self.compaction + 0.1).min(1.0);
+    }
+
+    /// Crop residues left on the plot after harvest.
+    pub fn add_residue(&mut self, nitrogen: f32, organic_matter: f32) {
+        self.nitrogen += nitrogen;
+        self.organic_matter += organic_matter;
+    }
+
+    /// Yield multiplier (0.4 - 1.0) given how much of the crop's nitrogen demand the soil can meet.
+    pub fn nitrogen_factor(&self, demand: f32) -> f32 {
+        if demand <= 0.0 {
+            return 1.0;
+        }
+        (0.4 + 0.6 * self.nitrogen / demand).min(1.0)
+    }
+}
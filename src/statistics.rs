@@ -0,0 +1,178 @@
+#![allow(dead_code)]
+
+use serde::Serialize;
+
+
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Sample variance, with `n - 1` degrees of freedom.
+pub fn variance(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64
+}
+
+pub fn standard_deviation(values: &[f64]) -> f64 {
+    variance(values).sqrt()
+}
+
+/// Paired Student's t-test of the differences `variant - baseline`, replicate by replicate.
+/// Pairing is what makes runs under common random numbers comparable with few replicates.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct PairedTTest {
+    pub replicates: usize,
+    pub mean_difference: f64,
+    pub standard_error: f64,
+    pub t: f64,
+    /// Two-sided
+    pub p_value: f64,
+}
+
+impl PairedTTest {
+    pub fn new(baseline: &[f64], variant: &[f64]) -> Self {
+        let differences: Vec<f64> = baseline.iter().zip(variant.iter()).map(|(baseline, variant)| variant - baseline).collect();
+        let replicates = differences.len();
+        let mean_difference = mean(&differences);
+        let standard_error = (variance(&differences) / replicates.max(1) as f64).sqrt();
+        let (t, p_value) = match (replicates, standard_error > 0.0) {
+            (0 | 1, _) => (0.0, 1.0),
+            (_, true) => {
+                let t = mean_difference / standard_error;
+                (t, student_t_p_value(t, (replicates - 1) as f64))
+            },
+            // Every replicate moved by the same amount: no doubt left, unless it did not move at all.
+            (_, false) if mean_difference == 0.0 => (0.0, 1.0),
+            (_, false) => (mean_difference.signum() * f64::INFINITY, 0.0),
+        };
+        Self { replicates, mean_difference, standard_error, t, p_value }
+    }
+
+    pub fn is_significant(&self, level: f64) -> bool {
+        self.p_value < level
+    }
+}
+
+/// Two-sided p-value of `t` under Student's t distribution with `df` degrees of freedom.
+pub fn student_t_p_value(t: f64, df: f64) -> f64 {
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t)).clamp(0.0, 1.0)
+}
+
+/// Natural logarithm of the gamma function (Lanczos approximation).
+pub fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let series = COEFFICIENTS.iter().enumerate()
+        .fold(1.000000000190015, |total, (idx, coefficient)| total + coefficient / (x + 1.0 + idx as f64));
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+/// Regularized incomplete beta function I_x(a, b).
+pub fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges quickly on this side; use the symmetry otherwise.
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Continued fraction of the incomplete beta function, by the modified Lentz method.
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-30;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut fraction = d;
+    for m in 1..200 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            fraction *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+    fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() < tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    #[test]
+    fn p_values_match_the_tables_of_the_t_distribution() {
+        // Two-sided 5% critical values.
+        assert_close(student_t_p_value(12.706, 1.0), 0.05, 1e-4);
+        assert_close(student_t_p_value(2.228, 10.0), 0.05, 1e-4);
+        assert_close(student_t_p_value(2.086, 20.0), 0.05, 1e-4);
+        // Two-sided 1% critical value.
+        assert_close(student_t_p_value(2.750, 30.0), 0.01, 1e-4);
+        // The t distribution with 1 degree of freedom is the Cauchy distribution: P(|T| > 1) = 1/2.
+        assert_close(student_t_p_value(1.0, 1.0), 0.5, 1e-9);
+        assert_close(student_t_p_value(0.0, 5.0), 1.0, 1e-12);
+        assert_close(student_t_p_value(-2.228, 10.0), student_t_p_value(2.228, 10.0), 1e-12);
+    }
+
+    #[test]
+    fn paired_t_test_of_known_differences() {
+        // Differences 1, 2, 2, 2: mean 1.75, standard error 0.25, so t = 7 with 3 degrees of freedom.
+        let test = PairedTTest::new(&[1.0, 2.0, 3.0, 4.0], &[2.0, 4.0, 5.0, 6.0]);
+
+        assert_eq!(test.replicates, 4);
+        assert_close(test.mean_difference, 1.75, 1e-12);
+        assert_close(test.standard_error, 0.25, 1e-12);
+        assert_close(test.t, 7.0, 1e-12);
+        // Closed form for 3 degrees of freedom: 1 - 2/π (atan(t/√3) + (t/√3) / (1 + t²/3)).
+        assert_close(test.p_value, 0.005986255697707, 1e-9);
+        assert!(test.is_significant(0.05));
+    }
+
+    #[test]
+    fn paired_t_test_without_spread() {
+        let unchanged = PairedTTest::new(&[1.0, 2.0, 3.0], &[1.0, 2.0, 3.0]);
+        assert_eq!((unchanged.t, unchanged.p_value), (0.0, 1.0));
+
+        let shifted = PairedTTest::new(&[1.0, 2.0, 3.0], &[2.0, 3.0, 4.0]);
+        assert_eq!((shifted.t, shifted.p_value), (f64::INFINITY, 0.0));
+    }
+}
@@ -0,0 +1,347 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use chrono::{Datelike, Days, NaiveDate};
+
+use crate::calendar::Hemisphere;
+use crate::errors::AgroException;
+use crate::seeds::SeedType;
+use crate::weather_service::Climate;
+
+
+/// Relative humidity of a barn or shed without climate control.
+const AMBIENT_HUMIDITY: f32 = 0.65;
+/// Days the produce is kept before it is sold, unless set per crop.
+const DEFAULT_HOLDING_DAYS: u32 = 7;
+/// Share of the produce lost by the end of its shelf life when stored at its optimum.
+const SHELF_LIFE_LOSS: f32 = 0.1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FacilityKind {
+    /// Follows the outside temperature of the season
+    Ambient,
+    /// Kept at a set temperature (°C) and relative humidity (0.0 - 1.0)
+    Cooled { temperature: f32, humidity: f32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageFacility {
+    pub name: String,
+    pub kind: FacilityKind,
+    pub capacity_kg: f32,
+}
+
+impl StorageFacility {
+    pub fn ambient(name: &str, capacity_kg: f32) -> Self {
+        Self { name: name.to_string(), kind: FacilityKind::Ambient, capacity_kg }
+    }
+
+    pub fn cooled(name: &str, capacity_kg: f32, temperature: f32, humidity: f32) -> Self {
+        Self { name: name.to_string(), kind: FacilityKind::Cooled { temperature, humidity }, capacity_kg }
+    }
+
+    /// Temperature (°C) and relative humidity inside the facility on `date`.
+    pub fn conditions_on(&self, date: NaiveDate, hemisphere: Hemisphere) -> (f32, f32) {
+        match self.kind {
+            FacilityKind::Ambient => (Climate::from_month(date.month(), hemisphere).mean_temperature(), AMBIENT_HUMIDITY),
+            FacilityKind::Cooled { temperature, humidity } => (temperature, humidity),
+        }
+    }
+}
+
+/// How long a crop keeps after harvest and in which conditions it keeps best.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostHarvestProfile {
+    /// Days the produce keeps when stored at its optimum, losing `SHELF_LIFE_LOSS` of its weight by then
+    pub shelf_life_days: f32,
+    pub optimum_temperature: f32,
+    pub optimum_humidity: f32,
+    /// Below this temperature (°C) the produce suffers chilling injury
+    pub chilling_threshold: Option<f32>,
+}
+
+impl PostHarvestProfile {
+    pub fn for_seed_type(seed_type: &SeedType) -> Self {
+        match seed_type {
+            SeedType::Sunflower(..) => Self {
+                shelf_life_days: 365.0,
+                optimum_temperature: 10.0,
+                optimum_humidity: 0.5,
+                chilling_threshold: None,
+            },
+            SeedType::Pea(..) => Self {
+                shelf_life_days: 10.0,
+                optimum_temperature: 0.0,
+                optimum_humidity: 0.95,
+                chilling_threshold: None,
+            },
+            SeedType::Carrot(..) => Self {
+                shelf_life_days: 150.0,
+                optimum_temperature: 0.0,
+                optimum_humidity: 0.98,
+                chilling_threshold: None,
+            },
+            SeedType::Tomato(..) => Self {
+                shelf_life_days: 14.0,
+                optimum_temperature: 12.0,
+                optimum_humidity: 0.9,
+                chilling_threshold: Some(10.0),
+            },
+            SeedType::Broccoli(..) => Self {
+                shelf_life_days: 21.0,
+                optimum_temperature: 0.0,
+                optimum_humidity: 0.95,
+                chilling_threshold: None,
+            },
+        }
+    }
+
+    /// Share of the stored produce lost in a day at the optimum, so that `SHELF_LIFE_LOSS` is gone
+    /// by the end of the shelf life.
+    pub fn base_spoilage(&self) -> f32 {
+        1.0 - (1.0 - SHELF_LIFE_LOSS).powf(1.0 / self.shelf_life_days)
+    }
+
+    /// Share of the stored produce lost in a day. Spoilage doubles for every 10 °C above the optimum,
+    /// grows with the distance from the optimum humidity and with chilling injury.
+    pub fn daily_spoilage(&self, temperature: f32, humidity: f32) -> f32 {
+        let temperature_factor = 2.0_f32.powf((temperature - self.optimum_temperature).max(0.0) / 10.0);
+        let humidity_factor = 1.0 + 3.0 * (humidity - self.optimum_humidity).abs();
+        let chilling_factor = match self.chilling_threshold {
+            Some(threshold) if temperature < threshold => 1.0 + 0.5 * (threshold - temperature),
+            _ => 1.0,
+        };
+        (self.base_spoilage() * temperature_factor * humidity_factor * chilling_factor).min(1.0)
+    }
+}
+
+/// Produce of one harvest kept in a facility until it is sold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredLot {
+    pub crop_name: String,
+    pub facility: String,
+    pub stored_on: NaiveDate,
+    pub sold_on: NaiveDate,
+    pub stored_kg: f32,
+    /// Produce left at the end of each day in storage
+    pub remaining_kg: Vec<f32>,
+}
+
+impl StoredLot {
+    /// Produce in the facility on `date`.
+    pub fn kg_on(&self, date: NaiveDate) -> f32 {
+        if date < self.stored_on || date >= self.sold_on {
+            return 0.0;
+        }
+        let day = (date - self.stored_on).num_days() as usize;
+        if day == 0 {
+            self.stored_kg
+        } else {
+            self.remaining_kg[day - 1]
+        }
+    }
+
+    pub fn sold_kg(&self) -> f32 {
+        self.remaining_kg.last().copied().unwrap_or(self.stored_kg)
+    }
+
+    pub fn lost_kg(&self) -> f32 {
+        self.stored_kg - self.sold_kg()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sale {
+    pub date: NaiveDate,
+    pub crop_name: String,
+    pub kg: f32,
+    pub revenue: f32,
+}
+
+/// Facilities where the harvest is kept, and what went through them.
+#[derive(Debug, Clone)]
+pub struct PostHarvestStore {
+    pub facilities: Vec<StorageFacility>,
+    hemisphere: Hemisphere,
+    /// Days each crop is held before it is sold, by lower case verbose name
+    holding_days: BTreeMap<String, u32>,
+    pub lots: Vec<StoredLot>,
+    pub sales: Vec<Sale>,
+}
+
+impl Default for PostHarvestStore {
+    fn default() -> Self {
+        Self::new(
+            vec![
+                StorageFacility::cooled("Cold room", 500.0, 4.0, 0.9),
+                StorageFacility::ambient("Barn", 2000.0),
+            ],
+            Hemisphere::Northern,
+        )
+    }
+}
+
+impl PostHarvestStore {
+    pub fn new(facilities: Vec<StorageFacility>, hemisphere: Hemisphere) -> Self {
+        Self {
+            facilities,
+            hemisphere,
+            holding_days: BTreeMap::new(),
+            lots: Vec::new(),
+            sales: Vec::new(),
+        }
+    }
+
+    pub fn hold_for(&mut self, crop_name: &str, days: u32) {
+        self.holding_days.insert(crop_name.to_lowercase(), days);
+    }
+
+    pub fn holding_days(&self, crop_name: &str) -> u32 {
+        self.holding_days.get(&crop_name.to_lowercase()).copied().unwrap_or(DEFAULT_HOLDING_DAYS)
+    }
+
+    /// Produce in a facility on `date`.
+    pub fn occupancy(&self, facility: &str, date: NaiveDate) -> f32 {
+        self.lots.iter()
+            .filter(|lot| lot.facility == facility)
+            .map(|lot| lot.kg_on(date))
+            .sum()
+    }
+
+    /// Puts a harvest into the facility where it keeps best and has room for the whole holding period,
+    /// then sells what is left at the end of it for `price` per kg.
+    pub fn store(&mut self, crop_name: &str, kg: f32, date: NaiveDate, price: f32) -> Result<StoredLot, AgroException> {
+        let seed_type = SeedType::from_str(crop_name).map_err(AgroException::InvalidCropError)?;
+        let profile = PostHarvestProfile::for_seed_type(&seed_type);
+        let holding_days = self.holding_days(crop_name);
+
+        let lot = self.facilities.iter()
+            .map(|facility| self.simulate(facility, &profile, crop_name, kg, date, holding_days))
+            .filter(|lot| self.has_room(lot))
+            .min_by(|first, second| first.lost_kg().total_cmp(&second.lost_kg()))
+            .ok_or(AgroException::StorageFull(format!("no room for {:.1} kg of {} on {}", kg, crop_name, date)))?;
+
+        self.sales.push(Sale {
+            date: lot.sold_on,
+            crop_name: crop_name.to_string(),
+            kg: lot.sold_kg(),
+            revenue: lot.sold_kg() * price,
+        });
+        self.lots.push(lot.clone());
+        Ok(lot)
+    }
+
+    fn simulate(&self, facility: &StorageFacility, profile: &PostHarvestProfile, crop_name: &str, kg: f32, date: NaiveDate, days: u32) -> StoredLot {
+        let mut remaining = kg;
+        let mut remaining_kg = Vec::new();
+        for day in 0..days {
+            let (temperature, humidity) = facility.conditions_on(date + Days::new(day as u64), self.hemisphere);
+            remaining *= 1.0 - profile.daily_spoilage(temperature, humidity);
+            remaining_kg.push(remaining);
+        }
+        StoredLot {
+            crop_name: crop_name.to_string(),
+            facility: facility.name.to_string(),
+            stored_on: date,
+            sold_on: date + Days::new(days as u64),
+            stored_kg: kg,
+            remaining_kg,
+        }
+    }
+
+    fn has_room(&self, lot: &StoredLot) -> bool {
+        let capacity = self.facilities.iter()
+            .find(|facility| facility.name == lot.facility)
+            .map_or(0.0, |facility| facility.capacity_kg);
+        lot.stored_on.iter_days()
+            .take_while(|date| *date < lot.sold_on)
+            .all(|date| self.occupancy(&lot.facility, date) + lot.kg_on(date) <= capacity)
+    }
+
+    /// Produce of a crop lost in storage up to and including `date`.
+    pub fn losses_until(&self, crop_name: &str, date: NaiveDate) -> f32 {
+        self.lots.iter()
+            .filter(|lot| lot.crop_name.eq_ignore_ascii_case(crop_name) && lot.stored_on <= date)
+            .map(|lot| {
+                let days = ((date - lot.stored_on).num_days() as usize + 1).min(lot.remaining_kg.len());
+                match days {
+                    0 => 0.0,
+                    _ => lot.stored_kg - lot.remaining_kg[days - 1],
+                }
+            })
+            .sum()
+    }
+
+    /// Cumulative storage losses of a crop on every day produce was kept.
+    pub fn loss_history(&self, crop_name: &str) -> Vec<(NaiveDate, f32)> {
+        let first = self.lots.iter().filter(|lot| lot.crop_name.eq_ignore_ascii_case(crop_name)).map(|lot| lot.stored_on).min();
+        let last = self.lots.iter().filter(|lot| lot.crop_name.eq_ignore_ascii_case(crop_name)).map(|lot| lot.sold_on).max();
+        match (first, last) {
+            (Some(first), Some(last)) => first.iter_days()
+                .take_while(|date| *date < last)
+                .map(|date| (date, self.losses_until(crop_name, date)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() < tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    fn profile(crop_name: &str) -> PostHarvestProfile {
+        PostHarvestProfile::for_seed_type(&SeedType::from_str(crop_name).unwrap())
+    }
+
+    #[test]
+    fn a_tenth_is_lost_over_the_shelf_life_at_the_optimum() {
+        for crop_name in ["Pea", "Broccoli", "Carrot", "Tomato"] {
+            let profile = profile(crop_name);
+            let daily = profile.daily_spoilage(profile.optimum_temperature, profile.optimum_humidity);
+
+            assert_close(daily, profile.base_spoilage(), 1e-7);
+            assert_close((1.0 - daily).powf(profile.shelf_life_days), 1.0 - SHELF_LIFE_LOSS, 1e-4);
+        }
+        // Peas keep ten days: about 1% a day.
+        assert_close(profile("Pea").base_spoilage(), 0.0105, 1e-4);
+    }
+
+    #[test]
+    fn spoilage_doubles_every_ten_degrees_above_the_optimum() {
+        let profile = profile("Broccoli");
+        let optimum = profile.daily_spoilage(0.0, profile.optimum_humidity);
+
+        assert_close(profile.daily_spoilage(10.0, profile.optimum_humidity), 2.0 * optimum, 1e-6);
+        assert_close(profile.daily_spoilage(20.0, profile.optimum_humidity), 4.0 * optimum, 1e-6);
+        // Colder than the optimum is no better and, without a chilling threshold, no worse.
+        assert_close(profile.daily_spoilage(-2.0, profile.optimum_humidity), optimum, 1e-7);
+    }
+
+    #[test]
+    fn tomatoes_suffer_chilling_injury() {
+        let profile = profile("Tomato");
+        let optimum = profile.daily_spoilage(profile.optimum_temperature, profile.optimum_humidity);
+
+        // 4 °C is 6 °C below the chilling threshold.
+        assert_close(profile.daily_spoilage(4.0, profile.optimum_humidity), 4.0 * optimum, 1e-6);
+    }
+
+    #[test]
+    fn peas_lose_little_in_a_week_in_the_cold_room() {
+        let mut store = PostHarvestStore::default();
+        let date = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+
+        let lot = store.store("Pea", 10.0, date, 2.0).unwrap();
+
+        assert_eq!(lot.facility, "Cold room");
+        assert_eq!(lot.remaining_kg.len(), DEFAULT_HOLDING_DAYS as usize);
+        // 4 °C and 90% humidity spoil peas about 1.5 times as fast as their optimum.
+        assert!(lot.lost_kg() > 0.7 && lot.lost_kg() < 1.2, "lost {} kg", lot.lost_kg());
+        assert_close(store.losses_until("Pea", lot.sold_on), lot.lost_kg(), 1e-5);
+    }
+}
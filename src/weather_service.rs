@@ -1,10 +1,17 @@
 #![allow(dead_code)]
 
+use std::collections::BTreeMap;
+use chrono::{Datelike, NaiveDate};
+use csv::ReaderBuilder;
 use serde::{Deserialize, Serialize};
 use rand::prelude::*;
 
-#[derive(Debug, PartialEq)]
+use crate::calendar::Hemisphere;
+use crate::errors::AgroException;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
 pub enum WeatherCondition {
+    #[default]
     Sunny,
     Cloudy,
     Rainy,
@@ -12,12 +19,6 @@ pub enum WeatherCondition {
     Stormy
 }
 
-impl Default for WeatherCondition {
-    fn default() -> Self {
-        Self::Sunny
-    }
-}
-
 impl WeatherCondition {
     fn get_weather_condition(&self) -> String {
         match self {
@@ -40,6 +41,64 @@ impl WeatherCondition {
         }
     }
 
+    /// Share of the clear-sky radiation that reaches the ground.
+    pub fn radiation_factor(&self) -> f32 {
+        match self {
+            WeatherCondition::Sunny => 1.0,
+            WeatherCondition::Cloudy => 0.6,
+            WeatherCondition::Rainy => 0.4,
+            WeatherCondition::Snowy => 0.3,
+            WeatherCondition::Stormy => 0.3,
+        }
+    }
+
+    /// Deviation (°C) of the day from the seasonal mean temperature.
+    pub fn temperature_offset(&self) -> f32 {
+        match self {
+            WeatherCondition::Sunny => 3.0,
+            WeatherCondition::Cloudy => 0.0,
+            WeatherCondition::Rainy => -1.0,
+            WeatherCondition::Snowy => -8.0,
+            WeatherCondition::Stormy => -2.0,
+        }
+    }
+
+    pub fn is_wet(&self) -> bool {
+        *self == WeatherCondition::Rainy || *self == WeatherCondition::Stormy
+    }
+
+    /// Draws the weather of a day, weighted by what is typical for the season.
+    pub fn generate_for(climate: &Climate, rng: &mut impl Rng) -> WeatherCondition {
+        Self::generate_with(climate, 1.0, rng)
+    }
+
+    /// Like `generate_for`, with rainy and stormy days `wet_weather_factor` times as likely.
+    pub fn generate_with(climate: &Climate, wet_weather_factor: f32, rng: &mut impl Rng) -> WeatherCondition {
+        // Sunny, Cloudy, Rainy, Snowy, Stormy
+        let mut weights: [f32; 5] = match climate {
+            Climate::Spring(..) => [30.0, 35.0, 25.0, 3.0, 7.0],
+            Climate::Summer(..) => [50.0, 25.0, 17.0, 0.0, 8.0],
+            Climate::Autumn(..) => [20.0, 35.0, 33.0, 2.0, 10.0],
+            Climate::Winter(..) => [15.0, 35.0, 25.0, 15.0, 10.0],
+        };
+        weights[2] *= wet_weather_factor.max(0.0);
+        weights[4] *= wet_weather_factor.max(0.0);
+        let mut draw = rng.gen_range(0.0..weights.iter().sum::<f32>());
+        for (idx, weight) in weights.iter().enumerate() {
+            if draw < *weight {
+                return match idx {
+                    0 => WeatherCondition::Sunny,
+                    1 => WeatherCondition::Cloudy,
+                    2 => WeatherCondition::Rainy,
+                    3 => WeatherCondition::Snowy,
+                    _ => WeatherCondition::Stormy,
+                };
+            }
+            draw -= weight;
+        }
+        WeatherCondition::default()
+    }
+
     pub fn generate_random_weather_condition() -> WeatherCondition {
         let mut rng = rand::thread_rng();
         let random = rng.gen_range(0..=4);
@@ -54,6 +113,93 @@ impl WeatherCondition {
     }
 }
 
+/// Water a rainy or stormy day brings (mm)
+const RAINFALL: f32 = 15.0;
+
+/// Knobs on the weather generator, e.g. to test how much the outcome of a run hinges on the weather.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct WeatherParameters {
+    /// Water a rainy or stormy day brings (mm)
+    pub rainfall_mm: f32,
+    /// Added to the seasonal mean temperature (°C)
+    pub temperature_shift: f32,
+    /// How many times as likely rainy and stormy days are
+    pub wet_weather_factor: f32,
+}
+
+impl Default for WeatherParameters {
+    fn default() -> Self {
+        Self {
+            rainfall_mm: RAINFALL,
+            temperature_shift: 0.0,
+            wet_weather_factor: 1.0,
+        }
+    }
+}
+
+/// Weather of every day over a period, drawn once for a region so all of its farms share it.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct WeatherSeries {
+    days: BTreeMap<NaiveDate, WeatherCondition>,
+}
+
+impl WeatherSeries {
+    /// Draws the weather of every day from `first_date` to `last_date`, both included.
+    pub fn generate(first_date: NaiveDate, last_date: NaiveDate, hemisphere: Hemisphere, rng: &mut impl Rng) -> Self {
+        let days = first_date.iter_days()
+            .take_while(|date| *date <= last_date)
+            .map(|date| (date, WeatherCondition::generate_for(&Climate::from_month(date.month(), hemisphere), rng)))
+            .collect();
+        Self { days }
+    }
+
+    /// Reads a forecast from a CSV file with the columns `date,condition`, e.g. `2025-03-01,rainy`.
+    pub fn import_csv(path: &str) -> Result<Self, AgroException> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .trim(csv::Trim::All)
+            .from_path(path)
+            .map_err(|err| AgroException::InvalidForecast(err.to_string()))?;
+        let mut days = BTreeMap::new();
+        for (line, result) in reader.records().enumerate() {
+            let record = result.map_err(|err| AgroException::InvalidForecast(err.to_string()))?;
+            let invalid = |msg: String| AgroException::InvalidForecast(format!("line {}: {}", line + 2, msg));
+            let date = NaiveDate::parse_from_str(record.get(0).unwrap_or_default(), "%Y-%m-%d")
+                .map_err(|err| invalid(err.to_string()))?;
+            let condition = record.get(1).unwrap_or_default();
+            let weather = [
+                WeatherCondition::Sunny,
+                WeatherCondition::Cloudy,
+                WeatherCondition::Rainy,
+                WeatherCondition::Snowy,
+                WeatherCondition::Stormy,
+            ].into_iter()
+                .find(|weather| weather.get_weather_condition().eq_ignore_ascii_case(condition))
+                .ok_or_else(|| invalid(format!("Invalid weather condition {}", condition)))?;
+            days.insert(date, weather);
+        }
+        Ok(Self { days })
+    }
+
+    /// Takes over the days `forecast` covers.
+    pub fn overlay(&mut self, forecast: &WeatherSeries) {
+        self.days.extend(forecast.days.iter().map(|(date, weather)| (*date, *weather)));
+    }
+
+    pub fn on(&self, date: NaiveDate) -> Option<WeatherCondition> {
+        self.days.get(&date).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.days.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.days.is_empty()
+    }
+}
+
 #[derive(Debug)]
 struct WeatherData {
     temperature: f32,
@@ -86,10 +232,45 @@ impl WeatherData {
 
 }
 
-enum Climate {
+#[derive(Debug, PartialEq)]
+pub enum Climate {
     // E.g. Autumn(WeatherCondition::Cloudy, 21.),
     Autumn(WeatherCondition, f32),
     Spring(WeatherCondition, f32), 
     Summer(WeatherCondition, f32), 
     Winter(WeatherCondition, f32), 
 }
+
+impl Climate {
+    /// Meteorological season for a month, with its typical weather and mean temperature (°C).
+    pub fn from_month(month: u32, hemisphere: Hemisphere) -> Self {
+        let month = match hemisphere {
+            Hemisphere::Northern => month,
+            Hemisphere::Southern => (month + 5) % 12 + 1,
+        };
+        match month {
+            3..=5 => Climate::Spring(WeatherCondition::Cloudy, 11.0),
+            6..=8 => Climate::Summer(WeatherCondition::Sunny, 18.0),
+            9..=11 => Climate::Autumn(WeatherCondition::Rainy, 12.0),
+            _ => Climate::Winter(WeatherCondition::Snowy, 5.0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Climate::Autumn(..) => "Autumn",
+            Climate::Spring(..) => "Spring",
+            Climate::Summer(..) => "Summer",
+            Climate::Winter(..) => "Winter",
+        }
+    }
+
+    pub fn mean_temperature(&self) -> f32 {
+        match self {
+            Climate::Autumn(_, temperature)
+            | Climate::Spring(_, temperature)
+            | Climate::Summer(_, temperature)
+            | Climate::Winter(_, temperature) => *temperature,
+        }
+    }
+}